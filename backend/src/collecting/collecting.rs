@@ -1,63 +1,390 @@
+use std::sync::Arc;
+
 use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use tokio::task::JoinSet;
 
 use crate::{
+    Blockhashes, Rpc, Websocket,
+    config::CommitmentConfig,
     errors::errors::Error,
-    rpc::{read::get_multiple_accounts, send::send_transaction},
-    txn_factory::{blockhash::get_blockhash, transfer::build_sol_transfer},
+    rpc::{
+        read::{MAX_ACCOUNTS_PER_REQUEST, get_multiple_accounts},
+        send::send_and_confirm,
+    },
+    txn_factory::{
+        rent::{PlannedTransfers, check_rent_exemption},
+        simulation::{SimulationReport, simulate},
+        transfer::build_sol_transfer,
+        util::LAMPORTS_PER_SIGNATURE,
+    },
 };
 
-pub async fn collect(
-    rpc_url: &str,
-    source_wallets: Vec<Keypair>,
-    destination: Pubkey,
-    total_lamports_to_collect: u64,
-) -> Result<(), Error> {
+/// How much each source wallet gives.
+#[derive(Debug, Clone, Copy)]
+pub enum CollectAmount {
+    /// The total, split evenly over the wallets.
+    Even(u64),
+    /// The same amount from every wallet.
+    Fixed(u64),
+    /// Everything the wallets can give.
+    All,
+    /// The total, split in proportion to what each wallet can give.
+    Proportional(u64),
+}
+
+impl CollectAmount {
+    /// Each wallet's share given what it can give, fails if a wallet can't give its share.
+    pub fn shares(&self, available: &[(String, u64)]) -> Result<Vec<u64>, String> {
+        if available.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let shares: Vec<u64> = match *self {
+            CollectAmount::Even(total) => vec![total / available.len() as u64; available.len()],
+            CollectAmount::Fixed(amount) => vec![amount; available.len()],
+            CollectAmount::All => available.iter().map(|(_, a)| *a).collect(),
+            CollectAmount::Proportional(total) => {
+                let held: u128 = available.iter().map(|(_, a)| *a as u128).sum();
+                if total as u128 > held {
+                    return Err(format!(
+                        "Need {} to collect but the wallets only have {}.",
+                        total, held
+                    ));
+                }
+
+                let mut shares: Vec<u64> = available
+                    .iter()
+                    .map(|(_, a)| (total as u128 * *a as u128 / held.max(1)) as u64)
+                    .collect();
+                // Rounding down leaves less than one unit per wallet, handed to wallets with room.
+                let mut remainder = total - shares.iter().sum::<u64>();
+                for (share, (_, a)) in shares.iter_mut().zip(available.iter()) {
+                    if remainder == 0 {
+                        break;
+                    }
+                    if *share < *a {
+                        *share += 1;
+                        remainder -= 1;
+                    }
+                }
+                shares
+            }
+        };
+
+        for (share, (pubkey, a)) in shares.iter().zip(available.iter()) {
+            if share > a {
+                return Err(format!(
+                    "Need {} to collect but got {} inside wallet {}.",
+                    share, a, pubkey
+                ));
+            }
+        }
+
+        Ok(shares)
+    }
+}
+
+/// The wallets a collection takes from, and the wallet paying their fees if it is sponsored.
+pub struct CollectSources {
+    pub wallets: Vec<Keypair>,
+    pub fee_payer: Option<Keypair>,
+}
+
+/// Lamports of every source wallet, failing if one is no longer a plain system account.
+pub async fn source_wallet_lamports(
+    rpc: &Rpc,
+    source_wallets: &[Keypair],
+    commitment: CommitmentConfig,
+) -> Result<Vec<(String, u64)>, Error> {
     let source_pubkeys: Vec<String> = source_wallets
         .iter()
         .map(|w| w.pubkey().to_string())
         .collect();
-    let balance_result = get_multiple_accounts(&rpc_url, "collect_sol", &source_pubkeys).await?;
 
-    let lamports_to_collect_per_wallet = total_lamports_to_collect / source_pubkeys.len() as u64;
+    let mut balances = Vec::new();
+    for chunk in source_pubkeys.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+        let accounts =
+            get_multiple_accounts(rpc, "collect_sol", &chunk.to_vec(), None, commitment.read)
+                .await?;
 
-    let latest_hash = match get_blockhash(&rpc_url, "collect_sol").await {
-        Ok(v) => v,
-        Err(err) => {
-            eprintln!("failed to get blockhash, {}", err);
-            return Err(err);
+        for (pubkey, account) in chunk.iter().zip(accounts.iter()) {
+            let mut balance = 0u64;
+            if let Some(wallet_account) = account {
+                if !wallet_account.is_plain_system_account() {
+                    return Err(Error::CompromisedAccount(format!(
+                        "Wallet {} is owned by {} and holds {} bytes of data.",
+                        pubkey, wallet_account.owner, wallet_account.space
+                    )));
+                }
+                balance = wallet_account.lamports;
+            }
+            balances.push((pubkey.clone(), balance));
         }
-    };
+    }
 
-    let mut txns: Vec<String> = Vec::new();
+    Ok(balances)
+}
+
+/// Checks every source wallet can give its share and returns the lamports to collect per wallet.
+/// What a wallet can give is its balance minus the fee of its transfer, or all of it when a
+/// `fee_payer` sponsors the fees. No wallet, nor the destination, may be left below the
+/// rent-exempt minimum without being emptied.
+async fn lamports_per_source_wallet(
+    rpc: &Rpc,
+    sources: &CollectSources,
+    destination: &Pubkey,
+    amount: CollectAmount,
+    commitment: CommitmentConfig,
+) -> Result<Vec<u64>, Error> {
+    let available: Vec<(String, u64)> = source_wallet_lamports(rpc, &sources.wallets, commitment)
+        .await?
+        .into_iter()
+        .map(|(pubkey, lamports)| match sources.fee_payer {
+            Some(_) => (pubkey, lamports),
+            None => (pubkey, lamports.saturating_sub(LAMPORTS_PER_SIGNATURE)),
+        })
+        .collect();
+
+    let shares = amount.shares(&available).map_err(Error::InsufficientSol)?;
+
+    let transfers: Vec<[(Pubkey, u64); 1]> = shares.iter().map(|s| [(*destination, *s)]).collect();
+    let planned: Vec<PlannedTransfers> = sources
+        .wallets
+        .iter()
+        .zip(transfers.iter())
+        .filter(|(_, [(_, lamports)])| *lamports > 0)
+        .map(|(wallet, transfers)| PlannedTransfers {
+            payer: wallet.pubkey(),
+            fee_payer: sources.fee_payer.as_ref().map(|f| f.pubkey()),
+            transfers,
+        })
+        .collect();
+    check_rent_exemption(rpc, &planned, &[], commitment).await?;
+
+    Ok(shares)
+}
 
-    for (i, wallet) in source_wallets.iter().enumerate() {
-        let mut balance = 0u64;
-        if let Some(wallet_balance) = &balance_result[i] {
-            balance = wallet_balance.lamports;
+/// Sends the collection and waits until every transfer reached `commitment.confirmation`.
+/// Each transfer is signed right before it is sent, so long collections don't run on an expired blockhash.
+/// A `fee_payer` co-signs every transfer and pays its fee, so the wallets give exact amounts.
+pub async fn collect(
+    rpc: &Rpc,
+    websocket_service: Websocket,
+    blockhashes: Blockhashes,
+    sources: CollectSources,
+    destination: Pubkey,
+    amount: CollectAmount,
+    commitment: CommitmentConfig,
+) -> Result<(), Error> {
+    let lamports_per_wallet =
+        lamports_per_source_wallet(rpc, &sources, &destination, amount, commitment).await?;
+
+    let mut confirmations = JoinSet::new();
+    for (wallet, &lamports) in sources.wallets.iter().zip(lamports_per_wallet.iter()) {
+        if lamports == 0 {
+            continue;
         }
+        let blockhash = blockhashes.get().await?;
+        let (latest_hash, last_valid_block_height) =
+            (blockhash.hash, blockhash.last_valid_block_height);
+        let txn = build_sol_transfer(
+            wallet,
+            sources.fee_payer.as_ref(),
+            lamports,
+            &destination,
+            &latest_hash,
+        )
+        .await?;
 
-        if balance < lamports_to_collect_per_wallet {
-            return Err(Error::InsufficientSol(format!(
-                "Need {} SOL to collect but got {} inside wallet {}.",
-                lamports_to_collect_per_wallet, balance, source_pubkeys[i]
-            )));
+        let rpc = Arc::clone(rpc);
+        let websocket_service = websocket_service.clone();
+        confirmations.spawn(async move {
+            send_and_confirm(
+                &rpc,
+                websocket_service,
+                "collect_sol",
+                &txn,
+                last_valid_block_height,
+                commitment,
+            )
+            .await
+        });
+    }
+
+    // Every transfer is waited for, the first failure is reported.
+    let mut result = Ok(());
+    while let Some(res) = confirmations.join_next().await {
+        match res? {
+            Ok(txn_hash) => println!("Collected: {}", txn_hash),
+            Err(err) => {
+                eprintln!("Collecting transfer failed: {}", err);
+                result = result.and(Err(err));
+            }
         }
+    }
+
+    result
+}
 
+/// Builds the same transactions as `collect` and simulates them instead of sending.
+pub async fn dry_run_collect(
+    rpc: &Rpc,
+    blockhashes: Blockhashes,
+    sources: CollectSources,
+    destination: Pubkey,
+    amount: CollectAmount,
+    commitment: CommitmentConfig,
+) -> Result<Vec<SimulationReport>, Error> {
+    let lamports_per_wallet =
+        lamports_per_source_wallet(rpc, &sources, &destination, amount, commitment).await?;
+    let latest_hash = blockhashes.get().await?.hash;
+
+    let mut reports = Vec::new();
+    for (wallet, &lamports) in sources.wallets.iter().zip(lamports_per_wallet.iter()) {
+        if lamports == 0 {
+            continue;
+        }
         let txn = build_sol_transfer(
-            &wallet,
-            lamports_to_collect_per_wallet,
+            wallet,
+            sources.fee_payer.as_ref(),
+            lamports,
             &destination,
             &latest_hash,
         )
         .await?;
+        reports.push(simulate(rpc, "collect_sol_dry_run", &txn, commitment).await?);
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use mock_node::MockNode;
+
+    use super::*;
+    use crate::rpc::mock::mock_services;
+
+    const WALLET_LAMPORTS: u64 = 5_000_000;
+
+    fn funded_wallets(node: &MockNode, count: usize, lamports: u64) -> Vec<Keypair> {
+        (0..count)
+            .map(|_| {
+                let wallet = Keypair::new();
+                node.airdrop(&wallet.pubkey(), lamports).unwrap();
+                wallet
+            })
+            .collect()
+    }
+
+    fn sources(wallets: &[Keypair], fee_payer: Option<&Keypair>) -> CollectSources {
+        CollectSources {
+            wallets: wallets.iter().map(|w| w.insecure_clone()).collect(),
+            fee_payer: fee_payer.map(|f| f.insecure_clone()),
+        }
+    }
+
+    #[tokio::test]
+    async fn collects_evenly_from_every_wallet() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallets = funded_wallets(&node, 3, WALLET_LAMPORTS);
+        let destination = Pubkey::new_unique();
+
+        collect(
+            &rpc,
+            websocket,
+            blockhashes,
+            sources(&wallets, None),
+            destination,
+            CollectAmount::Even(6_000_000),
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(node.balance(&destination), 6_000_000);
+        for wallet in &wallets {
+            assert_eq!(
+                node.balance(&wallet.pubkey()),
+                WALLET_LAMPORTS - 2_000_000 - LAMPORTS_PER_SIGNATURE
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn sponsored_collection_empties_the_wallets() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallets = funded_wallets(&node, 3, WALLET_LAMPORTS);
+        let fee_payer = funded_wallets(&node, 1, WALLET_LAMPORTS).remove(0);
+        let destination = Pubkey::new_unique();
+
+        collect(
+            &rpc,
+            websocket,
+            blockhashes,
+            sources(&wallets, Some(&fee_payer)),
+            destination,
+            CollectAmount::All,
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
 
-        txns.push(txn.transaction);
+        assert_eq!(node.balance(&destination), 3 * WALLET_LAMPORTS);
+        for wallet in &wallets {
+            assert_eq!(node.balance(&wallet.pubkey()), 0);
+        }
+        // Two signatures on each of the three transfers.
+        assert_eq!(
+            node.balance(&fee_payer.pubkey()),
+            WALLET_LAMPORTS - 6 * LAMPORTS_PER_SIGNATURE
+        );
     }
 
-    for txn in txns {
-        let txn_hash = send_transaction(&rpc_url, "collect_sol", &txn).await?;
-        println!("Collecting: {}", txn_hash);
+    #[tokio::test]
+    async fn refuses_to_leave_a_wallet_below_rent() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallets = funded_wallets(&node, 2, 1_000_000);
+        let destination = Pubkey::new_unique();
+
+        let result = collect(
+            &rpc,
+            websocket,
+            blockhashes,
+            sources(&wallets, None),
+            destination,
+            CollectAmount::Fixed(500_000),
+            CommitmentConfig::default(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(Error::RentExemption(_))));
+        assert_eq!(node.balance(&destination), 0);
     }
 
-    Ok(())
+    #[tokio::test]
+    async fn dry_run_sends_nothing() {
+        let node = MockNode::new();
+        let (rpc, _, blockhashes) = mock_services(&node).await;
+        let wallets = funded_wallets(&node, 2, WALLET_LAMPORTS);
+        let destination = Pubkey::new_unique();
+
+        let reports = dry_run_collect(
+            &rpc,
+            blockhashes,
+            sources(&wallets, None),
+            destination,
+            CollectAmount::Fixed(1_000_000),
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(reports.len(), 2);
+        assert!(reports.iter().all(|r| r.err.is_none()));
+        assert_eq!(node.balance(&destination), 0);
+    }
 }
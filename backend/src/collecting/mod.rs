@@ -1 +1,2 @@
 pub mod collecting;
+pub mod token_collecting;
@@ -0,0 +1,395 @@
+use std::sync::Arc;
+
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+use spl_associated_token_account_interface::address::get_associated_token_address_with_program_id;
+use tokio::task::JoinSet;
+
+use crate::{
+    Blockhashes, Rpc, Websocket,
+    collecting::collecting::{CollectAmount, CollectSources, source_wallet_lamports},
+    config::CommitmentConfig,
+    errors::errors::Error,
+    rpc::{
+        read::{get_balance, get_minimum_balance_for_rent_exemption, get_multiple_accounts},
+        send::send_and_confirm,
+    },
+    tokens::{
+        mints::{MintInfo, get_mint},
+        token_accounts::{get_token_accounts, token_account_len},
+    },
+    txn_factory::{
+        rent::{PlannedTransfers, check_rent_exemption},
+        simulation::{SimulationReport, simulate},
+        token_transfer::{TokenRecipient, build_token_collection},
+        util::LAMPORTS_PER_SIGNATURE,
+    },
+};
+
+/// What one wallet gives in a token collection.
+#[derive(Debug, Clone, Default)]
+struct TokenSource {
+    /// Token accounts of the mint and how much is taken from each.
+    transfers: Vec<(Pubkey, u64)>,
+    /// Token accounts left empty, closed afterwards.
+    close: Vec<Pubkey>,
+}
+
+impl TokenSource {
+    fn is_empty(&self) -> bool {
+        self.transfers.is_empty() && self.close.is_empty()
+    }
+}
+
+pub struct TokenCollection {
+    mint: MintInfo,
+    destination: TokenRecipient,
+    /// One per source wallet, in the same order.
+    sources: Vec<TokenSource>,
+}
+
+/// Works out which token accounts every source wallet gives from, draining the largest
+/// first. Every wallet must hold SOL for its fee and, if the destination has no associated
+/// token account yet, its rent, since whichever transfer lands first creates it. With a
+/// `fee_payer` the wallets need no SOL, it pays every fee and the rent once.
+pub async fn plan_token_collection(
+    rpc: &Rpc,
+    sources: &CollectSources,
+    destination: &Pubkey,
+    mint: &Pubkey,
+    amount: CollectAmount,
+    close_token_accounts: bool,
+    commitment: CommitmentConfig,
+) -> Result<TokenCollection, Error> {
+    let mint = get_mint(rpc, mint, commitment.read).await?;
+
+    let destination_account =
+        get_associated_token_address_with_program_id(destination, &mint.mint, &mint.program_id);
+    let existing = get_multiple_accounts(
+        rpc,
+        "plan_token_collection",
+        &vec![destination_account.to_string()],
+        None,
+        commitment.read,
+    )
+    .await?;
+    let destination = TokenRecipient {
+        owner: *destination,
+        create_token_account: existing.first().is_none_or(|a| a.is_none()),
+    };
+
+    let mut token_accounts = Vec::new();
+    let mut available = Vec::new();
+    for wallet in &sources.wallets {
+        let mut accounts: Vec<_> = get_token_accounts(rpc, &wallet.pubkey(), commitment.read)
+            .await?
+            .into_iter()
+            .filter(|a| a.mint == mint.mint)
+            .collect();
+        accounts.sort_by_key(|a| std::cmp::Reverse(a.amount));
+
+        available.push((
+            wallet.pubkey().to_string(),
+            accounts.iter().map(|a| a.amount).sum::<u64>(),
+        ));
+        token_accounts.push(accounts);
+    }
+    let shares = amount
+        .shares(&available)
+        .map_err(Error::InsufficientTokens)?;
+
+    let mut token_sources = Vec::new();
+    for (accounts, share) in token_accounts.iter().zip(shares.iter()) {
+        let mut source = TokenSource::default();
+        let mut remaining = *share;
+        for account in accounts {
+            let taken = remaining.min(account.amount);
+            if taken > 0 {
+                source.transfers.push((account.pubkey, taken));
+                remaining -= taken;
+            }
+            if close_token_accounts && taken == account.amount {
+                source.close.push(account.pubkey);
+            }
+        }
+        token_sources.push(source);
+    }
+
+    let rent = if destination.create_token_account {
+        get_minimum_balance_for_rent_exemption(
+            rpc,
+            "plan_token_collection",
+            token_account_len(&mint.program_id) as u128,
+            commitment.read,
+        )
+        .await? as u64
+    } else {
+        0
+    };
+    // Also fails if a wallet is no longer a plain system account, sponsored or not.
+    let lamports = source_wallet_lamports(rpc, &sources.wallets, commitment).await?;
+
+    // Closed accounts refund their rent within the same transaction, only its end state
+    // counts for the rent check.
+    let mut assumed_balances: Vec<(Pubkey, u64)> = Vec::new();
+    for (((wallet, (_, balance)), accounts), source) in sources
+        .wallets
+        .iter()
+        .zip(lamports.iter())
+        .zip(token_accounts.iter())
+        .zip(token_sources.iter())
+    {
+        let refunded: u64 = accounts
+            .iter()
+            .filter(|a| source.close.contains(&a.pubkey))
+            .map(|a| a.lamports)
+            .sum();
+        assumed_balances.push((wallet.pubkey(), balance + refunded));
+    }
+
+    let destination_rent = [(destination_account, rent)];
+    let rent_payments: &[&[(Pubkey, u64)]] = match &sources.fee_payer {
+        Some(fee_payer) => {
+            let transactions = token_sources.iter().filter(|s| !s.is_empty()).count() as u64;
+            // The fee payer signs next to every wallet and pays for both signatures.
+            let needed = 2 * LAMPORTS_PER_SIGNATURE * transactions + rent;
+            let balance = get_balance(
+                rpc,
+                "plan_token_collection",
+                &fee_payer.pubkey().to_string(),
+                commitment.read,
+            )
+            .await?;
+            if transactions > 0 && balance < needed {
+                return Err(Error::InsufficientSol(format!(
+                    "Need {} lamports for fees and rent but got {} inside fee payer {}.",
+                    needed,
+                    balance,
+                    fee_payer.pubkey()
+                )));
+            }
+            // The rent it puts into the destination is taken off up front.
+            assumed_balances.push((fee_payer.pubkey(), balance.saturating_sub(rent)));
+            &[&[]]
+        }
+        None => {
+            for ((pubkey, balance), source) in lamports.iter().zip(token_sources.iter()) {
+                if !source.is_empty() && *balance < LAMPORTS_PER_SIGNATURE + rent {
+                    return Err(Error::InsufficientSol(format!(
+                        "Need {} lamports for fees and rent but got {} inside wallet {}.",
+                        LAMPORTS_PER_SIGNATURE + rent,
+                        balance,
+                        pubkey
+                    )));
+                }
+            }
+            // Any wallet may be the one creating the destination, both ways must hold.
+            if rent > 0 {
+                &[&destination_rent, &[]]
+            } else {
+                &[&[]]
+            }
+        }
+    };
+    for transfers in rent_payments {
+        let planned: Vec<PlannedTransfers> = sources
+            .wallets
+            .iter()
+            .zip(token_sources.iter())
+            .filter(|(_, source)| !source.is_empty())
+            .map(|(wallet, _)| PlannedTransfers {
+                payer: wallet.pubkey(),
+                fee_payer: sources.fee_payer.as_ref().map(|f| f.pubkey()),
+                transfers,
+            })
+            .collect();
+        check_rent_exemption(rpc, &planned, &assumed_balances, commitment).await?;
+    }
+
+    Ok(TokenCollection {
+        mint,
+        destination,
+        sources: token_sources,
+    })
+}
+
+/// Sends the token collection and waits until every transfer reached `commitment.confirmation`.
+pub async fn collect_tokens(
+    rpc: &Rpc,
+    websocket_service: Websocket,
+    blockhashes: Blockhashes,
+    sources: CollectSources,
+    collection: &TokenCollection,
+    commitment: CommitmentConfig,
+) -> Result<(), Error> {
+    let mut confirmations = JoinSet::new();
+    for (wallet, source) in sources.wallets.iter().zip(collection.sources.iter()) {
+        if source.is_empty() {
+            continue;
+        }
+        let blockhash = blockhashes.get().await?;
+        let (latest_hash, last_valid_block_height) =
+            (blockhash.hash, blockhash.last_valid_block_height);
+        let txn = build_token_collection(
+            wallet,
+            sources.fee_payer.as_ref(),
+            &collection.mint,
+            &source.transfers,
+            &source.close,
+            &collection.destination,
+            &latest_hash,
+        )
+        .await?;
+
+        let rpc = Arc::clone(rpc);
+        let websocket_service = websocket_service.clone();
+        confirmations.spawn(async move {
+            send_and_confirm(
+                &rpc,
+                websocket_service,
+                "collect_tokens",
+                &txn,
+                last_valid_block_height,
+                commitment,
+            )
+            .await
+        });
+    }
+
+    // Every transfer is waited for, the first failure is reported.
+    let mut result = Ok(());
+    while let Some(res) = confirmations.join_next().await {
+        match res? {
+            Ok(txn_hash) => println!("Collected tokens: {}", txn_hash),
+            Err(err) => {
+                eprintln!("Token collection transfer failed: {}", err);
+                result = result.and(Err(err));
+            }
+        }
+    }
+
+    result
+}
+
+/// Builds the same transactions as `collect_tokens` and simulates them instead of sending.
+pub async fn dry_run_collect_tokens(
+    rpc: &Rpc,
+    blockhashes: Blockhashes,
+    sources: CollectSources,
+    collection: &TokenCollection,
+    commitment: CommitmentConfig,
+) -> Result<Vec<SimulationReport>, Error> {
+    let latest_hash = blockhashes.get().await?.hash;
+
+    let mut reports = Vec::new();
+    for (wallet, source) in sources.wallets.iter().zip(collection.sources.iter()) {
+        if source.is_empty() {
+            continue;
+        }
+        let txn = build_token_collection(
+            wallet,
+            sources.fee_payer.as_ref(),
+            &collection.mint,
+            &source.transfers,
+            &source.close,
+            &collection.destination,
+            &latest_hash,
+        )
+        .await?;
+        reports.push(simulate(rpc, "collect_tokens_dry_run", &txn, commitment).await?);
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use mock_node::MockNode;
+    use solana_sdk::signature::Keypair;
+
+    use super::*;
+    use crate::rpc::mock::{mock_mint, mock_services, mock_token_account, mock_token_amount};
+
+    const WALLET_LAMPORTS: u64 = 5_000_000;
+
+    #[tokio::test]
+    async fn collects_everything_and_closes_the_emptied_accounts() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let mint = mock_mint(&node, &Pubkey::new_unique(), 6);
+        let wallets: Vec<Keypair> = (0..2).map(|_| Keypair::new()).collect();
+        let mut token_accounts = Vec::new();
+        for (i, wallet) in wallets.iter().enumerate() {
+            node.airdrop(&wallet.pubkey(), WALLET_LAMPORTS).unwrap();
+            token_accounts.push(mock_token_account(
+                &node,
+                &wallet.pubkey(),
+                &mint,
+                1_000 * (i as u64 + 1),
+            ));
+        }
+        let sources = CollectSources {
+            wallets: wallets.iter().map(|w| w.insecure_clone()).collect(),
+            fee_payer: None,
+        };
+        let destination = Pubkey::new_unique();
+
+        let collection = plan_token_collection(
+            &rpc,
+            &sources,
+            &destination,
+            &mint,
+            CollectAmount::All,
+            true,
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
+        collect_tokens(
+            &rpc,
+            websocket,
+            blockhashes,
+            sources,
+            &collection,
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        let destination_account = get_associated_token_address_with_program_id(
+            &destination,
+            &mint,
+            &spl_token_interface::ID,
+        );
+        assert_eq!(mock_token_amount(&node, &destination_account), 3_000);
+        for token_account in &token_accounts {
+            assert!(node.account(token_account).is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_more_than_the_wallets_hold() {
+        let node = MockNode::new();
+        let (rpc, _, _) = mock_services(&node).await;
+        let mint = mock_mint(&node, &Pubkey::new_unique(), 6);
+        let wallet = Keypair::new();
+        node.airdrop(&wallet.pubkey(), WALLET_LAMPORTS).unwrap();
+        mock_token_account(&node, &wallet.pubkey(), &mint, 1_000);
+        let sources = CollectSources {
+            wallets: vec![wallet],
+            fee_payer: None,
+        };
+
+        let result = plan_token_collection(
+            &rpc,
+            &sources,
+            &Pubkey::new_unique(),
+            &mint,
+            CollectAmount::Fixed(1_001),
+            false,
+            CommitmentConfig::default(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(Error::InsufficientTokens(_))));
+    }
+}
@@ -1,3 +1,97 @@
+use std::env;
+
+use crate::rpc::commitment::Commitment;
+
 pub struct Config {
     pub wallet_seed: Vec<u8>,
+    /// Derive the treasury wallet from the seed, set by `TREASURY_ENABLED`.
+    pub treasury_enabled: bool,
+    pub commitment: CommitmentConfig,
+    pub swap: SwapConfig,
+}
+
+/// Commitment used by each kind of operation unless a request overrides it.
+#[derive(Debug, Clone, Copy)]
+pub struct CommitmentConfig {
+    /// Balances, accounts, rent and simulations.
+    pub read: Commitment,
+    /// Blockhashes transactions are built on, and fee quotes for them.
+    pub blockhash: Commitment,
+    /// State the node checks a transaction against before forwarding it.
+    pub preflight: Commitment,
+    /// What sent transactions are confirmed at.
+    pub confirmation: Commitment,
+}
+
+impl CommitmentConfig {
+    /// Reads `COMMITMENT_READ`, `COMMITMENT_BLOCKHASH`, `COMMITMENT_PREFLIGHT` and
+    /// `COMMITMENT_CONFIRMATION`, falling back to the defaults for unset ones.
+    pub fn from_env() -> Self {
+        let default = Self::default();
+        let var = |name: &str, fallback: Commitment| match env::var(name) {
+            Ok(value) => value
+                .parse::<Commitment>()
+                .unwrap_or_else(|err| panic!("invalid {}: {}", name, err)),
+            Err(_) => fallback,
+        };
+
+        Self {
+            read: var("COMMITMENT_READ", default.read),
+            blockhash: var("COMMITMENT_BLOCKHASH", default.blockhash),
+            preflight: var("COMMITMENT_PREFLIGHT", default.preflight),
+            confirmation: var("COMMITMENT_CONFIRMATION", default.confirmation),
+        }
+    }
+
+    pub fn with_read(self, read: Option<Commitment>) -> Self {
+        Self {
+            read: read.unwrap_or(self.read),
+            ..self
+        }
+    }
+
+    pub fn with_confirmation(self, confirmation: Option<Commitment>) -> Self {
+        Self {
+            confirmation: confirmation.unwrap_or(self.confirmation),
+            ..self
+        }
+    }
+}
+
+/// Base URLs of the swap providers, overridable to point at local stand-ins.
+pub struct SwapConfig {
+    pub jupiter_ultra_url: String,
+    pub jupiter_swap_url: String,
+    pub raydium_url: String,
+    /// Holdings worth less than this many lamports are left alone when liquidating.
+    pub dust_lamports: u64,
+}
+
+impl SwapConfig {
+    /// Reads `JUPITER_ULTRA_URL`, `JUPITER_SWAP_URL`, `RAYDIUM_TRADE_URL` and
+    /// `LIQUIDATION_DUST_LAMPORTS`.
+    pub fn from_env() -> Self {
+        let var =
+            |name: &str, fallback: &str| env::var(name).unwrap_or_else(|_| fallback.to_owned());
+
+        Self {
+            jupiter_ultra_url: var("JUPITER_ULTRA_URL", "https://lite-api.jup.ag/ultra/v1"),
+            jupiter_swap_url: var("JUPITER_SWAP_URL", "https://lite-api.jup.ag/swap/v1"),
+            raydium_url: var("RAYDIUM_TRADE_URL", "https://transaction-v1.raydium.io"),
+            dust_lamports: var("LIQUIDATION_DUST_LAMPORTS", "100000")
+                .parse()
+                .expect("invalid LIQUIDATION_DUST_LAMPORTS"),
+        }
+    }
+}
+
+impl Default for CommitmentConfig {
+    fn default() -> Self {
+        Self {
+            read: Commitment::Finalized,
+            blockhash: Commitment::Processed,
+            preflight: Commitment::Processed,
+            confirmation: Commitment::Confirmed,
+        }
+    }
 }
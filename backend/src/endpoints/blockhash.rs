@@ -0,0 +1,63 @@
+use std::str::FromStr;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use solana_sdk::hash::Hash;
+
+use crate::{
+    AppState,
+    endpoints::responses::{bad_request, server_error},
+};
+
+#[derive(Deserialize)]
+pub struct LatestBlockhashRequest {
+    /// A previously handed out blockhash to check.
+    hash: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct LatestBlockhashResponse {
+    blockhash: String,
+    last_valid_block_height: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    hash_valid: Option<bool>,
+}
+
+/// The blockhash signers currently get from the cache, optionally reporting
+/// whether an earlier one is still valid.
+pub async fn latest_blockhash(
+    State(state): State<AppState>,
+    Query(params): Query<LatestBlockhashRequest>,
+) -> Response {
+    let hash = match params.hash.as_deref().map(Hash::from_str) {
+        Some(Err(_)) => return bad_request("The hash is not a valid blockhash"),
+        Some(Ok(h)) => Some(h),
+        None => None,
+    };
+
+    let latest = match state.services.blockhashes.get().await {
+        Ok(b) => b,
+        Err(err) => {
+            eprintln!("Error getting blockhash {}", err);
+            return server_error("Failed to get a blockhash.");
+        }
+    };
+
+    let hash_valid = match hash {
+        Some(h) => Some(state.services.blockhashes.is_valid(&h).await),
+        None => None,
+    };
+
+    let res = LatestBlockhashResponse {
+        blockhash: latest.hash.to_string(),
+        last_valid_block_height: latest.last_valid_block_height.to_string(),
+        hash_valid,
+    };
+
+    (StatusCode::OK, Json(res)).into_response()
+}
@@ -7,21 +7,62 @@ use axum::{
 };
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use solana_sdk::pubkey::Pubkey;
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
 
 use crate::{
     AppState,
-    collecting::collecting::collect,
-    endpoints::responses::{bad_request, confilict, server_error},
+    collecting::{
+        collecting::{CollectAmount, CollectSources, collect, dry_run_collect},
+        token_collecting::{collect_tokens, dry_run_collect_tokens, plan_token_collection},
+    },
+    config::CommitmentConfig,
+    endpoints::{
+        misc::{SimulatedTransaction, fee_payer_wallet},
+        responses::{bad_request, confilict, server_error},
+    },
     errors::errors::Error,
-    storage::mnemonic_wallet_storage::get_wallets_by_pubkey,
+    rpc::commitment::Commitment,
+    storage::mnemonic_wallet_storage::{get_treasury_wallet, get_wallets_by_pubkey},
 };
 
+/// How much each source wallet gives.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CollectMode {
+    /// `amount` is the total, split evenly over the wallets.
+    #[default]
+    Even,
+    /// `amount` is taken from every wallet.
+    Fixed,
+    /// Everything, `amount` is not used.
+    All,
+    /// `amount` is the total, split in proportion to the wallets' balances.
+    Proportional,
+}
+
 #[derive(Deserialize)]
 pub struct CollectSolRequest {
-    lamports: String,
+    /// Lamports, or base units of `mint`.
+    #[serde(alias = "lamports")]
+    amount: Option<String>,
+    #[serde(default)]
+    mode: CollectMode,
+    /// Collects this token into the destination's associated token account instead of SOL.
+    mint: Option<String>,
+    /// Closes the token accounts the collection leaves empty, returning their rent to the wallets.
+    #[serde(default)]
+    close_token_accounts: bool,
     source_pubkeys: Vec<String>,
-    destination: String,
+    /// Defaults to the treasury when it is enabled.
+    destination: Option<String>,
+    /// `treasury` or a fleet wallet pubkey. It co-signs and pays every fee, so the source
+    /// wallets give exact amounts and need no SOL of their own.
+    fee_payer: Option<String>,
+    /// Simulate the collection instead of sending it.
+    #[serde(default)]
+    dry_run: bool,
+    /// Overrides the configured confirmation commitment, e.g. `finalized` for treasury-sized collections.
+    commitment: Option<Commitment>,
 }
 
 #[derive(Serialize)]
@@ -29,6 +70,12 @@ pub struct CollectSolResponse {
     message: String,
 }
 
+#[derive(Serialize)]
+pub struct DryRunCollectSolResponse {
+    message: String,
+    simulations: Vec<SimulatedTransaction>,
+}
+
 pub async fn collect_sol(
     State(state): State<AppState>,
     Json(payload): Json<CollectSolRequest>,
@@ -47,23 +94,99 @@ pub async fn collect_sol(
         Ok(w) => w,
     };
 
-    let lamports_parse_result = payload.lamports.parse::<u64>();
-    let lamports = match lamports_parse_result {
-        Ok(l) => l,
-        Err(_err) => {
-            return bad_request("The lamports are in invalid format");
-        }
+    let amount = match (
+        payload.mode,
+        payload.amount.as_deref().map(str::parse::<u64>),
+    ) {
+        (CollectMode::All, _) => CollectAmount::All,
+        (_, None) => return bad_request("The amount is missing"),
+        (_, Some(Err(_err))) => return bad_request("The amount is in invalid format"),
+        (CollectMode::Even, Some(Ok(a))) => CollectAmount::Even(a),
+        (CollectMode::Fixed, Some(Ok(a))) => CollectAmount::Fixed(a),
+        (CollectMode::Proportional, Some(Ok(a))) => CollectAmount::Proportional(a),
     };
 
-    let destination_parse = Pubkey::from_str(&payload.destination);
-    let destination = match destination_parse {
-        Ok(d) => d,
-        Err(_err) => {
-            return bad_request("Destination is not a valid public key");
-        }
+    let destination = match &payload.destination {
+        Some(destination) => match Pubkey::from_str(destination) {
+            Ok(d) => d,
+            Err(_err) => {
+                return bad_request("Destination is not a valid public key");
+            }
+        },
+        None => match get_treasury_wallet(&config) {
+            Some(treasury) => treasury.pubkey(),
+            None => return bad_request("Destination is missing and the treasury is not enabled"),
+        },
     };
 
-    let collect_result = collect(&state.rpc_url, source_wallets, destination, lamports).await;
+    let fee_payer = match &payload.fee_payer {
+        None => None,
+        Some(fee_payer) => match fee_payer_wallet(&db, &config, fee_payer, &source_wallets).await {
+            Err(res) => return res,
+            Ok(w) => Some(w),
+        },
+    };
+
+    let sources = CollectSources {
+        wallets: source_wallets,
+        fee_payer,
+    };
+    let commitment = config.commitment.with_confirmation(payload.commitment);
+
+    if let Some(mint) = &payload.mint {
+        let Ok(mint) = Pubkey::from_str(mint) else {
+            return bad_request("Mint is not a valid public key");
+        };
+        return collect_token(
+            state,
+            sources,
+            destination,
+            mint,
+            amount,
+            &payload,
+            commitment,
+        )
+        .await;
+    }
+
+    if payload.dry_run {
+        let dry_run_result = dry_run_collect(
+            &state.rpc,
+            state.services.blockhashes,
+            sources,
+            destination,
+            amount,
+            commitment,
+        )
+        .await;
+
+        return match dry_run_result {
+            Err(Error::InsufficientSol(_)) => confilict("Not enough SOL in wallets."),
+            Err(Error::CompromisedAccount(msg)) | Err(Error::RentExemption(msg)) => confilict(&msg),
+            Err(err) => {
+                eprintln!("Error while simulating collection {}", err);
+                server_error("Error during collection dry run")
+            }
+            Ok(reports) => {
+                let res = DryRunCollectSolResponse {
+                    message: String::from("Simulated collection, nothing was sent."),
+                    simulations: reports.iter().map(SimulatedTransaction::from).collect(),
+                };
+                (StatusCode::OK, Json(res)).into_response()
+            }
+        };
+    }
+
+    let collect_result = collect(
+        &state.rpc,
+        state.services.websocket,
+        state.services.blockhashes,
+        sources,
+        destination,
+        amount,
+        commitment,
+    )
+    .await;
 
     if let Err(err) = collect_result {
         eprintln!("Error while collecting sol {}", err);
@@ -72,6 +195,10 @@ pub async fn collect_sol(
             return confilict("Not enough SOL in wallets.");
         }
 
+        if let Error::CompromisedAccount(msg) | Error::RentExemption(msg) = &err {
+            return confilict(msg);
+        }
+
         return server_error("Error during collection");
     }
 
@@ -81,3 +208,80 @@ pub async fn collect_sol(
 
     return (StatusCode::OK, Json(res)).into_response();
 }
+
+async fn collect_token(
+    state: AppState,
+    sources: CollectSources,
+    destination: Pubkey,
+    mint: Pubkey,
+    amount: CollectAmount,
+    payload: &CollectSolRequest,
+    commitment: CommitmentConfig,
+) -> Response {
+    let collection = plan_token_collection(
+        &state.rpc,
+        &sources,
+        &destination,
+        &mint,
+        amount,
+        payload.close_token_accounts,
+        commitment,
+    )
+    .await;
+
+    let collect_result = match collection {
+        Err(err) => Err(err),
+        Ok(collection) if payload.dry_run => {
+            let dry_run_result = dry_run_collect_tokens(
+                &state.rpc,
+                state.services.blockhashes,
+                sources,
+                &collection,
+                commitment,
+            )
+            .await;
+
+            return match dry_run_result {
+                Err(err) => {
+                    eprintln!("Error while simulating token collection {}", err);
+                    server_error("Error during collection dry run")
+                }
+                Ok(reports) => {
+                    let res = DryRunCollectSolResponse {
+                        message: String::from("Simulated collection, nothing was sent."),
+                        simulations: reports.iter().map(SimulatedTransaction::from).collect(),
+                    };
+                    (StatusCode::OK, Json(res)).into_response()
+                }
+            };
+        }
+        Ok(collection) => {
+            collect_tokens(
+                &state.rpc,
+                state.services.websocket,
+                state.services.blockhashes,
+                sources,
+                &collection,
+                commitment,
+            )
+            .await
+        }
+    };
+
+    match collect_result {
+        Err(Error::InvalidMint(msg)) => bad_request(&msg),
+        Err(Error::InsufficientTokens(_)) => confilict("Not enough tokens in wallets."),
+        Err(Error::InsufficientSol(msg)) => confilict(&msg),
+        Err(Error::CompromisedAccount(msg)) | Err(Error::RentExemption(msg)) => confilict(&msg),
+        Err(err) => {
+            eprintln!("Error while collecting tokens {}", err);
+            server_error("Error during collection")
+        }
+        Ok(()) => {
+            let res = CollectSolResponse {
+                message: String::from("Collected successfully."),
+            };
+            (StatusCode::OK, Json(res)).into_response()
+        }
+    }
+}
@@ -1,33 +1,196 @@
-use std::sync::Arc;
+use std::{collections::HashMap, sync::Arc};
 
 use axum::{
     Json,
-    extract::State,
+    extract::{Query, State},
     http::StatusCode,
     response::{IntoResponse, Response},
 };
 use serde::{Deserialize, Serialize};
-use solana_sdk::{pubkey::Pubkey, signer::Signer};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use std::str::FromStr;
+use tokio_rusqlite::Connection;
 
 use crate::{
     AppState,
+    config::{CommitmentConfig, Config},
     endpoints::{
-        misc::ErrorResponse,
-        responses::{bad_request, server_error},
+        misc::{ErrorResponse, SimulatedTransaction},
+        responses::{bad_request, confilict, server_error},
     },
     errors::errors::Error,
-    storage::mnemonic_wallet_storage::get_all_wallets,
+    funding::{
+        funding::{FundingJob, FundingTarget},
+        top_up::top_up_targets,
+        treasury::funding_shortfall,
+    },
+    rpc::commitment::Commitment,
+    storage::mnemonic_wallet_storage::{
+        get_all_wallets, get_treasury_wallet, get_wallets_by_index_range, get_wallets_by_pubkey,
+        get_wallets_by_tag,
+    },
 };
 
+#[derive(Deserialize)]
+pub struct WalletRange {
+    start: u64,
+    /// Included.
+    end: u64,
+}
+
+/// Which fleet wallets a job targets, the whole fleet when nothing is set.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct WalletSelection {
+    wallets: Option<Vec<String>>,
+    /// By wallet index, as listed by `/wallets/list`.
+    wallet_range: Option<WalletRange>,
+    tag: Option<String>,
+}
+
+pub async fn select_wallets(
+    db: &Connection,
+    config: &Config,
+    selection: &WalletSelection,
+) -> Result<Vec<Keypair>, Response> {
+    let wallets = match (&selection.wallets, &selection.wallet_range, &selection.tag) {
+        (None, None, None) => get_all_wallets(db, config, 1, 9999).await,
+        (Some(pubkeys), None, None) => {
+            if let Some(invalid) = pubkeys.iter().find(|p| Pubkey::from_str(p).is_err()) {
+                return Err(bad_request(&format!(
+                    "{} is not a valid public key",
+                    invalid
+                )));
+            }
+            get_wallets_by_pubkey(db, config, pubkeys).await
+        }
+        (None, Some(range), None) if range.start > range.end => {
+            return Err(bad_request("The wallet range ends before it starts"));
+        }
+        (None, Some(range), None) => {
+            get_wallets_by_index_range(db, config, range.start, range.end).await
+        }
+        (None, None, Some(tag)) => get_wallets_by_tag(db, config, tag).await,
+        _ => {
+            return Err(bad_request(
+                "Give at most one of wallets, wallet_range or tag.",
+            ));
+        }
+    };
+
+    let wallets = match wallets {
+        Err(err) => {
+            eprintln!("Error getting wallets for funding: {}", err);
+            return Err(server_error("Internal error"));
+        }
+        Ok(w) => w,
+    };
+
+    if let Some(pubkeys) = &selection.wallets
+        && let Some(missing) = pubkeys
+            .iter()
+            .find(|p| !wallets.iter().any(|w| &w.pubkey().to_string() == *p))
+    {
+        return Err(bad_request(&format!("{} is not a fleet wallet.", missing)));
+    }
+
+    Ok(wallets)
+}
+
 #[derive(Deserialize)]
 pub struct InitiateFundingRequest {
-    lamports_per_wallet: String,
+    #[serde(flatten)]
+    selection: WalletSelection,
+    /// Sent to every fleet wallet.
+    lamports_per_wallet: Option<String>,
+    /// Fleet wallet pubkey to the lamports it is sent, instead of `lamports_per_wallet`.
+    allocations: Option<HashMap<String, String>>,
+    /// Tops every fleet wallet up to this many lamports instead.
+    target_balance: Option<String>,
+    /// Funds this many of the wallets first, which then fund the rest in parallel.
+    fan_out: Option<usize>,
+    /// Sends from the treasury instead of a fresh funding wallet.
+    #[serde(default)]
+    from_treasury: bool,
+}
+
+#[derive(Deserialize)]
+pub struct CsvFundingQuery {
+    fan_out: Option<usize>,
+    #[serde(default)]
+    from_treasury: bool,
+}
+
+/// The wallet a new job sends from, the treasury or a fresh one to deposit into.
+/// `None` if the treasury is asked for but not enabled.
+fn distro_wallet(config: &Config, from_treasury: bool) -> Option<Keypair> {
+    if !from_treasury {
+        return Some(Keypair::new());
+    }
+    get_treasury_wallet(config)
+}
+
+/// How much each wallet of a SOL funding job gets.
+enum Allocation {
+    PerWallet(u64),
+    /// Pubkey and lamports, as given by the caller.
+    Explicit(Vec<(String, String)>),
+    /// Whatever each wallet is short of the target balance.
+    TopUp(u64),
 }
 
 #[derive(Serialize)]
 struct JobProperty {
     funding_wallet_pubkey: String,
     total_funding_lamports: String,
+    /// Lamports still to deposit into the funding wallet, zero when the treasury holds enough.
+    deposit_lamports: String,
+    /// Wallets the job sends to.
+    wallets: usize,
+    /// Wallets funding the others in tree mode.
+    relays: Vec<String>,
+    /// SOL transactions of the job, for token jobs see `token`.
+    transactions: usize,
+    /// Set for token jobs, the tokens go into `funding_token_account`.
+    token: Option<TokenJobProperty>,
+}
+
+#[derive(Serialize)]
+struct TokenJobProperty {
+    mint: String,
+    program_id: String,
+    decimals: u8,
+    amount_per_wallet: String,
+    funding_token_account: String,
+    total_funding_amount: String,
+    /// Tokens still to deposit into `funding_token_account`.
+    deposit_amount: String,
+    transactions: usize,
+}
+
+impl JobProperty {
+    /// Describes the job with what is still missing from its funding wallet.
+    fn new(job: &FundingJob, shortfall: (u128, u128)) -> Self {
+        let (deposit_lamports, deposit_amount) = shortfall;
+        Self {
+            funding_wallet_pubkey: job.distro_wallet.pubkey().to_string(),
+            total_funding_lamports: job.total_funding_lamports.to_string(),
+            deposit_lamports: deposit_lamports.to_string(),
+            wallets: job.targets.len(),
+            relays: job.relays.iter().map(|r| r.pubkey().to_string()).collect(),
+            transactions: job.transfers.len(),
+            token: job.token.as_ref().map(|token| TokenJobProperty {
+                mint: token.mint.mint.to_string(),
+                program_id: token.mint.program_id.to_string(),
+                decimals: token.mint.decimals,
+                amount_per_wallet: token.amount_per_wallet.to_string(),
+                funding_token_account: token.source_token_account.to_string(),
+                total_funding_amount: token.total_funding_amount.to_string(),
+                deposit_amount: deposit_amount.to_string(),
+                transactions: token.batches.len(),
+            }),
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -39,71 +202,351 @@ pub struct InitiateFundingResponse {
 pub async fn initiate_job(
     State(state): State<AppState>,
     Json(payload): Json<InitiateFundingRequest>,
+) -> Response {
+    let allocation = match (
+        payload.lamports_per_wallet,
+        payload.allocations,
+        payload.target_balance,
+    ) {
+        (Some(lamports), None, None) => match lamports.parse::<u64>() {
+            Err(_) => {
+                return bad_request("Invalid lamports string format");
+            }
+            Ok(v) => Allocation::PerWallet(v),
+        },
+        (None, Some(allocations), None) => Allocation::Explicit(allocations.into_iter().collect()),
+        (None, None, Some(target)) => match target.parse::<u64>() {
+            Err(_) => return bad_request("Invalid target balance string format"),
+            Ok(v) => Allocation::TopUp(v),
+        },
+        _ => {
+            return bad_request("Give one of lamports_per_wallet, allocations or target_balance.");
+        }
+    };
+
+    start_sol_job(
+        state,
+        &payload.selection,
+        allocation,
+        payload.fan_out,
+        payload.from_treasury,
+    )
+    .await
+}
+
+/// Starts a SOL funding job from a `pubkey,lamports` CSV body, a header line is skipped.
+pub async fn initiate_csv_job(
+    State(state): State<AppState>,
+    Query(params): Query<CsvFundingQuery>,
+    body: String,
+) -> Response {
+    let mut allocations = Vec::new();
+    for (i, line) in body.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+        match fields.as_slice() {
+            [_, lamports] if i == 0 && lamports.parse::<u64>().is_err() => continue,
+            [pubkey, lamports] => allocations.push((pubkey.to_string(), lamports.to_string())),
+            _ => {
+                return bad_request(&format!("Line {} is not pubkey,lamports.", i + 1));
+            }
+        }
+    }
+
+    start_sol_job(
+        state,
+        &WalletSelection::default(),
+        Allocation::Explicit(allocations),
+        params.fan_out,
+        params.from_treasury,
+    )
+    .await
+}
+
+/// Pairs the allocations with the targeted fleet wallets, in fleet order. Wallets
+/// allocated zero lamports are left out.
+fn funding_targets(
+    fleet: &[Pubkey],
+    allocations: &[(String, String)],
+) -> Result<Vec<FundingTarget>, String> {
+    let mut lamports_by_pubkey: HashMap<Pubkey, u64> = HashMap::new();
+    for (pubkey, lamports) in allocations {
+        let parsed = Pubkey::from_str(pubkey).ok().filter(|p| fleet.contains(p));
+        let Some(parsed) = parsed else {
+            return Err(format!(
+                "{} is not one of the targeted fleet wallets.",
+                pubkey
+            ));
+        };
+        let Ok(lamports) = lamports.parse::<u64>() else {
+            return Err(format!("Invalid lamports for {}.", pubkey));
+        };
+        if lamports_by_pubkey.insert(parsed, lamports).is_some() {
+            return Err(format!("{} is allocated twice.", pubkey));
+        }
+    }
+
+    Ok(fleet
+        .iter()
+        .filter_map(|pubkey| {
+            lamports_by_pubkey
+                .get(pubkey)
+                .filter(|l| **l > 0)
+                .map(|lamports| FundingTarget {
+                    pubkey: *pubkey,
+                    lamports: *lamports,
+                })
+        })
+        .collect())
+}
+
+async fn start_sol_job(
+    state: AppState,
+    selection: &WalletSelection,
+    allocation: Allocation,
+    fan_out: Option<usize>,
+    from_treasury: bool,
 ) -> Response {
     let db_arc = Arc::clone(&state.services.database);
     let db = db_arc.read().await;
     let config_arc = Arc::clone(&state.config);
     let config = config_arc.read().await;
 
-    let lamports = payload.lamports_per_wallet.parse::<u64>();
-    let lamports_per_wallet = match lamports {
-        Err(_) => {
-            return bad_request("Invalid lamports string format");
+    let wallets = match select_wallets(&db, &config, selection).await {
+        Err(res) => return res,
+        Ok(w) => w,
+    };
+    let Some(distro_wallet) = distro_wallet(&config, from_treasury) else {
+        return bad_request("The treasury is not enabled.");
+    };
+
+    let pubkeys: Vec<Pubkey> = wallets.iter().map(|w| w.pubkey()).collect();
+    let targets = match allocation {
+        Allocation::PerWallet(lamports) => pubkeys
+            .iter()
+            .map(|pubkey| FundingTarget {
+                pubkey: *pubkey,
+                lamports,
+            })
+            .collect(),
+        Allocation::Explicit(allocations) => match funding_targets(&pubkeys, &allocations) {
+            Err(msg) => return bad_request(&msg),
+            Ok(t) => t,
+        },
+        Allocation::TopUp(target_balance) => {
+            match top_up_targets(&state.rpc, &pubkeys, target_balance, config.commitment).await {
+                Err(err) => {
+                    eprintln!("Error reading balances for top-up {}", err);
+                    return server_error("Internal error");
+                }
+                Ok(t) if t.is_empty() && !pubkeys.is_empty() => {
+                    return confilict("Every wallet is at or above the target balance.");
+                }
+                Ok(t) => t,
+            }
         }
-        Ok(v) => v,
     };
 
-    let wallets = get_all_wallets(&*db, &*config, 1, 9999).await;
-    let wallets = match wallets {
+    if targets.is_empty() {
+        return bad_request("There are 0 wallets.");
+    }
+
+    // The first wallets funded become the relays.
+    let relays: Vec<Keypair> = match fan_out {
+        None => Vec::new(),
+        Some(k) if k == 0 || k >= targets.len() => {
+            return bad_request(
+                "fan_out must be at least 1 and below the number of wallets funded.",
+            );
+        }
+        Some(k) => targets[..k]
+            .iter()
+            .filter_map(|t| wallets.iter().find(|w| w.pubkey() == t.pubkey))
+            .map(|w| w.insecure_clone())
+            .collect(),
+    };
+
+    let funding_arc = Arc::clone(&state.services.funding);
+    let mut funding = funding_arc.write().await;
+    let job_result = funding
+        .initiate_funding_job(
+            Arc::clone(&state.rpc),
+            distro_wallet,
+            targets,
+            relays,
+            config.commitment,
+        )
+        .await;
+
+    let job = match job_result {
+        Err(Error::RentExemption(msg)) => return bad_request(&msg),
+        Err(Error::CompromisedAccount(msg)) => return confilict(&msg),
         Err(err) => {
-            eprintln!("Error getting wallets for funding: {}", err);
+            eprintln!("Error initiating funding job {}", err);
+            return server_error("There was an internal error starting the fundin job.");
+        }
+        Ok(j) => j,
+    };
+
+    let shortfall = match funding_shortfall(&state.rpc, job, config.commitment).await {
+        Err(err) => {
+            eprintln!("Error reading the funding wallet balance {}", err);
             return server_error("Internal error");
         }
+        Ok(s) => s,
+    };
+
+    let res = InitiateFundingResponse {
+        message: format!("Initiated funding."),
+        job: JobProperty::new(job, shortfall),
+    };
+
+    return (StatusCode::OK, Json(res)).into_response();
+}
+
+#[derive(Deserialize)]
+pub struct InitiateTokenFundingRequest {
+    #[serde(flatten)]
+    selection: WalletSelection,
+    mint: String,
+    /// In the mint's base units.
+    amount_per_wallet: String,
+    /// Sends from the treasury instead of a fresh funding wallet.
+    #[serde(default)]
+    from_treasury: bool,
+}
+
+/// Starts a job sending the same amount of a Token or Token-2022 mint to every wallet.
+pub async fn initiate_token_job(
+    State(state): State<AppState>,
+    Json(payload): Json<InitiateTokenFundingRequest>,
+) -> Response {
+    let Ok(mint) = Pubkey::from_str(&payload.mint) else {
+        return bad_request("The mint must be a valid public key");
+    };
+    let amount_per_wallet = match payload.amount_per_wallet.parse::<u64>() {
+        Ok(a) if a > 0 => a,
+        _ => return bad_request("Invalid amount string format"),
+    };
+
+    let db_arc = Arc::clone(&state.services.database);
+    let db = db_arc.read().await;
+    let config_arc = Arc::clone(&state.config);
+    let config = config_arc.read().await;
+
+    let wallets = match select_wallets(&db, &config, &payload.selection).await {
+        Err(res) => return res,
         Ok(w) => w,
     };
+    let Some(distro_wallet) = distro_wallet(&config, payload.from_treasury) else {
+        return bad_request("The treasury is not enabled.");
+    };
 
     let pubkeys: Vec<Pubkey> = wallets.iter().map(|w| w.pubkey()).collect();
-    let wallet_count = pubkeys.len() as u128;
-
-    if wallet_count == 0 {
+    if pubkeys.is_empty() {
         return bad_request("There are 0 wallets.");
     }
 
     let funding_arc = Arc::clone(&state.services.funding);
     let mut funding = funding_arc.write().await;
     let job_result = funding
-        .initiate_funding_job(state.rpc_url, pubkeys, lamports_per_wallet)
+        .initiate_token_funding_job(
+            Arc::clone(&state.rpc),
+            distro_wallet,
+            pubkeys,
+            mint,
+            amount_per_wallet,
+            config.commitment,
+        )
         .await;
 
     let job = match job_result {
+        Err(Error::InvalidMint(msg)) => return bad_request(&msg),
+        Err(Error::CompromisedAccount(msg)) => return confilict(&msg),
         Err(err) => {
-            eprintln!("Error initiating funding job {}", err);
-            return server_error("There was an internal error starting the fundin job.");
+            eprintln!("Error initiating token funding job {}", err);
+            return server_error("There was an internal error starting the funding job.");
         }
         Ok(j) => j,
     };
 
+    let shortfall = match funding_shortfall(&state.rpc, job, config.commitment).await {
+        Err(err) => {
+            eprintln!("Error reading the funding wallet balances {}", err);
+            return server_error("Internal error");
+        }
+        Ok(s) => s,
+    };
+
     let res = InitiateFundingResponse {
-        message: format!("Initiated funding."),
-        job: JobProperty {
-            funding_wallet_pubkey: job.distro_wallet.pubkey().to_string(),
-            total_funding_lamports: job.total_funding_lamports.to_string(),
-        },
+        message: String::from("Initiated token funding."),
+        job: JobProperty::new(job, shortfall),
     };
 
-    return (StatusCode::OK, Json(res)).into_response();
+    (StatusCode::OK, Json(res)).into_response()
+}
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct CompleteFundingRequest {
+    /// Simulate every transfer of the job instead of sending it.
+    dry_run: bool,
+    /// Overrides the configured confirmation commitment.
+    commitment: Option<Commitment>,
 }
 
 #[derive(Serialize)]
 pub struct CompleteFundingResponse {
     message: String,
+    /// Transactions of the job, confirmed ones aren't sent again.
+    confirmed: usize,
+    /// Waiting on a relay whose funding failed.
+    pending: usize,
+    failed: Vec<FailedFundingTransfer>,
 }
 
-pub async fn complete_job(State(state): State<AppState>) -> impl IntoResponse {
+#[derive(Serialize)]
+pub struct FailedFundingTransfer {
+    payer: String,
+    error: String,
+}
+
+#[derive(Serialize)]
+pub struct DryRunFundingResponse {
+    message: String,
+    simulations: Vec<SimulatedTransaction>,
+}
+
+pub async fn complete_job(
+    State(state): State<AppState>,
+    payload: Option<Json<CompleteFundingRequest>>,
+) -> impl IntoResponse {
+    // The body is optional, completing without one sends the job.
+    let Json(params) = payload.unwrap_or_default();
+    let commitment = state
+        .config
+        .read()
+        .await
+        .commitment
+        .with_confirmation(params.commitment);
+
+    if params.dry_run {
+        return dry_run_job(state, commitment).await;
+    }
+
     let funding_arc = Arc::clone(&state.services.funding);
-    let funding = funding_arc.write().await;
+    let mut funding = funding_arc.write().await;
     let completion_result = funding
-        .complete_funding_job(state.rpc_url, state.services.websocket)
+        .complete_funding_job(
+            state.rpc,
+            state.services.websocket,
+            state.services.blockhashes,
+            commitment,
+        )
         .await;
 
     if completion_result.is_err() {
@@ -115,6 +558,8 @@ pub async fn complete_job(State(state): State<AppState>) -> impl IntoResponse {
                 }),
             )
                 .into_response();
+        } else if let Err(Error::RentExemption(msg)) = completion_result {
+            return confilict(&msg);
         } else if let Err(Error::InsufficientFunding(_)) = completion_result {
             return (
                 StatusCode::CONFLICT,
@@ -138,9 +583,48 @@ pub async fn complete_job(State(state): State<AppState>) -> impl IntoResponse {
         }
     }
 
+    let progress = completion_result.unwrap();
+    let message = if progress.failed.is_empty() && progress.pending == 0 {
+        String::from("Completed funding.")
+    } else {
+        String::from("Funding is incomplete, complete the job again to resume.")
+    };
     let res = CompleteFundingResponse {
-        message: format!("Completed funding."),
+        message,
+        confirmed: progress.confirmed,
+        pending: progress.pending,
+        failed: progress
+            .failed
+            .into_iter()
+            .map(|(payer, error)| FailedFundingTransfer {
+                payer: payer.to_string(),
+                error,
+            })
+            .collect(),
     };
 
     return (StatusCode::OK, Json(res)).into_response();
 }
+
+async fn dry_run_job(state: AppState, commitment: CommitmentConfig) -> Response {
+    let funding_arc = Arc::clone(&state.services.funding);
+    let funding = funding_arc.read().await;
+    let dry_run_result = funding
+        .dry_run_funding_job(state.rpc, state.services.blockhashes, commitment)
+        .await;
+
+    match dry_run_result {
+        Err(Error::FundingJobNotStarted(_)) => confilict("No active funding job."),
+        Err(err) => {
+            eprintln!("Error simulating funding job: {}", err);
+            server_error("Internal error.")
+        }
+        Ok(reports) => {
+            let res = DryRunFundingResponse {
+                message: String::from("Simulated funding, nothing was sent."),
+                simulations: reports.iter().map(SimulatedTransaction::from).collect(),
+            };
+            (StatusCode::OK, Json(res)).into_response()
+        }
+    }
+}
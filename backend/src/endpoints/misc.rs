@@ -1,6 +1,103 @@
+use std::str::FromStr;
+
+use axum::response::Response;
 use serde::Serialize;
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use tokio_rusqlite::Connection;
+
+use crate::{
+    config::Config,
+    endpoints::responses::{bad_request, server_error},
+    storage::mnemonic_wallet_storage::{get_treasury_wallet, get_wallets_by_pubkey},
+    txn_factory::simulation::SimulationReport,
+};
 
 #[derive(Serialize)]
 pub struct ErrorResponse {
     pub message: String,
 }
+
+#[derive(Serialize)]
+pub struct SimulatedBalanceChange {
+    pub pubkey: String,
+    pub pre_lamports: String,
+    pub post_lamports: String,
+    pub change_lamports: String,
+}
+
+#[derive(Serialize)]
+pub struct SimulatedTransaction {
+    pub signature: String,
+    pub fee_lamports: Option<String>,
+    pub compute_units: u64,
+    pub balance_changes: Vec<SimulatedBalanceChange>,
+    pub logs: Vec<String>,
+    pub err: Option<String>,
+}
+
+impl From<&SimulationReport> for SimulatedTransaction {
+    fn from(report: &SimulationReport) -> Self {
+        Self {
+            signature: report.signature.clone(),
+            fee_lamports: report.fee.map(|f| f.to_string()),
+            compute_units: report.compute_units,
+            balance_changes: report
+                .balance_changes
+                .iter()
+                .map(|c| SimulatedBalanceChange {
+                    pubkey: c.pubkey.clone(),
+                    pre_lamports: c.pre_lamports.to_string(),
+                    post_lamports: c.post_lamports.to_string(),
+                    change_lamports: (c.post_lamports as i128 - c.pre_lamports as i128).to_string(),
+                })
+                .collect(),
+            logs: report.logs.clone(),
+            err: report.err.clone(),
+        }
+    }
+}
+
+/// The wallet sponsoring the fees of a request, `treasury` or a fleet wallet pubkey,
+/// which can't be one of the wallets it sponsors.
+pub async fn fee_payer_wallet(
+    db: &Connection,
+    config: &Config,
+    fee_payer: &str,
+    sponsored_wallets: &[Keypair],
+) -> Result<Keypair, Response> {
+    let wallet = if fee_payer == "treasury" {
+        match get_treasury_wallet(config) {
+            Some(treasury) => treasury,
+            None => return Err(bad_request("The treasury is not enabled.")),
+        }
+    } else {
+        if Pubkey::from_str(fee_payer).is_err() {
+            return Err(bad_request("Fee payer is not a valid public key"));
+        }
+        let wallets = get_wallets_by_pubkey(db, config, &vec![fee_payer.to_string()]).await;
+        match wallets.map(|mut w| w.pop()) {
+            Err(err) => {
+                eprintln!("Error getting the fee payer: {}", err);
+                return Err(server_error("Internal error."));
+            }
+            Ok(None) => {
+                return Err(bad_request(&format!(
+                    "{} is not a fleet wallet.",
+                    fee_payer
+                )));
+            }
+            Ok(Some(w)) => w,
+        }
+    };
+
+    if sponsored_wallets
+        .iter()
+        .any(|w| w.pubkey() == wallet.pubkey())
+    {
+        return Err(bad_request(
+            "The fee payer can't be one of the wallets it sponsors.",
+        ));
+    }
+
+    Ok(wallet)
+}
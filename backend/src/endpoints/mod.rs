@@ -1,6 +1,13 @@
+pub mod blockhash;
 pub mod collecting;
 pub mod funding;
+#[cfg(feature = "mock")]
+pub mod paper;
+pub mod rebalancing;
 mod responses;
+pub mod swap;
+pub mod treasury;
 pub mod wallet;
+pub mod wsol;
 
 mod misc;
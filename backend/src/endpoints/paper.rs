@@ -0,0 +1,146 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+};
+
+use axum::{
+    Json,
+    extract::State,
+    response::{IntoResponse, Response},
+};
+use reqwest::StatusCode;
+use serde::Serialize;
+use solana_sdk::{account::Account, program_pack::Pack, pubkey::Pubkey, signer::Signer};
+use spl_token_interface::state::Account as SplTokenAccount;
+use tokio::{sync::Semaphore, task::JoinSet};
+
+use crate::{
+    AppState,
+    endpoints::responses::{confilict, server_error},
+    errors::errors::Error,
+    paper::{cluster_accounts, cluster_token_accounts},
+    storage::mnemonic_wallet_storage::get_all_wallets,
+    tokens::token_accounts::get_token_accounts,
+};
+
+/// Wallets whose token accounts are copied at the same time.
+const TOKEN_ACCOUNT_LOOKUPS: usize = 16;
+
+#[derive(Serialize)]
+pub struct SyncPaperFleetResponse {
+    message: String,
+    synced_wallets: usize,
+    synced_token_accounts: usize,
+}
+
+/// Copies the fleet's accounts from the real cluster into the paper ledger: the wallets,
+/// their token accounts and the mints of those. Paper token accounts the cluster doesn't
+/// have are closed, so the paper fleet ends up exactly like the real one.
+pub async fn sync_fleet(State(state): State<AppState>) -> Response {
+    let Some(paper) = &state.services.paper else {
+        return confilict("Paper trading mode is not enabled.");
+    };
+    let Some(cluster) = &paper.cluster else {
+        return confilict("No cluster to copy balances from, set HELIUS_API_KEY.");
+    };
+
+    let db_arc = Arc::clone(&state.services.database);
+    let db = db_arc.read().await;
+    let config_arc = Arc::clone(&state.config);
+    let config = config_arc.read().await;
+    let commitment = config.commitment.read;
+
+    let wallets = match get_all_wallets(&db, &config, 1, 9999).await {
+        Err(err) => {
+            eprintln!("Error getting wallets to sync: {}", err);
+            return server_error("Internal error.");
+        }
+        Ok(w) => w,
+    };
+    let pubkeys: Vec<Pubkey> = wallets.iter().map(|w| w.pubkey()).collect();
+
+    let mut accounts: HashMap<Pubkey, Account> =
+        match cluster_accounts(cluster, "paper_sync", &pubkeys, commitment).await {
+            Err(err) => {
+                eprintln!("Error fetching cluster balances {}", err);
+                return server_error("Failed to fetch the fleet's balances from the cluster.");
+            }
+            Ok(a) => a.into_iter().collect(),
+        };
+
+    let lookups = Arc::new(Semaphore::new(TOKEN_ACCOUNT_LOOKUPS));
+    let mut token_set = JoinSet::new();
+    for &owner in &pubkeys {
+        let cluster = Arc::clone(cluster);
+        let rpc = Arc::clone(&state.rpc);
+        let lookups = Arc::clone(&lookups);
+        token_set.spawn(async move {
+            let _permit = lookups.acquire_owned().await;
+            let copied = cluster_token_accounts(&cluster, &owner, commitment).await?;
+            let paper = get_token_accounts(&rpc, &owner, commitment).await?;
+            Ok::<_, Error>((copied, paper))
+        });
+    }
+
+    let mut token_accounts = Vec::new();
+    let mut paper_token_accounts = Vec::new();
+    while let Some(res) = token_set.join_next().await {
+        match res {
+            Ok(Ok((copied, paper))) => {
+                token_accounts.extend(copied);
+                paper_token_accounts.extend(paper.into_iter().map(|a| a.pubkey));
+            }
+            Ok(Err(err)) => {
+                eprintln!("Error fetching cluster token accounts {}", err);
+                return server_error(
+                    "Failed to fetch the fleet's token accounts from the cluster.",
+                );
+            }
+            Err(err) => eprintln!("Join error: {:?}", err),
+        }
+    }
+
+    // Transfers and burns read the mint, which the paper ledger may not have yet.
+    let mints: HashSet<Pubkey> = token_accounts
+        .iter()
+        .filter_map(|(_, a)| SplTokenAccount::unpack_from_slice(&a.data).ok())
+        .map(|a| a.mint)
+        .collect();
+    let mints: Vec<Pubkey> = mints.into_iter().collect();
+    let mint_accounts = match cluster_accounts(cluster, "paper_sync", &mints, commitment).await {
+        Err(err) => {
+            eprintln!("Error fetching cluster mints {}", err);
+            return server_error("Failed to fetch the fleet's mints from the cluster.");
+        }
+        Ok(a) => a,
+    };
+
+    // Copy the full account so wallets taken over on the cluster stay flagged on paper.
+    for pubkey in &pubkeys {
+        let account = accounts
+            .remove(pubkey)
+            .unwrap_or_else(|| mock_node::system_account(0));
+        paper.node.set_account(*pubkey, account);
+    }
+    for (pubkey, account) in mint_accounts {
+        paper.node.set_account(pubkey, account);
+    }
+    let copied: HashSet<Pubkey> = token_accounts.iter().map(|(p, _)| *p).collect();
+    for pubkey in paper_token_accounts.iter().filter(|p| !copied.contains(p)) {
+        paper
+            .node
+            .set_account(*pubkey, mock_node::system_account(0));
+    }
+    let synced_token_accounts = token_accounts.len();
+    for (pubkey, account) in token_accounts {
+        paper.node.set_account(pubkey, account);
+    }
+
+    let res = SyncPaperFleetResponse {
+        message: String::from("Copied fleet accounts into the paper ledger."),
+        synced_wallets: pubkeys.len(),
+        synced_token_accounts,
+    };
+
+    (StatusCode::OK, Json(res)).into_response()
+}
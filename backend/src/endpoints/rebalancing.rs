@@ -0,0 +1,260 @@
+use std::{collections::HashMap, str::FromStr, sync::Arc};
+
+use axum::{
+    Json,
+    extract::State,
+    response::{IntoResponse, Response},
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+
+use crate::{
+    AppState,
+    endpoints::{
+        funding::{WalletSelection, select_wallets},
+        misc::{SimulatedTransaction, fee_payer_wallet},
+        responses::{bad_request, confilict, server_error},
+    },
+    errors::errors::Error,
+    rebalancing::rebalance::{
+        RebalanceBatch, RebalanceTarget, dry_run_rebalance, plan_rebalance, rebalance,
+    },
+    rpc::commitment::Commitment,
+};
+
+/// What the wallets' balances are evened out to.
+#[derive(Deserialize, Default, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum RebalanceMode {
+    /// Every wallet ends with the same balance.
+    #[default]
+    Equal,
+    /// Every wallet ends with at least `minimum`.
+    Minimum,
+    /// Balances follow `weights`.
+    Weights,
+}
+
+#[derive(Deserialize)]
+pub struct RebalanceRequest {
+    #[serde(flatten)]
+    selection: WalletSelection,
+    #[serde(default)]
+    distribution: RebalanceMode,
+    /// Lamports, for the `minimum` distribution.
+    minimum: Option<String>,
+    /// Fleet wallet pubkey to its weight, for the `weights` distribution. Targeted
+    /// wallets without one get nothing.
+    weights: Option<HashMap<String, u64>>,
+    /// Wallets short of their target by at most this many lamports are left alone.
+    tolerance: Option<String>,
+    /// `treasury` or a fleet wallet outside the rebalance, it co-signs and pays every fee.
+    fee_payer: Option<String>,
+    /// Simulate the transfers instead of sending them.
+    #[serde(default)]
+    dry_run: bool,
+    commitment: Option<Commitment>,
+}
+
+#[derive(Serialize)]
+pub struct RebalanceTransfer {
+    to: String,
+    lamports: String,
+}
+
+#[derive(Serialize)]
+pub struct RebalanceTransactionResult {
+    from: String,
+    transfers: Vec<RebalanceTransfer>,
+    signature: Option<String>,
+    simulation: Option<SimulatedTransaction>,
+    error: Option<String>,
+}
+
+impl RebalanceTransactionResult {
+    fn new<T>(batch: &RebalanceBatch<T>) -> Self {
+        Self {
+            from: batch.transaction.from.to_string(),
+            transfers: batch
+                .transaction
+                .transfers
+                .iter()
+                .map(|(to, lamports)| RebalanceTransfer {
+                    to: to.to_string(),
+                    lamports: lamports.to_string(),
+                })
+                .collect(),
+            signature: None,
+            simulation: None,
+            error: batch.result.as_ref().err().map(|e| e.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct RebalanceResponse {
+    message: String,
+    transactions: Vec<RebalanceTransactionResult>,
+}
+
+/// Moves SOL between fleet wallets until their balances match the requested distribution.
+pub async fn rebalance_fleet(
+    State(state): State<AppState>,
+    Json(payload): Json<RebalanceRequest>,
+) -> Response {
+    let tolerance = match payload.tolerance.as_deref().map(str::parse::<u64>) {
+        None => 0,
+        Some(Ok(t)) => t,
+        Some(Err(_)) => return bad_request("The tolerance is in invalid format"),
+    };
+
+    let config_arc = Arc::clone(&state.config);
+    let config = config_arc.read().await;
+    let db_arc = Arc::clone(&state.services.database);
+    let db = db_arc.read().await;
+
+    let wallets = match select_wallets(&db, &config, &payload.selection).await {
+        Err(res) => return res,
+        Ok(w) => w,
+    };
+    if wallets.len() < 2 {
+        return bad_request("Rebalancing needs at least 2 wallets.");
+    }
+    let fee_payer = match &payload.fee_payer {
+        None => None,
+        Some(fee_payer) => match fee_payer_wallet(&db, &config, fee_payer, &wallets).await {
+            Ok(w) => Some(w),
+            Err(res) => return res,
+        },
+    };
+    let fee_payer_pubkey = fee_payer.as_ref().map(|f| f.pubkey());
+
+    let target = match (payload.distribution, &payload.minimum, &payload.weights) {
+        (RebalanceMode::Equal, None, None) => RebalanceTarget::Equal,
+        (RebalanceMode::Minimum, Some(minimum), None) => match minimum.parse::<u64>() {
+            Ok(m) => RebalanceTarget::Minimum(m),
+            Err(_) => return bad_request("The minimum is in invalid format"),
+        },
+        (RebalanceMode::Weights, None, Some(weights)) => {
+            let mut by_pubkey = HashMap::new();
+            for (pubkey, weight) in weights {
+                let parsed = Pubkey::from_str(pubkey)
+                    .ok()
+                    .filter(|p| wallets.iter().any(|w| w.pubkey() == *p));
+                let Some(parsed) = parsed else {
+                    return bad_request(&format!(
+                        "{} is not one of the targeted fleet wallets.",
+                        pubkey
+                    ));
+                };
+                by_pubkey.insert(parsed, *weight);
+            }
+            let weights: Vec<u64> = wallets
+                .iter()
+                .map(|w| by_pubkey.get(&w.pubkey()).copied().unwrap_or(0))
+                .collect();
+            // All zero leaves nothing to split by, every target would be 0.
+            if weights.iter().all(|w| *w == 0) {
+                return bad_request("At least one weight must be above 0.");
+            }
+            RebalanceTarget::Weights(weights)
+        }
+        _ => {
+            return bad_request(
+                "Give minimum only with the minimum distribution and weights only with the weights distribution.",
+            );
+        }
+    };
+
+    let commitment = config.commitment.with_confirmation(payload.commitment);
+    let plan = match plan_rebalance(
+        &state.rpc,
+        &wallets,
+        fee_payer_pubkey.as_ref(),
+        &target,
+        tolerance,
+        commitment,
+    )
+    .await
+    {
+        Err(Error::InsufficientSol(msg))
+        | Err(Error::CompromisedAccount(msg))
+        | Err(Error::RentExemption(msg)) => {
+            return confilict(&msg);
+        }
+        Err(err) => {
+            eprintln!("Error planning the rebalance: {}", err);
+            return server_error("Internal error.");
+        }
+        Ok(p) => p,
+    };
+
+    if plan.is_empty() {
+        let res = RebalanceResponse {
+            message: String::from("The wallets are already balanced."),
+            transactions: Vec::new(),
+        };
+        return (StatusCode::OK, Json(res)).into_response();
+    }
+
+    if payload.dry_run {
+        let batches = match dry_run_rebalance(
+            &state.rpc,
+            state.services.blockhashes,
+            &wallets,
+            fee_payer.as_ref(),
+            plan,
+            commitment,
+        )
+        .await
+        {
+            Err(err) => {
+                eprintln!("Error simulating the rebalance: {}", err);
+                return server_error("Internal error.");
+            }
+            Ok(b) => b,
+        };
+
+        let res = RebalanceResponse {
+            message: String::from("Simulated the rebalance, nothing was sent."),
+            transactions: batches
+                .iter()
+                .map(|batch| RebalanceTransactionResult {
+                    simulation: batch.result.as_ref().ok().map(SimulatedTransaction::from),
+                    ..RebalanceTransactionResult::new(batch)
+                })
+                .collect(),
+        };
+        return (StatusCode::OK, Json(res)).into_response();
+    }
+
+    let batches = rebalance(
+        &state.rpc,
+        state.services.websocket,
+        state.services.blockhashes,
+        &wallets,
+        fee_payer.as_ref(),
+        plan,
+        commitment,
+    )
+    .await;
+
+    let res = RebalanceResponse {
+        message: String::from("Rebalanced the wallets."),
+        transactions: batches
+            .iter()
+            .map(|batch| {
+                if let Err(err) = &batch.result {
+                    eprintln!("Rebalance transaction failed: {}", err);
+                }
+                RebalanceTransactionResult {
+                    signature: batch.result.as_ref().ok().cloned(),
+                    ..RebalanceTransactionResult::new(batch)
+                }
+            })
+            .collect(),
+    };
+
+    (StatusCode::OK, Json(res)).into_response()
+}
@@ -0,0 +1,503 @@
+use std::{str::FromStr, sync::Arc};
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+use tokio::task::JoinSet;
+
+use crate::{
+    AppState,
+    endpoints::responses::{bad_request, confilict, server_error},
+    errors::errors::Error,
+    storage::{mnemonic_wallet_storage::get_wallets_by_pubkey, swap_storage::save_swap},
+    swapping::{
+        liquidating::{LiquidationOrder, LiquidationOutcome, liquidate as liquidate_wallet},
+        swap::swap as swap_with_router,
+        swap_provider::{DEFAULT_SLIPPAGE_BPS, SwapOrder, WSOL_MINT},
+    },
+};
+
+/// Validates the order's parameters, the error is meant for a bad request.
+fn parse_order(
+    input_mint: &str,
+    output_mint: &str,
+    amount: &str,
+    slippage_bps: Option<u16>,
+    max_price_impact_pct: Option<f64>,
+) -> Result<SwapOrder, &'static str> {
+    let amount = match amount.parse::<u64>() {
+        Ok(a) => a,
+        Err(_) => {
+            return Err("The amount is in invalid format");
+        }
+    };
+
+    if Pubkey::from_str(input_mint).is_err() || Pubkey::from_str(output_mint).is_err() {
+        return Err("Mints must be valid public keys");
+    }
+
+    let slippage_bps = parse_guards(slippage_bps, max_price_impact_pct)?;
+
+    Ok(SwapOrder {
+        input_mint: input_mint.to_owned(),
+        output_mint: output_mint.to_owned(),
+        amount,
+        slippage_bps,
+        max_price_impact_pct,
+    })
+}
+
+/// Validates the guards and returns the slippage to use.
+fn parse_guards(
+    slippage_bps: Option<u16>,
+    max_price_impact_pct: Option<f64>,
+) -> Result<u16, &'static str> {
+    let slippage_bps = slippage_bps.unwrap_or(DEFAULT_SLIPPAGE_BPS);
+    if slippage_bps > 10_000 {
+        return Err("slippage_bps can be at most 10000");
+    }
+    if max_price_impact_pct.is_some_and(|p| !(0.0..=100.0).contains(&p)) {
+        return Err("max_price_impact_pct must be between 0 and 100");
+    }
+
+    Ok(slippage_bps)
+}
+
+#[derive(Deserialize)]
+pub struct SwapRequest {
+    input_mint: String,
+    output_mint: String,
+    /// Input amount per wallet, in the input mint's base units.
+    amount: String,
+    wallets: Vec<String>,
+    slippage_bps: Option<u16>,
+    max_price_impact_pct: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct SwapResponse {
+    message: String,
+    results: Vec<SwapWalletResult>,
+}
+
+#[derive(Serialize)]
+pub struct SwapWalletResult {
+    pubkey: String,
+    signature: Option<String>,
+    provider: Option<String>,
+    /// Realized amounts, read from the landed transaction.
+    in_amount: Option<String>,
+    out_amount: Option<String>,
+    quoted_out_amount: Option<String>,
+    minimum_out_amount: Option<String>,
+    /// Output per input in whole tokens.
+    effective_price: Option<f64>,
+    fee_lamports: Option<String>,
+    /// Rent put into token accounts opened by the swap, negative if closed ones refunded more.
+    rent_lamports: Option<String>,
+    error: Option<String>,
+}
+
+impl SwapWalletResult {
+    fn failed(pubkey: String, error: String) -> Self {
+        Self {
+            pubkey,
+            signature: None,
+            provider: None,
+            in_amount: None,
+            out_amount: None,
+            quoted_out_amount: None,
+            minimum_out_amount: None,
+            effective_price: None,
+            fee_lamports: None,
+            rent_lamports: None,
+            error: Some(error),
+        }
+    }
+}
+
+/// Swaps the same amount on every given fleet wallet concurrently.
+pub async fn swap(State(state): State<AppState>, Json(payload): Json<SwapRequest>) -> Response {
+    let order = match parse_order(
+        &payload.input_mint,
+        &payload.output_mint,
+        &payload.amount,
+        payload.slippage_bps,
+        payload.max_price_impact_pct,
+    ) {
+        Ok(o) => o,
+        Err(msg) => return bad_request(msg),
+    };
+    if payload.wallets.iter().any(|p| Pubkey::from_str(p).is_err()) {
+        return bad_request("Wallets must be valid public keys");
+    }
+
+    let config_arc = Arc::clone(&state.config);
+    let config = config_arc.read().await;
+    let db_arc = Arc::clone(&state.services.database);
+    let db = db_arc.read().await;
+
+    let wallets = match get_wallets_by_pubkey(&db, &config, &payload.wallets).await {
+        Err(err) => {
+            eprintln!("Error getting wallets to swap: {}", err);
+            return server_error("Internal error.");
+        }
+        Ok(w) => w,
+    };
+
+    let mut results: Vec<SwapWalletResult> = payload
+        .wallets
+        .iter()
+        .filter(|p| !wallets.iter().any(|w| &w.pubkey().to_string() == *p))
+        .map(|p| SwapWalletResult::failed(p.to_owned(), String::from("Not a fleet wallet.")))
+        .collect();
+
+    let mut swap_set = JoinSet::new();
+    for wallet in wallets {
+        let rpc = Arc::clone(&state.rpc);
+        let router = Arc::clone(&state.services.swaps);
+        let websocket = state.services.websocket.clone();
+        let commitment = config.commitment;
+        let order = order.clone();
+
+        swap_set.spawn(async move {
+            let result =
+                swap_with_router(&rpc, &router, &order, &wallet, websocket, commitment).await;
+            (wallet.pubkey().to_string(), result)
+        });
+    }
+
+    while let Some(res) = swap_set.join_next().await {
+        match res {
+            Ok((pubkey, Ok(swap))) => {
+                if let Err(err) = save_swap(&db, &pubkey, &order, &swap).await {
+                    eprintln!("Failed to store swap {}: {}", swap.signature, err);
+                }
+                results.push(SwapWalletResult {
+                    error: swap
+                        .fill
+                        .err
+                        .as_ref()
+                        .map(|err| format!("Swap failed on chain: {}", err)),
+                    pubkey,
+                    signature: Some(swap.signature),
+                    provider: Some(swap.provider.to_owned()),
+                    in_amount: Some(swap.fill.in_amount.to_string()),
+                    out_amount: Some(swap.fill.out_amount.to_string()),
+                    quoted_out_amount: Some(swap.quoted_out_amount.to_string()),
+                    minimum_out_amount: Some(swap.min_out_amount.to_string()),
+                    effective_price: swap.fill.effective_price,
+                    fee_lamports: Some(swap.fill.fee_lamports.to_string()),
+                    rent_lamports: Some(swap.fill.rent_lamports.to_string()),
+                });
+            }
+            Ok((pubkey, Err(err))) => {
+                eprintln!("Swap of {} failed: {}", pubkey, err);
+                results.push(SwapWalletResult::failed(pubkey, err.to_string()));
+            }
+            Err(err) => eprintln!("Join error: {:?}", err),
+        }
+    }
+
+    let swapped = results.iter().filter(|r| r.error.is_none()).count();
+    let res = SwapResponse {
+        message: format!(
+            "Swapped on {} of {} wallets.",
+            swapped,
+            payload.wallets.len()
+        ),
+        results,
+    };
+
+    (StatusCode::OK, Json(res)).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct SwapQuoteRequest {
+    input_mint: String,
+    output_mint: String,
+    amount: String,
+    slippage_bps: Option<u16>,
+    max_price_impact_pct: Option<f64>,
+    /// Fleet wallet that would swap, providers quoting together with the transaction need it.
+    wallet: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct SwapQuoteResponse {
+    message: String,
+    /// Provider a swap would go through, `None` if every quote exceeds a guard.
+    best_provider: Option<String>,
+    quotes: Vec<ProviderQuote>,
+}
+
+#[derive(Serialize)]
+pub struct ProviderQuote {
+    provider: String,
+    in_amount: String,
+    expected_out_amount: String,
+    minimum_out_amount: String,
+    slippage_bps: u16,
+    price_impact_pct: f64,
+    /// Why a swap would refuse this quote.
+    rejected: Option<String>,
+}
+
+/// Previews what a swap would do, best quote first, without signing anything.
+pub async fn quote(
+    State(state): State<AppState>,
+    Query(params): Query<SwapQuoteRequest>,
+) -> Response {
+    let order = match parse_order(
+        &params.input_mint,
+        &params.output_mint,
+        &params.amount,
+        params.slippage_bps,
+        params.max_price_impact_pct,
+    ) {
+        Ok(o) => o,
+        Err(msg) => return bad_request(msg),
+    };
+
+    let taker = match params.wallet.as_deref().map(Pubkey::from_str) {
+        Some(Err(_)) => return bad_request("Wallet must be a valid public key"),
+        Some(Ok(p)) => Some(p),
+        None => None,
+    };
+
+    let quotes = match state.services.swaps.quotes(&order, taker.as_ref()).await {
+        Ok(q) => q,
+        Err(Error::Swap(msg)) => return confilict(&msg),
+        Err(err) => {
+            eprintln!("Error quoting swap {}", err);
+            return server_error("Failed to quote the swap.");
+        }
+    };
+
+    let quotes: Vec<ProviderQuote> = quotes
+        .iter()
+        .map(|q| ProviderQuote {
+            provider: q.provider.to_owned(),
+            in_amount: q.in_amount.to_string(),
+            expected_out_amount: q.out_amount.to_string(),
+            minimum_out_amount: q.min_out_amount.to_string(),
+            slippage_bps: q.slippage_bps,
+            price_impact_pct: q.price_impact_pct,
+            rejected: q.check_guards(&order).err().map(|err| err.to_string()),
+        })
+        .collect();
+
+    let res = SwapQuoteResponse {
+        message: format!("Got {} quotes.", quotes.len()),
+        best_provider: quotes
+            .iter()
+            .find(|q| q.rejected.is_none())
+            .map(|q| q.provider.clone()),
+        quotes,
+    };
+
+    (StatusCode::OK, Json(res)).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct LiquidateRequest {
+    wallets: Vec<String>,
+    /// Mint the holdings are swapped into, SOL if not given.
+    output_mint: Option<String>,
+    slippage_bps: Option<u16>,
+    max_price_impact_pct: Option<f64>,
+    /// Overrides the configured dust threshold.
+    dust_lamports: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct LiquidateResponse {
+    message: String,
+    results: Vec<LiquidationMintResult>,
+}
+
+#[derive(Serialize)]
+pub struct LiquidationMintResult {
+    pubkey: String,
+    /// `None` when the wallet's holdings couldn't be listed.
+    mint: Option<String>,
+    amount: Option<String>,
+    /// Why the holding was left alone.
+    skipped: Option<String>,
+    signature: Option<String>,
+    provider: Option<String>,
+    out_amount: Option<String>,
+    effective_price: Option<f64>,
+    fee_lamports: Option<String>,
+    error: Option<String>,
+}
+
+impl LiquidationMintResult {
+    fn new(pubkey: String, mint: Option<String>, amount: Option<u64>) -> Self {
+        Self {
+            pubkey,
+            mint,
+            amount: amount.map(|a| a.to_string()),
+            skipped: None,
+            signature: None,
+            provider: None,
+            out_amount: None,
+            effective_price: None,
+            fee_lamports: None,
+            error: None,
+        }
+    }
+}
+
+/// Swaps every token held by the given fleet wallets into SOL or the output mint.
+/// Wallets run concurrently, the mints of one wallet one after another.
+pub async fn liquidate(
+    State(state): State<AppState>,
+    Json(payload): Json<LiquidateRequest>,
+) -> Response {
+    let output_mint = payload.output_mint.unwrap_or_else(|| WSOL_MINT.to_owned());
+    if Pubkey::from_str(&output_mint).is_err() {
+        return bad_request("The output mint must be a valid public key");
+    }
+    let slippage_bps = match parse_guards(payload.slippage_bps, payload.max_price_impact_pct) {
+        Ok(s) => s,
+        Err(msg) => return bad_request(msg),
+    };
+    if payload.wallets.iter().any(|p| Pubkey::from_str(p).is_err()) {
+        return bad_request("Wallets must be valid public keys");
+    }
+
+    let config_arc = Arc::clone(&state.config);
+    let config = config_arc.read().await;
+    let db_arc = Arc::clone(&state.services.database);
+    let db = db_arc.read().await;
+
+    let dust_lamports = match payload.dust_lamports.as_deref().map(str::parse::<u64>) {
+        Some(Err(_)) => return bad_request("dust_lamports is in invalid format"),
+        Some(Ok(d)) => d,
+        None => config.swap.dust_lamports,
+    };
+    let liquidation = LiquidationOrder {
+        output_mint,
+        slippage_bps,
+        max_price_impact_pct: payload.max_price_impact_pct,
+        dust_lamports,
+    };
+
+    let wallets = match get_wallets_by_pubkey(&db, &config, &payload.wallets).await {
+        Err(err) => {
+            eprintln!("Error getting wallets to liquidate: {}", err);
+            return server_error("Internal error.");
+        }
+        Ok(w) => w,
+    };
+
+    let mut results: Vec<LiquidationMintResult> = payload
+        .wallets
+        .iter()
+        .filter(|p| !wallets.iter().any(|w| &w.pubkey().to_string() == *p))
+        .map(|p| LiquidationMintResult {
+            error: Some(String::from("Not a fleet wallet.")),
+            ..LiquidationMintResult::new(p.to_owned(), None, None)
+        })
+        .collect();
+
+    let mut liquidation_set = JoinSet::new();
+    for wallet in wallets {
+        let rpc = Arc::clone(&state.rpc);
+        let router = Arc::clone(&state.services.swaps);
+        let websocket = state.services.websocket.clone();
+        let commitment = config.commitment;
+        let liquidation = liquidation.clone();
+
+        liquidation_set.spawn(async move {
+            let result =
+                liquidate_wallet(&rpc, &router, &wallet, &liquidation, websocket, commitment).await;
+            (wallet.pubkey().to_string(), result)
+        });
+    }
+
+    while let Some(res) = liquidation_set.join_next().await {
+        let (pubkey, mint_results) = match res {
+            Ok((pubkey, Ok(r))) => (pubkey, r),
+            Ok((pubkey, Err(err))) => {
+                eprintln!("Listing the holdings of {} failed: {}", pubkey, err);
+                results.push(LiquidationMintResult {
+                    error: Some(err.to_string()),
+                    ..LiquidationMintResult::new(pubkey, None, None)
+                });
+                continue;
+            }
+            Err(err) => {
+                eprintln!("Join error: {:?}", err);
+                continue;
+            }
+        };
+
+        for mint_result in mint_results {
+            let order = mint_result.order;
+            let result = LiquidationMintResult::new(
+                pubkey.clone(),
+                Some(order.input_mint.clone()),
+                Some(order.amount),
+            );
+
+            results.push(match mint_result.outcome {
+                LiquidationOutcome::Skipped(reason) => LiquidationMintResult {
+                    skipped: Some(reason),
+                    ..result
+                },
+                LiquidationOutcome::Failed(err) => {
+                    eprintln!(
+                        "Liquidating {} of {} failed: {}",
+                        order.input_mint, pubkey, err
+                    );
+                    LiquidationMintResult {
+                        error: Some(err.to_string()),
+                        ..result
+                    }
+                }
+                LiquidationOutcome::Swapped(swap) => {
+                    if let Err(err) = save_swap(&db, &pubkey, &order, &swap).await {
+                        eprintln!("Failed to store swap {}: {}", swap.signature, err);
+                    }
+                    LiquidationMintResult {
+                        error: swap
+                            .fill
+                            .err
+                            .as_ref()
+                            .map(|err| format!("Swap failed on chain: {}", err)),
+                        signature: Some(swap.signature),
+                        provider: Some(swap.provider.to_owned()),
+                        out_amount: Some(swap.fill.out_amount.to_string()),
+                        effective_price: swap.fill.effective_price,
+                        fee_lamports: Some(swap.fill.fee_lamports.to_string()),
+                        ..result
+                    }
+                }
+            });
+        }
+    }
+
+    let swapped = results
+        .iter()
+        .filter(|r| r.signature.is_some() && r.error.is_none())
+        .count();
+    let skipped = results.iter().filter(|r| r.skipped.is_some()).count();
+    let res = LiquidateResponse {
+        message: format!(
+            "Swapped {} holdings, skipped {}, {} failed.",
+            swapped,
+            skipped,
+            results.len() - swapped - skipped
+        ),
+        results,
+    };
+
+    (StatusCode::OK, Json(res)).into_response()
+}
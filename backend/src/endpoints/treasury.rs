@@ -0,0 +1,63 @@
+use std::sync::Arc;
+
+use axum::{
+    Json,
+    extract::{Query, State},
+    response::{IntoResponse, Response},
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use solana_sdk::signer::Signer;
+
+use crate::{
+    AppState,
+    endpoints::responses::{confilict, server_error},
+    rpc::{commitment::Commitment, read::get_balance},
+    storage::mnemonic_wallet_storage::get_treasury_wallet,
+};
+
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct TreasuryRequest {
+    /// Overrides the configured read commitment.
+    commitment: Option<Commitment>,
+}
+
+#[derive(Serialize)]
+pub struct TreasuryResponse {
+    message: String,
+    /// Deposit address, funding jobs can draw from it with `from_treasury`.
+    pubkey: String,
+    lamports: String,
+}
+
+pub async fn treasury(
+    State(state): State<AppState>,
+    Query(params): Query<TreasuryRequest>,
+) -> Response {
+    let config_arc = Arc::clone(&state.config);
+    let config = config_arc.read().await;
+
+    let Some(treasury) = get_treasury_wallet(&config) else {
+        return confilict("The treasury is not enabled.");
+    };
+    let commitment = config.commitment.with_read(params.commitment);
+
+    let pubkey = treasury.pubkey().to_string();
+    let lamports = match get_balance(&state.rpc, "treasury_balance", &pubkey, commitment.read).await
+    {
+        Err(err) => {
+            eprintln!("Error getting the treasury balance: {}", err);
+            return server_error("Internal error.");
+        }
+        Ok(l) => l,
+    };
+
+    let res = TreasuryResponse {
+        message: String::from("Retrieved the treasury."),
+        pubkey,
+        lamports: lamports.to_string(),
+    };
+
+    (StatusCode::OK, Json(res)).into_response()
+}
@@ -1,4 +1,4 @@
-use std::sync::Arc;
+use std::{collections::HashMap, str::FromStr, sync::Arc};
 
 use axum::{
     Json,
@@ -7,18 +7,38 @@ use axum::{
 };
 use reqwest::StatusCode;
 use serde::{Deserialize, Serialize};
-use solana_sdk::signer::Signer;
+use solana_sdk::{pubkey::Pubkey, signer::Signer};
+use solana_system_interface::program as system_program;
+use tokio::{sync::Semaphore, task::JoinSet};
 
 use crate::{
     AppState,
-    endpoints::{misc::ErrorResponse, responses::server_error},
-    rpc::read::get_multiple_accounts,
-    storage::mnemonic_wallet_storage::{create_new_wallet, get_all_wallets},
+    endpoints::{
+        misc::{ErrorResponse, SimulatedTransaction, fee_payer_wallet},
+        responses::{bad_request, server_error},
+    },
+    rpc::{
+        commitment::Commitment,
+        read::{AccountEncoding, MAX_ACCOUNTS_PER_REQUEST, get_multiple_accounts},
+        send::request_airdrop,
+    },
+    storage::mnemonic_wallet_storage::{
+        create_new_wallet, get_all_wallets, get_wallet_labels, get_wallets_by_pubkey,
+        tag_wallets as store_wallet_tags,
+    },
+    tokens::{
+        balances::{TokenBalance, token_balances},
+        mints::get_mints,
+        reclaiming::{ReclaimBatch, dry_run_reclaim_rent, reclaim_rent},
+        token_accounts::{TokenAccount, get_token_accounts},
+    },
 };
 
 #[derive(Deserialize)]
 pub struct CreateWalletRequest {
     count: u16,
+    /// Tags the new wallets, e.g. to fund just them afterwards.
+    tag: Option<String>,
 }
 
 #[derive(Serialize)]
@@ -50,6 +70,13 @@ pub async fn create_wallets(
         }
     }
 
+    if let Some(tag) = payload.tag
+        && let Err(err) = store_wallet_tags(&db, pubkeys.clone(), tag).await
+    {
+        eprintln!("Error tagging new wallets: {}", err);
+        return server_error("Created the wallets but failed to tag them.");
+    }
+
     let res = CreateWalletResponse {
         message: format!("Created {} wallets", payload.count),
         pubkeys: pubkeys,
@@ -62,6 +89,12 @@ pub async fn create_wallets(
 pub struct ListWalletsRequest {
     page: u16,
     page_size: u16,
+    /// Overrides the configured read commitment.
+    commitment: Option<Commitment>,
+    /// Only report balances of this mint.
+    mint: Option<String>,
+    /// Also return each wallet's account data, in this encoding.
+    encoding: Option<AccountEncoding>,
 }
 
 impl Default for ListWalletsRequest {
@@ -69,6 +102,9 @@ impl Default for ListWalletsRequest {
         Self {
             page: 1,
             page_size: 999,
+            commitment: None,
+            mint: None,
+            encoding: None,
         }
     }
 }
@@ -77,14 +113,69 @@ impl Default for ListWalletsRequest {
 pub struct ListWalletsResponse {
     message: String,
     wallets: Vec<ListWalletResponseWallet>,
+    /// Token holdings summed over the listed wallets.
+    fleet_tokens: Vec<FleetTokenHolding>,
+}
+
+#[derive(Serialize)]
+pub struct WalletTokenBalance {
+    mint: String,
+    program_id: String,
+    amount: String,
+    decimals: u8,
+    ui_amount: f64,
+    ui_amount_string: String,
+}
+
+impl From<&TokenBalance> for WalletTokenBalance {
+    fn from(balance: &TokenBalance) -> Self {
+        Self {
+            mint: balance.mint.to_string(),
+            program_id: balance.program_id.to_string(),
+            amount: balance.amount.to_string(),
+            decimals: balance.decimals,
+            ui_amount: balance.ui_amount(),
+            ui_amount_string: balance.ui_amount_string(),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct FleetTokenHolding {
+    #[serde(flatten)]
+    balance: WalletTokenBalance,
+    /// Wallets holding a non-zero amount.
+    wallets: usize,
 }
 
 #[derive(Serialize)]
 pub struct ListWalletResponseWallet {
     pubkey: String,
+    /// Position in the fleet, wallets are derived from the seed at this index.
+    index: Option<u64>,
+    tags: Vec<String>,
     sol_lamports: String,
+    /// Held as wrapped SOL in token accounts.
+    wsol_lamports: String,
+    /// Native plus wrapped SOL.
+    effective_sol_lamports: String,
+    owner: String,
+    executable: bool,
+    data_length: String,
+    rent_epoch: String,
+    /// `[data, encoding]`, only present when the data was asked for.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<(String, AccountEncoding)>,
+    /// False once a program took the wallet over or it holds data, such wallets are
+    /// not safe to fund or collect from.
+    plain_system_account: bool,
+    /// Token and Token-2022 balances per mint.
+    tokens: Vec<WalletTokenBalance>,
 }
 
+/// Wallets whose token accounts are looked up at the same time.
+const TOKEN_ACCOUNT_LOOKUPS: usize = 16;
+
 pub async fn list_wallets(
     State(state): State<AppState>,
     Query(params): Query<ListWalletsRequest>,
@@ -94,6 +185,13 @@ pub async fn list_wallets(
     let config_arc = Arc::clone(&state.config);
     let config = config_arc.read().await;
 
+    let mint_filter = match params.mint.as_deref().map(Pubkey::from_str) {
+        Some(Err(_)) => return bad_request("The mint must be a valid public key"),
+        Some(Ok(m)) => Some(m),
+        None => None,
+    };
+    let commitment = config.commitment.with_read(params.commitment).read;
+
     let wallets = get_all_wallets(&*db, &*config, params.page, params.page_size).await;
     let wallets = match wallets {
         Err(err) => {
@@ -103,41 +201,484 @@ pub async fn list_wallets(
         Ok(w) => w,
     };
 
-    let pubkeys = wallets.iter().map(|w| w.pubkey().to_string()).collect();
+    let pubkeys: Vec<String> = wallets.iter().map(|w| w.pubkey().to_string()).collect();
+
+    // Base58 is refused for data over 128 bytes, so the data is always fetched as base64
+    // and encoded as asked for afterwards.
+    let data_encoding = params.encoding.map(|_| AccountEncoding::Base64);
+    let mut accounts = Vec::new();
+    for chunk in pubkeys.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+        let accounts_result = get_multiple_accounts(
+            &state.rpc,
+            "list_wallets",
+            &chunk.to_vec(),
+            data_encoding,
+            commitment,
+        )
+        .await;
 
-    let accounts_result = get_multiple_accounts(&state.rpc_url, "list_wallets", &pubkeys).await;
+        match accounts_result {
+            Ok(acc) => accounts.extend(acc),
+            Err(err) => {
+                eprintln!("Error fetching multiple accounts {}", err);
+                return (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    Json(ErrorResponse {
+                        message: String::from("Interal error while feching wallet balances."),
+                    }),
+                )
+                    .into_response();
+            }
+        }
+    }
 
-    let accounts = match accounts_result {
-        Ok(acc) => acc,
+    // A page holds up to 999 wallets, looked up a few at a time so the node doesn't rate limit.
+    let lookups = Arc::new(Semaphore::new(TOKEN_ACCOUNT_LOOKUPS));
+    let mut token_set = JoinSet::new();
+    for wallet in wallets.iter() {
+        let rpc = Arc::clone(&state.rpc);
+        let lookups = Arc::clone(&lookups);
+        let owner = wallet.pubkey();
+        token_set.spawn(async move {
+            let _permit = lookups.acquire_owned().await;
+            let token_accounts = get_token_accounts(&rpc, &owner, commitment).await;
+            (owner.to_string(), token_accounts)
+        });
+    }
+
+    let mut token_accounts: HashMap<String, Vec<TokenAccount>> = HashMap::new();
+    let mut wsol: HashMap<String, u64> = HashMap::new();
+    while let Some(res) = token_set.join_next().await {
+        match res {
+            Ok((owner, Ok(accounts))) => {
+                let wrapped = accounts.iter().filter(|a| a.native).map(|a| a.amount).sum();
+                wsol.insert(owner.clone(), wrapped);
+                let accounts = accounts
+                    .into_iter()
+                    .filter(|a| mint_filter.is_none_or(|m| a.mint == m))
+                    .collect();
+                token_accounts.insert(owner, accounts);
+            }
+            Ok((owner, Err(err))) => {
+                eprintln!("Error fetching token accounts of {} {}", owner, err);
+                return server_error("Interal error while feching token balances.");
+            }
+            Err(err) => eprintln!("Join error: {:?}", err),
+        }
+    }
+
+    let mut mints: Vec<Pubkey> = token_accounts.values().flatten().map(|a| a.mint).collect();
+    mints.sort();
+    mints.dedup();
+    let decimals = match get_mints(&state.rpc, &mints, commitment).await {
+        Ok(infos) => infos
+            .into_iter()
+            .map(|(mint, info)| (mint, info.decimals))
+            .collect(),
+        Err(err) => {
+            eprintln!("Error fetching mints {}", err);
+            return server_error("Interal error while feching token balances.");
+        }
+    };
+
+    let mut labels = match get_wallet_labels(&db).await {
+        Ok(l) => l,
         Err(err) => {
-            eprintln!("Error fetching multiple accounts {}", err);
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                Json(ErrorResponse {
-                    message: String::from("Interal error while feching wallet balances."),
-                }),
-            )
-                .into_response();
+            eprintln!("Error getting wallet tags: {}", err);
+            return server_error("Internal error.");
         }
     };
 
     let mut wallets: Vec<ListWalletResponseWallet> = Vec::new();
+    let mut holders: HashMap<Pubkey, usize> = HashMap::new();
 
     for (i, acc) in accounts.iter().enumerate() {
         if let Some(pk) = pubkeys.get(i) {
-            let lamports = acc.as_ref().map(|a| a.lamports).unwrap_or(0u64);
+            let balances = token_balances(
+                token_accounts
+                    .get(pk)
+                    .map(Vec::as_slice)
+                    .unwrap_or_default(),
+                &decimals,
+            );
+            for balance in balances.iter().filter(|b| b.amount > 0) {
+                *holders.entry(balance.mint).or_default() += 1;
+            }
+            let tokens = balances.iter().map(WalletTokenBalance::from).collect();
+            let wsol_lamports = wsol.get(pk).copied().unwrap_or(0);
+            let sol_lamports = acc.as_ref().map(|a| a.lamports).unwrap_or(0);
+            let (index, tags) = match labels.remove(pk) {
+                Some(l) => (Some(l.index), l.tags),
+                None => (None, Vec::new()),
+            };
 
-            wallets.push(ListWalletResponseWallet {
-                pubkey: pk.to_owned(),
-                sol_lamports: lamports.to_string(),
-            });
+            let data = match (params.encoding, acc) {
+                (Some(encoding), Some(a)) => match a.decoded_data() {
+                    Ok(bytes) => Some((encoding.encode(&bytes), encoding)),
+                    Err(err) => {
+                        eprintln!("Error decoding the data of {} {}", pk, err);
+                        return server_error("Interal error while feching wallet data.");
+                    }
+                },
+                (Some(encoding), None) => Some((String::new(), encoding)),
+                (None, _) => None,
+            };
+
+            // Accounts that don't exist yet are unused wallets.
+            let wallet = match acc {
+                Some(a) => ListWalletResponseWallet {
+                    pubkey: pk.to_owned(),
+                    index,
+                    tags,
+                    sol_lamports: sol_lamports.to_string(),
+                    wsol_lamports: wsol_lamports.to_string(),
+                    effective_sol_lamports: (sol_lamports as u128 + wsol_lamports as u128)
+                        .to_string(),
+                    owner: a.owner.clone(),
+                    executable: a.executable,
+                    data_length: a.space.to_string(),
+                    rent_epoch: a.rent_epoch.to_string(),
+                    data,
+                    plain_system_account: a.is_plain_system_account(),
+                    tokens,
+                },
+                None => ListWalletResponseWallet {
+                    pubkey: pk.to_owned(),
+                    index,
+                    tags,
+                    sol_lamports: String::from("0"),
+                    wsol_lamports: wsol_lamports.to_string(),
+                    effective_sol_lamports: wsol_lamports.to_string(),
+                    owner: system_program::ID.to_string(),
+                    executable: false,
+                    data_length: String::from("0"),
+                    rent_epoch: String::from("0"),
+                    data,
+                    plain_system_account: true,
+                    tokens,
+                },
+            };
+
+            wallets.push(wallet);
         }
     }
 
+    let all_token_accounts: Vec<TokenAccount> = token_accounts.into_values().flatten().collect();
+    let fleet_tokens = token_balances(&all_token_accounts, &decimals)
+        .iter()
+        .map(|b| FleetTokenHolding {
+            balance: WalletTokenBalance::from(b),
+            wallets: holders.get(&b.mint).copied().unwrap_or(0),
+        })
+        .collect();
+
     let res = ListWalletsResponse {
         message: String::from("Retrieved wallets."),
         wallets: wallets,
+        fleet_tokens,
     };
 
     return (StatusCode::OK, Json(res)).into_response();
 }
+
+#[derive(Deserialize)]
+pub struct AirdropRequest {
+    pubkey: String,
+    lamports: String,
+}
+
+#[derive(Serialize)]
+pub struct AirdropResponse {
+    message: String,
+    signature: String,
+}
+
+/// Only served by the mock node and test clusters, mainnet rejects airdrops.
+pub async fn airdrop(
+    State(state): State<AppState>,
+    Json(payload): Json<AirdropRequest>,
+) -> Response {
+    let lamports = match payload.lamports.parse::<u64>() {
+        Ok(l) => l,
+        Err(_) => {
+            return bad_request("The lamports are in invalid format");
+        }
+    };
+
+    let commitment = state.config.read().await.commitment.read;
+    let signature_result =
+        request_airdrop(&state.rpc, "airdrop", &payload.pubkey, lamports, commitment).await;
+
+    let signature = match signature_result {
+        Ok(s) => s,
+        Err(err) => {
+            eprintln!("Error requesting airdrop {}", err);
+            return server_error("Airdrop failed.");
+        }
+    };
+
+    let res = AirdropResponse {
+        message: format!("Airdropped {} lamports", lamports),
+        signature,
+    };
+
+    (StatusCode::OK, Json(res)).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct ReclaimRentRequest {
+    /// Defaults to the whole fleet.
+    wallets: Option<Vec<String>>,
+    /// Accounts holding at most this many base units have them burned and are closed too.
+    dust_amount: Option<String>,
+    /// `treasury` or a fleet wallet outside `wallets`, it co-signs and pays every fee.
+    fee_payer: Option<String>,
+    /// Simulate the closes instead of sending them.
+    #[serde(default)]
+    dry_run: bool,
+    commitment: Option<Commitment>,
+}
+
+#[derive(Serialize)]
+pub struct ReclaimedTokenAccount {
+    pubkey: String,
+    mint: String,
+    burned: String,
+    lamports: String,
+}
+
+#[derive(Serialize)]
+pub struct ReclaimRentTransaction {
+    pubkey: String,
+    accounts: Vec<ReclaimedTokenAccount>,
+    lamports: String,
+    signature: Option<String>,
+    simulation: Option<SimulatedTransaction>,
+    error: Option<String>,
+}
+
+impl ReclaimRentTransaction {
+    fn new<T>(pubkey: &str, batch: &ReclaimBatch<T>) -> Self {
+        Self {
+            pubkey: pubkey.to_owned(),
+            accounts: batch
+                .accounts
+                .iter()
+                .map(|a| ReclaimedTokenAccount {
+                    pubkey: a.pubkey.to_string(),
+                    mint: a.mint.to_string(),
+                    burned: a.burn.to_string(),
+                    lamports: a.lamports.to_string(),
+                })
+                .collect(),
+            lamports: batch.lamports().to_string(),
+            signature: None,
+            simulation: None,
+            error: batch.result.as_ref().err().map(|e| e.to_string()),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct ReclaimRentResponse {
+    message: String,
+    /// Rent returned by the transactions that went through, or would in a dry run.
+    reclaimed_lamports: String,
+    transactions: Vec<ReclaimRentTransaction>,
+    /// Wallets whose token accounts couldn't be listed, or that aren't fleet wallets.
+    errors: Vec<ReclaimRentError>,
+}
+
+#[derive(Serialize)]
+pub struct ReclaimRentError {
+    pubkey: String,
+    error: String,
+}
+
+pub async fn reclaim_rent_job(
+    State(state): State<AppState>,
+    Json(payload): Json<ReclaimRentRequest>,
+) -> Response {
+    let dust_amount = match payload.dust_amount.as_deref().map(str::parse::<u64>) {
+        Some(Err(_)) => return bad_request("The dust amount is in invalid format"),
+        Some(Ok(d)) => Some(d),
+        None => None,
+    };
+    if let Some(wallets) = &payload.wallets
+        && wallets.iter().any(|p| Pubkey::from_str(p).is_err())
+    {
+        return bad_request("Wallets must be valid public keys");
+    }
+
+    let config_arc = Arc::clone(&state.config);
+    let config = config_arc.read().await;
+    let db_arc = Arc::clone(&state.services.database);
+    let db = db_arc.read().await;
+
+    let wallets = match &payload.wallets {
+        Some(pubkeys) => get_wallets_by_pubkey(&db, &config, pubkeys).await,
+        None => get_all_wallets(&db, &config, 0, 0).await,
+    };
+    let wallets = match wallets {
+        Err(err) => {
+            eprintln!("Error getting wallets to reclaim rent from: {}", err);
+            return server_error("Internal error.");
+        }
+        Ok(w) => w,
+    };
+
+    let mut errors: Vec<ReclaimRentError> = payload
+        .wallets
+        .iter()
+        .flatten()
+        .filter(|p| !wallets.iter().any(|w| &w.pubkey().to_string() == *p))
+        .map(|p| ReclaimRentError {
+            pubkey: p.to_owned(),
+            error: String::from("Not a fleet wallet."),
+        })
+        .collect();
+
+    let fee_payer = match &payload.fee_payer {
+        None => None,
+        Some(fee_payer) => match fee_payer_wallet(&db, &config, fee_payer, &wallets).await {
+            Ok(w) => Some(Arc::new(w)),
+            Err(res) => return res,
+        },
+    };
+
+    let commitment = config.commitment.with_confirmation(payload.commitment);
+    let mut reclaim_set = JoinSet::new();
+    for wallet in wallets {
+        let rpc = Arc::clone(&state.rpc);
+        let websocket = state.services.websocket.clone();
+        let blockhashes = Arc::clone(&state.services.blockhashes);
+        let fee_payer = fee_payer.clone();
+        let dry_run = payload.dry_run;
+
+        reclaim_set.spawn(async move {
+            let pubkey = wallet.pubkey().to_string();
+            let transactions = if dry_run {
+                dry_run_reclaim_rent(
+                    &rpc,
+                    blockhashes,
+                    &wallet,
+                    fee_payer.as_deref(),
+                    dust_amount,
+                    commitment,
+                )
+                .await
+                .map(|batches| {
+                    let transactions = batches
+                        .iter()
+                        .map(|batch| ReclaimRentTransaction {
+                            simulation: batch.result.as_ref().ok().map(SimulatedTransaction::from),
+                            ..ReclaimRentTransaction::new(&pubkey, batch)
+                        })
+                        .collect::<Vec<_>>();
+                    let lamports: u64 = batches
+                        .iter()
+                        .filter(|b| b.result.as_ref().is_ok_and(|r| r.err.is_none()))
+                        .map(|b| b.lamports())
+                        .sum();
+                    (transactions, lamports)
+                })
+            } else {
+                reclaim_rent(
+                    &rpc,
+                    websocket,
+                    blockhashes,
+                    &wallet,
+                    fee_payer.as_deref(),
+                    dust_amount,
+                    commitment,
+                )
+                .await
+                .map(|batches| {
+                    let transactions = batches
+                        .iter()
+                        .map(|batch| ReclaimRentTransaction {
+                            signature: batch.result.as_ref().ok().cloned(),
+                            ..ReclaimRentTransaction::new(&pubkey, batch)
+                        })
+                        .collect::<Vec<_>>();
+                    let lamports: u64 = batches
+                        .iter()
+                        .filter(|b| b.result.is_ok())
+                        .map(|b| b.lamports())
+                        .sum();
+                    (transactions, lamports)
+                })
+            };
+            (pubkey, transactions)
+        });
+    }
+
+    let mut transactions = Vec::new();
+    let mut reclaimed_lamports = 0u64;
+    while let Some(res) = reclaim_set.join_next().await {
+        match res {
+            Ok((_, Ok((wallet_transactions, lamports)))) => {
+                transactions.extend(wallet_transactions);
+                reclaimed_lamports += lamports;
+            }
+            Ok((pubkey, Err(err))) => {
+                eprintln!("Reclaiming rent of {} failed: {}", pubkey, err);
+                errors.push(ReclaimRentError {
+                    pubkey,
+                    error: err.to_string(),
+                });
+            }
+            Err(err) => eprintln!("Join error: {:?}", err),
+        }
+    }
+
+    let res = ReclaimRentResponse {
+        message: if payload.dry_run {
+            String::from("Simulated the closes, nothing was sent.")
+        } else {
+            String::from("Reclaimed rent.")
+        },
+        reclaimed_lamports: reclaimed_lamports.to_string(),
+        transactions,
+        errors,
+    };
+
+    (StatusCode::OK, Json(res)).into_response()
+}
+
+#[derive(Deserialize)]
+pub struct TagWalletsRequest {
+    wallets: Vec<String>,
+    tag: String,
+}
+
+#[derive(Serialize)]
+pub struct TagWalletsResponse {
+    message: String,
+    /// Wallets that didn't have the tag yet.
+    tagged: usize,
+}
+
+pub async fn tag_wallets(
+    State(state): State<AppState>,
+    Json(payload): Json<TagWalletsRequest>,
+) -> Response {
+    if payload.tag.trim().is_empty() {
+        return bad_request("The tag is empty");
+    }
+
+    let db = state.services.database.read().await;
+    match store_wallet_tags(&db, payload.wallets, payload.tag).await {
+        Err(err) => {
+            eprintln!("Error tagging wallets: {}", err);
+            server_error("Internal error.")
+        }
+        Ok(tagged) => {
+            let res = TagWalletsResponse {
+                message: format!("Tagged {} wallets", tagged),
+                tagged,
+            };
+            (StatusCode::OK, Json(res)).into_response()
+        }
+    }
+}
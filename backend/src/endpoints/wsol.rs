@@ -0,0 +1,246 @@
+use std::{str::FromStr, sync::Arc};
+
+use axum::{
+    Json,
+    extract::State,
+    response::{IntoResponse, Response},
+};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use tokio::task::JoinSet;
+
+use crate::{
+    AppState,
+    endpoints::{
+        misc::fee_payer_wallet,
+        responses::{bad_request, server_error},
+    },
+    storage::mnemonic_wallet_storage::get_wallets_by_pubkey,
+    tokens::wsol::{unwrap_sol as unwrap_wallet_sol, wrap_sol as wrap_wallet_sol},
+};
+
+#[derive(Deserialize)]
+pub struct WrapSolRequest {
+    wallets: Vec<String>,
+    /// Wrapped per wallet.
+    lamports: String,
+    /// `treasury` or a fleet wallet outside `wallets`, it co-signs and pays the fees and rent.
+    fee_payer: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct UnwrapSolRequest {
+    wallets: Vec<String>,
+    /// `treasury` or a fleet wallet outside `wallets`, it co-signs and pays the fees.
+    fee_payer: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct WsolResponse {
+    message: String,
+    results: Vec<WsolWalletResult>,
+}
+
+#[derive(Serialize)]
+pub struct WsolWalletResult {
+    pubkey: String,
+    signature: Option<String>,
+    /// Wrapped, or unwrapped back to native SOL.
+    lamports: Option<String>,
+    /// Rent of the closed wSOL accounts, only set when unwrapping.
+    rent_lamports: Option<String>,
+    error: Option<String>,
+}
+
+impl WsolWalletResult {
+    fn new(pubkey: String) -> Self {
+        Self {
+            pubkey,
+            signature: None,
+            lamports: None,
+            rent_lamports: None,
+            error: None,
+        }
+    }
+}
+
+/// Loads the requested fleet wallets, requested pubkeys that aren't fleet wallets get a failed result.
+async fn fleet_wallets(
+    state: &AppState,
+    pubkeys: &Vec<String>,
+) -> Result<(Vec<Keypair>, Vec<WsolWalletResult>), Response> {
+    if pubkeys.iter().any(|p| Pubkey::from_str(p).is_err()) {
+        return Err(bad_request("Wallets must be valid public keys"));
+    }
+
+    let config = state.config.read().await;
+    let db = state.services.database.read().await;
+    let wallets = match get_wallets_by_pubkey(&db, &config, pubkeys).await {
+        Err(err) => {
+            eprintln!("Error getting wallets: {}", err);
+            return Err(server_error("Internal error."));
+        }
+        Ok(w) => w,
+    };
+
+    let missing = pubkeys
+        .iter()
+        .filter(|p| !wallets.iter().any(|w| &w.pubkey().to_string() == *p))
+        .map(|p| WsolWalletResult {
+            error: Some(String::from("Not a fleet wallet.")),
+            ..WsolWalletResult::new(p.to_owned())
+        })
+        .collect();
+
+    Ok((wallets, missing))
+}
+
+/// The requested fee payer of the wallets, if any.
+async fn sponsor(
+    state: &AppState,
+    fee_payer: &Option<String>,
+    wallets: &[Keypair],
+) -> Result<Option<Arc<Keypair>>, Response> {
+    let Some(fee_payer) = fee_payer else {
+        return Ok(None);
+    };
+    let config = state.config.read().await;
+    let db = state.services.database.read().await;
+
+    fee_payer_wallet(&db, &config, fee_payer, wallets)
+        .await
+        .map(|w| Some(Arc::new(w)))
+}
+
+pub async fn wrap_sol(
+    State(state): State<AppState>,
+    Json(payload): Json<WrapSolRequest>,
+) -> Response {
+    let lamports = match payload.lamports.parse::<u64>() {
+        Ok(l) if l > 0 => l,
+        _ => return bad_request("The lamports are in invalid format"),
+    };
+    let (wallets, mut results) = match fleet_wallets(&state, &payload.wallets).await {
+        Ok(w) => w,
+        Err(res) => return res,
+    };
+    let fee_payer = match sponsor(&state, &payload.fee_payer, &wallets).await {
+        Ok(f) => f,
+        Err(res) => return res,
+    };
+    let commitment = state.config.read().await.commitment;
+
+    let mut wrap_set = JoinSet::new();
+    for wallet in wallets {
+        let rpc = Arc::clone(&state.rpc);
+        let websocket = state.services.websocket.clone();
+        let blockhashes = Arc::clone(&state.services.blockhashes);
+        let fee_payer = fee_payer.clone();
+
+        wrap_set.spawn(async move {
+            let result = wrap_wallet_sol(
+                &rpc,
+                websocket,
+                blockhashes,
+                &wallet,
+                fee_payer.as_deref(),
+                lamports,
+                commitment,
+            )
+            .await;
+            (wallet.pubkey().to_string(), result)
+        });
+    }
+
+    while let Some(res) = wrap_set.join_next().await {
+        match res {
+            Ok((pubkey, Ok(signature))) => results.push(WsolWalletResult {
+                signature: Some(signature),
+                lamports: Some(lamports.to_string()),
+                ..WsolWalletResult::new(pubkey)
+            }),
+            Ok((pubkey, Err(err))) => {
+                eprintln!("Wrapping SOL of {} failed: {}", pubkey, err);
+                results.push(WsolWalletResult {
+                    error: Some(err.to_string()),
+                    ..WsolWalletResult::new(pubkey)
+                });
+            }
+            Err(err) => eprintln!("Join error: {:?}", err),
+        }
+    }
+
+    let res = WsolResponse {
+        message: String::from("Wrapped SOL."),
+        results,
+    };
+
+    (StatusCode::OK, Json(res)).into_response()
+}
+
+pub async fn unwrap_sol(
+    State(state): State<AppState>,
+    Json(payload): Json<UnwrapSolRequest>,
+) -> Response {
+    let (wallets, mut results) = match fleet_wallets(&state, &payload.wallets).await {
+        Ok(w) => w,
+        Err(res) => return res,
+    };
+    let fee_payer = match sponsor(&state, &payload.fee_payer, &wallets).await {
+        Ok(f) => f,
+        Err(res) => return res,
+    };
+    let commitment = state.config.read().await.commitment;
+
+    let mut unwrap_set = JoinSet::new();
+    for wallet in wallets {
+        let rpc = Arc::clone(&state.rpc);
+        let websocket = state.services.websocket.clone();
+        let blockhashes = Arc::clone(&state.services.blockhashes);
+        let fee_payer = fee_payer.clone();
+
+        unwrap_set.spawn(async move {
+            let result = unwrap_wallet_sol(
+                &rpc,
+                websocket,
+                blockhashes,
+                &wallet,
+                fee_payer.as_deref(),
+                commitment,
+            )
+            .await;
+            (wallet.pubkey().to_string(), result)
+        });
+    }
+
+    while let Some(res) = unwrap_set.join_next().await {
+        match res {
+            Ok((pubkey, Ok(Some(unwrapped)))) => results.push(WsolWalletResult {
+                signature: Some(unwrapped.signature),
+                lamports: Some(unwrapped.lamports.to_string()),
+                rent_lamports: Some(unwrapped.rent_lamports.to_string()),
+                ..WsolWalletResult::new(pubkey)
+            }),
+            Ok((pubkey, Ok(None))) => results.push(WsolWalletResult {
+                lamports: Some(String::from("0")),
+                ..WsolWalletResult::new(pubkey)
+            }),
+            Ok((pubkey, Err(err))) => {
+                eprintln!("Unwrapping SOL of {} failed: {}", pubkey, err);
+                results.push(WsolWalletResult {
+                    error: Some(err.to_string()),
+                    ..WsolWalletResult::new(pubkey)
+                });
+            }
+            Err(err) => eprintln!("Join error: {:?}", err),
+        }
+    }
+
+    let res = WsolResponse {
+        message: String::from("Unwrapped SOL."),
+        results,
+    };
+
+    (StatusCode::OK, Json(res)).into_response()
+}
@@ -1,5 +1,7 @@
 use base64::DecodeError;
-use solana_sdk::{hash::ParseHashError, signer::SignerError};
+use solana_sdk::{
+    hash::ParseHashError, message::CompileError, pubkey::ParsePubkeyError, signer::SignerError,
+};
 use thiserror::Error;
 use tokio::task::JoinError;
 
@@ -14,30 +16,69 @@ pub enum Error {
     #[error("{0}")]
     InsufficientSol(String),
 
+    #[error("{0}")]
+    InsufficientTokens(String),
+
+    #[error("{0}")]
+    RentExemption(String),
+
+    #[error("{0}")]
+    CompromisedAccount(String),
+
     #[error("{0}")]
     Database(#[from] tokio_rusqlite::Error),
 
+    #[error("{0}")]
+    Sqlite(#[from] rusqlite::Error),
+
     #[error("{0}")]
     RpcError(String),
 
+    #[error("{0}")]
+    TransactionFailed(String),
+
+    #[error("{0}")]
+    ConfirmationTimeout(String),
+
+    #[error("{0}")]
+    InvalidMint(String),
+
+    #[error("{0}")]
+    Swap(String),
+
+    #[error("{0}")]
+    SwapGuard(String),
+
     #[error("{0}")]
     Transport(#[from] reqwest::Error),
 
+    #[error("{0}")]
+    Websocket(#[from] Box<tungstenite::Error>),
+
     #[error("{0}")]
     Parse(#[from] serde_json::Error),
 
     #[error("{0}")]
     HashParse(#[from] ParseHashError),
 
+    #[error("{0}")]
+    PubkeyParse(#[from] ParsePubkeyError),
+
     #[error("{0}")]
     Bincode(#[from] bincode::Error),
 
     #[error("{0}")]
     Decoding(#[from] DecodeError),
 
+    #[error("{0}")]
+    Base58Decoding(#[from] bs58::decode::Error),
+
     #[error("{0}")]
     JoinError(#[from] JoinError),
 
     #[error("{0}")]
     SignerError(#[from] SignerError),
+
+    #[error("{0}")]
+    MessageCompile(#[from] CompileError),
 }
@@ -0,0 +1,80 @@
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    funding::funding::{FundingTarget, FundingTransfer, TransferStatus},
+    txn_factory::{transfer::batch_sol_transfers, util::LAMPORTS_PER_SIGNATURE},
+};
+
+fn transfers_from(
+    payer: &Pubkey,
+    transfers: &[(Pubkey, u64)],
+    funded_by: Option<usize>,
+) -> Vec<FundingTransfer> {
+    batch_sol_transfers(payer, None, transfers)
+        .into_iter()
+        .map(|batch| FundingTransfer {
+            payer: *payer,
+            transfers: batch,
+            funded_by,
+            status: TransferStatus::Pending,
+            sent: None,
+        })
+        .collect()
+}
+
+/// Plans the transactions sending every target its lamports. Without relays the
+/// distribution wallet pays everyone. With relays, which must be among the targets,
+/// the other targets are split into one contiguous range per relay. The distribution
+/// wallet sends each relay its own lamports plus its range and the fees of paying it,
+/// then the relays pay their ranges in parallel.
+pub fn plan_funding_transfers(
+    distro_wallet: &Pubkey,
+    targets: &[FundingTarget],
+    relays: &[Pubkey],
+) -> Vec<FundingTransfer> {
+    if relays.is_empty() {
+        let transfers: Vec<(Pubkey, u64)> =
+            targets.iter().map(|t| (t.pubkey, t.lamports)).collect();
+        return transfers_from(distro_wallet, &transfers, None);
+    }
+
+    let rest: Vec<(Pubkey, u64)> = targets
+        .iter()
+        .filter(|t| !relays.contains(&t.pubkey))
+        .map(|t| (t.pubkey, t.lamports))
+        .collect();
+    let range_len = rest.len().div_ceil(relays.len()).max(1);
+    let mut ranges: Vec<&[(Pubkey, u64)]> = rest.chunks(range_len).collect();
+    ranges.resize(relays.len(), &[]);
+
+    let mut relay_transfers = Vec::new();
+    let mut relay_amounts = Vec::new();
+    for (relay, range) in relays.iter().zip(ranges.iter()) {
+        let own = targets
+            .iter()
+            .find(|t| t.pubkey == *relay)
+            .map(|t| t.lamports)
+            .unwrap_or(0);
+        let transfers = transfers_from(relay, range, None);
+        let passed_on: u64 = range.iter().map(|(_, lamports)| lamports).sum();
+        let fees = LAMPORTS_PER_SIGNATURE * transfers.len() as u64;
+
+        relay_amounts.push((*relay, own + passed_on + fees));
+        relay_transfers.push(transfers);
+    }
+
+    let mut plan = transfers_from(distro_wallet, &relay_amounts, None);
+    let funding_relays = plan.clone();
+    for (relay, transfers) in relays.iter().zip(relay_transfers) {
+        let funded_by = funding_relays
+            .iter()
+            .position(|t| t.transfers.iter().any(|(to, _)| to == relay));
+        plan.extend(
+            transfers
+                .into_iter()
+                .map(|t| FundingTransfer { funded_by, ..t }),
+        );
+    }
+
+    plan
+}
@@ -1,27 +1,143 @@
 use async_trait::async_trait;
 use solana_sdk::{pubkey::Pubkey, signature::Keypair};
 
-use crate::{Websocket, errors::errors::Error};
+use crate::{
+    Blockhashes, Rpc, Websocket,
+    config::CommitmentConfig,
+    errors::errors::Error,
+    tokens::mints::MintInfo,
+    txn_factory::{simulation::SimulationReport, token_transfer::TokenRecipient},
+};
+
+/// A wallet and the lamports it is sent.
+#[derive(Debug, Clone, Copy)]
+pub struct FundingTarget {
+    pub pubkey: Pubkey,
+    pub lamports: u64,
+}
+
+/// Where a transaction of a funding job stands. Anything not confirmed is sent
+/// again when the job is completed again, unless its last signature may still land.
+#[derive(Debug, Clone)]
+pub enum TransferStatus {
+    Pending,
+    Confirmed(String),
+    Failed(String),
+}
+
+/// The last signed version of a transaction, looked up before it is sent again.
+#[derive(Debug, Clone)]
+pub struct SentTransfer {
+    pub signature: String,
+    pub last_valid_block_height: u64,
+}
+
+/// One transaction of a SOL funding job.
+#[derive(Debug, Clone)]
+pub struct FundingTransfer {
+    /// Signs and pays, the distribution wallet or one of the relays.
+    pub payer: Pubkey,
+    pub transfers: Vec<(Pubkey, u64)>,
+    /// The transaction funding `payer`, it has to be confirmed first.
+    pub funded_by: Option<usize>,
+    pub status: TransferStatus,
+    pub sent: Option<SentTransfer>,
+}
+
+/// How far a funding job got.
+pub struct FundingProgress {
+    pub confirmed: usize,
+    pub pending: usize,
+    /// Payer and error of every failed transaction.
+    pub failed: Vec<(Pubkey, String)>,
+}
+
+impl FundingProgress {
+    /// Adds a transaction paid by `payer`.
+    pub fn count(&mut self, payer: &Pubkey, status: &TransferStatus) {
+        match status {
+            TransferStatus::Confirmed(_) => self.confirmed += 1,
+            TransferStatus::Pending => self.pending += 1,
+            TransferStatus::Failed(err) => self.failed.push((*payer, err.clone())),
+        }
+    }
+}
 
 #[derive(Debug)]
 pub struct FundingJob {
     pub distro_wallet: Keypair,
-    pub target_pubkeys: Vec<Pubkey>,
-    pub lamports_per_wallet: u64,
+    /// For token jobs the lamports are zero, the token amount is in `token`.
+    pub targets: Vec<FundingTarget>,
+    /// Fleet wallets passing SOL on to the rest in tree mode, empty otherwise.
+    pub relays: Vec<Keypair>,
+    /// The transactions of a SOL job, empty for token jobs.
+    pub transfers: Vec<FundingTransfer>,
+    /// SOL the distribution wallet needs, for token jobs only fees and rent.
     pub total_funding_lamports: u128,
+    /// Set for jobs distributing a token instead of SOL.
+    pub token: Option<TokenFundingJob>,
+}
+
+#[derive(Debug)]
+pub struct TokenFundingJob {
+    pub mint: MintInfo,
+    /// In the mint's base units.
+    pub amount_per_wallet: u64,
+    /// Tokens to deposit into `source_token_account`.
+    pub total_funding_amount: u128,
+    /// The distribution wallet's associated token account the tokens are sent from.
+    pub source_token_account: Pubkey,
+    /// Recipients grouped by the transaction they are paid in.
+    pub batches: Vec<TokenFundingBatch>,
+}
+
+/// One transaction of a token funding job.
+#[derive(Debug, Clone)]
+pub struct TokenFundingBatch {
+    pub recipients: Vec<TokenRecipient>,
+    pub status: TransferStatus,
+    pub sent: Option<SentTransfer>,
 }
 
 #[async_trait]
 pub trait Funding: Send + Sync {
+    /// Starts a job sending every target its own amount of SOL from `distro_wallet`,
+    /// a fresh wallet or the treasury. With relays, which must be targets too, the
+    /// distribution wallet only funds them and each relay funds its share of the
+    /// other targets.
     async fn initiate_funding_job(
         &mut self,
-        rpc_url: String,
+        rpc: Rpc,
+        distro_wallet: Keypair,
+        targets: Vec<FundingTarget>,
+        relays: Vec<Keypair>,
+        commitment: CommitmentConfig,
+    ) -> Result<&FundingJob, Error>;
+    /// Starts a job distributing `amount_per_wallet` of `mint` to every target.
+    async fn initiate_token_funding_job(
+        &mut self,
+        rpc: Rpc,
+        distro_wallet: Keypair,
         target_pubkeys: Vec<Pubkey>,
-        lamports_per_wallet: u64,
+        mint: Pubkey,
+        amount_per_wallet: u64,
+        commitment: CommitmentConfig,
     ) -> Result<&FundingJob, Error>;
+    /// Sends whatever of the active job isn't confirmed yet, so a partly failed job
+    /// resumes where it stopped.
     async fn complete_funding_job(
-        &self,
-        rpc_url: String,
+        &mut self,
+        rpc: Rpc,
         websocket: Websocket,
-    ) -> Result<(), Error>;
+        blockhashes: Blockhashes,
+        commitment: CommitmentConfig,
+    ) -> Result<FundingProgress, Error>;
+    /// Simulates the transfers of the active job that could be sent right now,
+    /// without sending anything.
+    async fn dry_run_funding_job(
+        &self,
+        rpc: Rpc,
+        blockhashes: Blockhashes,
+        commitment: CommitmentConfig,
+    ) -> Result<Vec<SimulationReport>, Error>;
 }
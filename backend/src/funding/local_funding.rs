@@ -6,13 +6,26 @@ use solana_sdk::signature::Keypair;
 use solana_sdk::signer::Signer;
 use tokio::task::JoinSet;
 
-use crate::Websocket;
+use crate::config::CommitmentConfig;
 use crate::errors::errors::Error;
-use crate::funding::funding::{Funding, FundingJob};
-use crate::rpc::read::{get_balance, get_minimum_balance_for_rent_exemption};
-use crate::rpc::send::send_transaction;
-use crate::txn_factory::blockhash::get_blockhash;
-use crate::txn_factory::transfer::build_sol_transfer;
+use crate::funding::fan_out::plan_funding_transfers;
+use crate::funding::funding::{
+    Funding, FundingJob, FundingProgress, FundingTarget, SentTransfer, TransferStatus,
+};
+use crate::funding::resume::settle_sent_transfers;
+use crate::funding::token_funding::{
+    complete_token_funding, dry_run_token_funding, plan_token_funding,
+};
+use crate::rpc::read::{
+    MAX_ACCOUNTS_PER_REQUEST, get_balance, get_minimum_balance_for_rent_exemption,
+    get_multiple_accounts,
+};
+use crate::rpc::send::send_and_confirm;
+use crate::txn_factory::rent::{PlannedTransfers, check_rent_exemption};
+use crate::txn_factory::simulation::{SimulationReport, simulate};
+use crate::txn_factory::transfer::build_sol_transfers;
+use crate::txn_factory::util::{LAMPORTS_PER_SIGNATURE, SimpleTransaction};
+use crate::{Blockhashes, Rpc, Websocket};
 
 pub struct LocalFunding {
     active_job: Option<FundingJob>,
@@ -24,40 +37,193 @@ impl LocalFunding {
     }
 }
 
+/// The keypair signing for `payer`, the distribution wallet or a relay.
+fn payer_wallet<'a>(job: &'a FundingJob, payer: &Pubkey) -> &'a Keypair {
+    std::iter::once(&job.distro_wallet)
+        .chain(job.relays.iter())
+        .find(|w| w.pubkey() == *payer)
+        .expect("every payer of a funding job is its distribution wallet or a relay")
+}
+
+/// Transactions not confirmed yet whose payer is already funded.
+fn ready_transfers(job: &FundingJob) -> Vec<usize> {
+    let confirmed = |i: usize| matches!(job.transfers[i].status, TransferStatus::Confirmed(_));
+    (0..job.transfers.len())
+        .filter(|i| !confirmed(*i))
+        .filter(|i| job.transfers[*i].funded_by.is_none_or(confirmed))
+        .collect()
+}
+
+/// Signs transaction `i` of the job on the latest blockhash, with the height it expires at.
+async fn sign_transfer(
+    job: &FundingJob,
+    i: usize,
+    blockhashes: &Blockhashes,
+) -> Result<(SimpleTransaction, u64), Error> {
+    let transfer = &job.transfers[i];
+    let payer = payer_wallet(job, &transfer.payer);
+    // Taken when the transfer is signed, late transfers of a big job get a newer hash.
+    let blockhash = blockhashes.get().await?;
+    let txn = build_sol_transfers(payer, None, &transfer.transfers, &blockhash.hash).await?;
+
+    println!(
+        "Built txn {} with {} transfers",
+        &txn.signature[..6],
+        transfer.transfers.len()
+    );
+
+    Ok((txn, blockhash.last_valid_block_height))
+}
+
+/// Fails if a wallet about to be funded, or to relay funds, was taken over by a program.
+/// Wallets that don't exist yet are fine.
+async fn check_plain_system_accounts(
+    rpc: &Rpc,
+    pubkeys: &[Pubkey],
+    commitment: CommitmentConfig,
+) -> Result<(), Error> {
+    let pubkeys: Vec<String> = pubkeys.iter().map(|p| p.to_string()).collect();
+    for chunk in pubkeys.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+        let accounts = get_multiple_accounts(
+            rpc,
+            "initiate_funding",
+            &chunk.to_vec(),
+            None,
+            commitment.read,
+        )
+        .await?;
+
+        for (pubkey, account) in chunk.iter().zip(accounts.iter()) {
+            if let Some(account) = account
+                && !account.is_plain_system_account()
+            {
+                return Err(Error::CompromisedAccount(format!(
+                    "Wallet {} is owned by {} and holds {} bytes of data.",
+                    pubkey, account.owner, account.space
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// The job's transactions not confirmed yet, in the order they are sent.
+fn planned_transfers(job: &FundingJob) -> Vec<PlannedTransfers<'_>> {
+    job.transfers
+        .iter()
+        .filter(|t| !matches!(t.status, TransferStatus::Confirmed(_)))
+        .map(|t| PlannedTransfers {
+            payer: t.payer,
+            fee_payer: None,
+            transfers: &t.transfers,
+        })
+        .collect()
+}
+
+/// Lamports the distribution wallet still has to send, with fees, and the rent it keeps.
+fn remaining_distro_lamports(job: &FundingJob, rent: u128) -> u128 {
+    let remaining: u128 = job
+        .transfers
+        .iter()
+        .filter(|t| t.payer == job.distro_wallet.pubkey())
+        .filter(|t| !matches!(t.status, TransferStatus::Confirmed(_)))
+        .map(|t| {
+            t.transfers.iter().map(|(_, l)| *l as u128).sum::<u128>()
+                + LAMPORTS_PER_SIGNATURE as u128
+        })
+        .sum();
+
+    if remaining == 0 { 0 } else { remaining + rent }
+}
+
 #[async_trait]
 impl Funding for LocalFunding {
     async fn initiate_funding_job(
         &mut self,
-        rpc_url: String,
-        target_pubkeys: Vec<Pubkey>,
-        lamports_per_wallet: u64,
+        rpc: Rpc,
+        distro_wallet: Keypair,
+        targets: Vec<FundingTarget>,
+        relays: Vec<Keypair>,
+        commitment: CommitmentConfig,
     ) -> Result<&FundingJob, Error> {
-        //TODO check that lamports per wallet is at least 0.001 or smth
+        let min_rent_result =
+            get_minimum_balance_for_rent_exemption(&rpc, "initiate_funding", 0, commitment.read)
+                .await?;
 
-        let total_funding_lamports =
-            ((lamports_per_wallet + 5000u64) as u128) * target_pubkeys.len() as u128;
+        let relay_pubkeys: Vec<Pubkey> = relays.iter().map(|r| r.pubkey()).collect();
+        // The relays are among the targets.
+        let target_pubkeys: Vec<Pubkey> = targets.iter().map(|t| t.pubkey).collect();
+        check_plain_system_accounts(&rpc, &target_pubkeys, commitment).await?;
+        let transfers = plan_funding_transfers(&distro_wallet.pubkey(), &targets, &relay_pubkeys);
 
-        let min_rent_result =
-            get_minimum_balance_for_rent_exemption(&rpc_url, "initiate_funding", 0).await?;
+        let job = self.active_job.insert(FundingJob {
+            distro_wallet,
+            targets,
+            relays,
+            transfers,
+            total_funding_lamports: 0,
+            token: None,
+        });
+        job.total_funding_lamports = remaining_distro_lamports(job, min_rent_result);
 
-        let total_lamports_to_provide = total_funding_lamports + min_rent_result;
+        // The funding wallet is checked as if the deposit already arrived.
+        let funded_distro = (
+            job.distro_wallet.pubkey(),
+            job.total_funding_lamports as u64,
+        );
+        check_rent_exemption(&rpc, &planned_transfers(job), &[funded_distro], commitment).await?;
 
-        self.active_job = Some(FundingJob {
-            distro_wallet: Keypair::new(),
-            target_pubkeys: target_pubkeys,
-            lamports_per_wallet: lamports_per_wallet,
-            total_funding_lamports: total_lamports_to_provide,
+        Ok(job)
+    }
+
+    async fn initiate_token_funding_job(
+        &mut self,
+        rpc: Rpc,
+        distro_wallet: Keypair,
+        target_pubkeys: Vec<Pubkey>,
+        mint: Pubkey,
+        amount_per_wallet: u64,
+        commitment: CommitmentConfig,
+    ) -> Result<&FundingJob, Error> {
+        check_plain_system_accounts(&rpc, &target_pubkeys, commitment).await?;
+
+        let (token_job, total_funding_lamports) = plan_token_funding(
+            &rpc,
+            &distro_wallet.pubkey(),
+            &target_pubkeys,
+            &mint,
+            amount_per_wallet,
+            commitment,
+        )
+        .await?;
+
+        let job = self.active_job.insert(FundingJob {
+            distro_wallet,
+            targets: target_pubkeys
+                .iter()
+                .map(|pubkey| FundingTarget {
+                    pubkey: *pubkey,
+                    lamports: 0,
+                })
+                .collect(),
+            relays: Vec::new(),
+            transfers: Vec::new(),
+            total_funding_lamports,
+            token: Some(token_job),
         });
 
-        return Ok(self.active_job.as_ref().unwrap());
+        Ok(job)
     }
 
     async fn complete_funding_job(
-        &self,
-        rpc_url: String,
+        &mut self,
+        rpc: Rpc,
         websocket_service: Websocket,
-    ) -> Result<(), Error> {
-        let job = match &self.active_job {
+        blockhashes: Blockhashes,
+        commitment: CommitmentConfig,
+    ) -> Result<FundingProgress, Error> {
+        let job = match &mut self.active_job {
             Some(j) => j,
             None => {
                 return Err(Error::FundingJobNotStarted(String::from(
@@ -66,84 +232,375 @@ impl Funding for LocalFunding {
             }
         };
 
-        let balance_result = get_balance(
-            &rpc_url,
-            "complete_funding_balance_req",
-            job.distro_wallet.pubkey().to_string().as_ref(),
-        )
-        .await;
+        if let Some(token_job) = &mut job.token {
+            complete_token_funding(
+                rpc,
+                websocket_service,
+                blockhashes,
+                &job.distro_wallet,
+                token_job,
+                commitment,
+            )
+            .await?;
+
+            let mut progress = FundingProgress {
+                confirmed: 0,
+                pending: 0,
+                failed: Vec::new(),
+            };
+            for batch in token_job.batches.iter() {
+                progress.count(&job.distro_wallet.pubkey(), &batch.status);
+            }
 
-        if balance_result.is_err() {
-            println!("Error getting balance {}", balance_result.err().unwrap());
-            return Err(Error::RpcError(String::from("Failed to get balance")));
+            return Ok(progress);
         }
 
-        let total_lamports_required = job.total_funding_lamports;
+        let sent = job
+            .transfers
+            .iter_mut()
+            .enumerate()
+            .filter(|(_, t)| !matches!(t.status, TransferStatus::Confirmed(_)))
+            .filter_map(|(i, t)| t.sent.as_ref().map(|sent| (i, &mut t.status, sent)))
+            .collect();
+        let in_flight = settle_sent_transfers(&rpc, sent, commitment).await?;
+
+        let min_rent_result =
+            get_minimum_balance_for_rent_exemption(&rpc, "complete_funding", 0, commitment.read)
+                .await?;
+        let total_lamports_required = remaining_distro_lamports(job, min_rent_result);
 
-        let provided_funding = balance_result.unwrap();
+        if total_lamports_required > 0 {
+            let balance_result = get_balance(
+                &rpc,
+                "complete_funding_balance_req",
+                job.distro_wallet.pubkey().to_string().as_ref(),
+                commitment.read,
+            )
+            .await;
 
-        if (provided_funding as u128) < total_lamports_required {
-            eprintln!("We dont have enough funding {}", provided_funding);
-            return Err(Error::InsufficientFunding(String::from(
-                "Insufficient funding to execute funding job",
-            )));
+            if balance_result.is_err() {
+                println!("Error getting balance {}", balance_result.err().unwrap());
+                return Err(Error::RpcError(String::from("Failed to get balance")));
+            }
+
+            let provided_funding = balance_result.unwrap();
+
+            if (provided_funding as u128) < total_lamports_required {
+                eprintln!("We dont have enough funding {}", provided_funding);
+                return Err(Error::InsufficientFunding(String::from(
+                    "Insufficient funding to execute funding job",
+                )));
+            }
         }
 
-        let latest_hash = match get_blockhash(&rpc_url, "funding").await {
-            Ok(v) => v,
-            Err(err) => {
-                eprintln!("failed to get blockhash, {}", err);
-                return Err(err);
+        check_rent_exemption(&rpc, &planned_transfers(job), &[], commitment).await?;
+
+        // Every round sends what became ready, so relays start once their own funding
+        // confirmed. A transaction is tried once per completion.
+        let mut attempted = vec![false; job.transfers.len()];
+        for i in in_flight {
+            attempted[i] = true;
+        }
+        loop {
+            let round: Vec<usize> = ready_transfers(job)
+                .into_iter()
+                .filter(|i| !attempted[*i])
+                .collect();
+            if round.is_empty() {
+                break;
             }
+
+            let mut send_set: JoinSet<(usize, Result<String, Error>)> = JoinSet::new();
+
+            for i in round {
+                attempted[i] = true;
+                let (txn, last_valid_block_height) = match sign_transfer(job, i, &blockhashes).await
+                {
+                    Ok(signed) => signed,
+                    Err(e) => {
+                        eprintln!("Funding task error: {:?}", e);
+                        job.transfers[i].status = TransferStatus::Failed(e.to_string());
+                        continue;
+                    }
+                };
+                // Remembered before sending, a resumed job looks it up instead of paying twice.
+                job.transfers[i].sent = Some(SentTransfer {
+                    signature: txn.signature.clone(),
+                    last_valid_block_height,
+                });
+
+                let rpc = Arc::clone(&rpc);
+                let websocket_service_arc = websocket_service.clone();
+
+                send_set.spawn(async move {
+                    let sig = txn.signature[..6].to_string();
+                    let result = send_and_confirm(
+                        &rpc,
+                        websocket_service_arc,
+                        "funding",
+                        &txn,
+                        last_valid_block_height,
+                        commitment,
+                    )
+                    .await;
+                    if result.is_ok() {
+                        println!("Confirmed {}", sig);
+                    }
+
+                    (i, result)
+                });
+            }
+
+            while let Some(res) = send_set.join_next().await {
+                match res {
+                    Ok((i, Ok(txn_hash))) => {
+                        println!("Funded: {}", txn_hash);
+                        job.transfers[i].status = TransferStatus::Confirmed(txn_hash);
+                    }
+                    Ok((i, Err(e))) => {
+                        eprintln!("Funding task error: {:?}", e);
+                        job.transfers[i].status = TransferStatus::Failed(e.to_string());
+                    }
+                    Err(e) => eprintln!("Join error: {:?}", e),
+                }
+            }
+        }
+
+        let mut progress = FundingProgress {
+            confirmed: 0,
+            pending: 0,
+            failed: Vec::new(),
         };
+        for transfer in job.transfers.iter() {
+            progress.count(&transfer.payer, &transfer.status);
+        }
 
-        let mut send_set: JoinSet<Result<String, Error>> = JoinSet::new();
+        Ok(progress)
+    }
 
-        let distro_wallet = Arc::new(job.distro_wallet.insecure_clone());
-        let lamports_per_wallet = job.lamports_per_wallet;
-        let rpc_url = Arc::new(rpc_url.clone());
+    async fn dry_run_funding_job(
+        &self,
+        rpc: Rpc,
+        blockhashes: Blockhashes,
+        commitment: CommitmentConfig,
+    ) -> Result<Vec<SimulationReport>, Error> {
+        let job = match &self.active_job {
+            Some(j) => j,
+            None => {
+                return Err(Error::FundingJobNotStarted(String::from(
+                    "There is no active funding job.",
+                )));
+            }
+        };
 
-        for pubkey in job.target_pubkeys.iter().cloned() {
-            let distro_wallet = Arc::clone(&distro_wallet);
-            let latest_hash = latest_hash.clone();
-            let rpc_url = Arc::clone(&rpc_url);
-            let websocket_service_arc = websocket_service.clone();
+        if let Some(token_job) = &job.token {
+            return dry_run_token_funding(
+                &rpc,
+                &blockhashes,
+                &job.distro_wallet,
+                token_job,
+                commitment,
+            )
+            .await;
+        }
 
-            send_set.spawn(async move {
-                let txn =
-                    build_sol_transfer(&distro_wallet, lamports_per_wallet, &pubkey, &latest_hash)
-                        .await?;
-                let sig = txn.signature[..6].to_string();
+        let latest_hash = blockhashes.get().await?.hash;
 
-                println!("Built txn {}", sig);
+        // Relays are funded only when the job runs, their transfers can't be simulated before.
+        let mut reports = Vec::new();
+        for i in ready_transfers(job) {
+            let transfer = &job.transfers[i];
+            let payer = payer_wallet(job, &transfer.payer);
+            let txn = build_sol_transfers(payer, None, &transfer.transfers, &latest_hash).await?;
 
-                let confirmation_handle = tokio::spawn(async move {
-                    let mut websocket = websocket_service_arc.write().await;
-                    websocket.confirm_transaction(&txn.signature).await;
-                });
+            reports.push(simulate(&rpc, "funding_dry_run", &txn, commitment).await?);
+        }
 
-                println!("Started confirmation {}", sig);
+        Ok(reports)
+    }
+}
 
-                let txn_hash = send_transaction(&rpc_url, "funding", &txn.transaction).await?;
+#[cfg(test)]
+mod tests {
+    use mock_node::MockNode;
+    use solana_sdk::account::Account;
 
-                println!("Sent {}", sig);
+    use super::*;
+    use crate::rpc::mock::mock_services;
 
-                confirmation_handle.await;
+    const LAMPORTS_PER_WALLET: u64 = 1_000_000;
 
-                println!("Confirmed {}", sig);
+    fn targets(wallets: &[Keypair]) -> Vec<FundingTarget> {
+        wallets
+            .iter()
+            .map(|w| FundingTarget {
+                pubkey: w.pubkey(),
+                lamports: LAMPORTS_PER_WALLET,
+            })
+            .collect()
+    }
 
-                Ok(txn_hash)
-            });
+    /// Starts a job over `wallets` and deposits what it asks for.
+    async fn deposited_job(
+        node: &MockNode,
+        rpc: &Rpc,
+        wallets: &[Keypair],
+        relays: usize,
+    ) -> LocalFunding {
+        let mut funding = LocalFunding::new();
+        let relays = wallets[..relays]
+            .iter()
+            .map(|w| w.insecure_clone())
+            .collect();
+        let job = funding
+            .initiate_funding_job(
+                Arc::clone(rpc),
+                Keypair::new(),
+                targets(wallets),
+                relays,
+                CommitmentConfig::default(),
+            )
+            .await
+            .unwrap();
+        node.airdrop(
+            &job.distro_wallet.pubkey(),
+            job.total_funding_lamports as u64,
+        )
+        .unwrap();
+
+        funding
+    }
+
+    #[tokio::test]
+    async fn funds_every_target() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallets: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let mut funding = deposited_job(&node, &rpc, &wallets, 0).await;
+
+        let progress = funding
+            .complete_funding_job(rpc, websocket, blockhashes, CommitmentConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(progress.confirmed, 1);
+        assert_eq!(progress.pending, 0);
+        assert!(progress.failed.is_empty());
+        for wallet in &wallets {
+            assert_eq!(node.balance(&wallet.pubkey()), LAMPORTS_PER_WALLET);
         }
+    }
 
-        while let Some(res) = send_set.join_next().await {
-            match res {
-                Ok(Ok(txn_hash)) => println!("Funded: {}", txn_hash),
-                Ok(Err(e)) => eprintln!("Funding task error: {:?}", e),
-                Err(e) => eprintln!("Join error: {:?}", e),
-            }
+    #[tokio::test]
+    async fn relays_pass_on_exactly_their_range() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallets: Vec<Keypair> = (0..5).map(|_| Keypair::new()).collect();
+        let mut funding = deposited_job(&node, &rpc, &wallets, 2).await;
+
+        let progress = funding
+            .complete_funding_job(rpc, websocket, blockhashes, CommitmentConfig::default())
+            .await
+            .unwrap();
+
+        // One transaction funding both relays, then one per relay.
+        assert_eq!(progress.confirmed, 3);
+        assert!(progress.failed.is_empty());
+        for wallet in &wallets {
+            assert_eq!(node.balance(&wallet.pubkey()), LAMPORTS_PER_WALLET);
         }
+    }
+
+    #[tokio::test]
+    async fn resuming_settles_sent_transfers_without_resending() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallets: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let mut funding = deposited_job(&node, &rpc, &wallets, 0).await;
+        funding
+            .complete_funding_job(
+                Arc::clone(&rpc),
+                websocket.clone(),
+                Arc::clone(&blockhashes),
+                CommitmentConfig::default(),
+            )
+            .await
+            .unwrap();
+
+        // As if the first completion stopped between sending and confirming.
+        let job = funding.active_job.as_mut().unwrap();
+        for transfer in job.transfers.iter_mut() {
+            transfer.status = TransferStatus::Pending;
+        }
+        let distro_balance = node.balance(&job.distro_wallet.pubkey());
+
+        let progress = funding
+            .complete_funding_job(rpc, websocket, blockhashes, CommitmentConfig::default())
+            .await
+            .unwrap();
+
+        assert_eq!(progress.confirmed, 1);
+        let job = funding.active_job.as_ref().unwrap();
+        assert_eq!(node.balance(&job.distro_wallet.pubkey()), distro_balance);
+        for wallet in &wallets {
+            assert_eq!(node.balance(&wallet.pubkey()), LAMPORTS_PER_WALLET);
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_to_complete_without_the_deposit() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallets: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        let mut funding = LocalFunding::new();
+        funding
+            .initiate_funding_job(
+                Arc::clone(&rpc),
+                Keypair::new(),
+                targets(&wallets),
+                Vec::new(),
+                CommitmentConfig::default(),
+            )
+            .await
+            .unwrap();
+
+        let result = funding
+            .complete_funding_job(rpc, websocket, blockhashes, CommitmentConfig::default())
+            .await;
+
+        assert!(matches!(result, Err(Error::InsufficientFunding(_))));
+        for wallet in &wallets {
+            assert_eq!(node.balance(&wallet.pubkey()), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_targets_taken_over_by_a_program() {
+        let node = MockNode::new();
+        let (rpc, _, _) = mock_services(&node).await;
+        let wallets: Vec<Keypair> = (0..3).map(|_| Keypair::new()).collect();
+        node.set_account(
+            wallets[1].pubkey(),
+            Account {
+                lamports: LAMPORTS_PER_WALLET,
+                data: vec![0; 8],
+                owner: Pubkey::new_unique(),
+                executable: false,
+                rent_epoch: 0,
+            },
+        );
+
+        let mut funding = LocalFunding::new();
+        let result = funding
+            .initiate_funding_job(
+                rpc,
+                Keypair::new(),
+                targets(&wallets),
+                Vec::new(),
+                CommitmentConfig::default(),
+            )
+            .await;
 
-        Ok(())
+        assert!(matches!(result, Err(Error::CompromisedAccount(_))));
     }
 }
@@ -1,2 +1,7 @@
+pub mod fan_out;
 pub mod funding;
 pub mod local_funding;
+pub mod resume;
+pub mod token_funding;
+pub mod top_up;
+pub mod treasury;
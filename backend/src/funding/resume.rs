@@ -0,0 +1,54 @@
+use crate::{
+    Rpc,
+    config::CommitmentConfig,
+    errors::errors::Error,
+    funding::funding::{SentTransfer, TransferStatus},
+    rpc::read::{get_block_height, get_signature_statuses},
+};
+
+/// Looks up the last signature of unconfirmed transactions sent before, given by their
+/// index. Landed ones are confirmed, the indices of the ones that may still land are
+/// returned so they aren't sent twice.
+pub async fn settle_sent_transfers(
+    rpc: &Rpc,
+    sent: Vec<(usize, &mut TransferStatus, &SentTransfer)>,
+    commitment: CommitmentConfig,
+) -> Result<Vec<usize>, Error> {
+    if sent.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let signatures: Vec<String> = sent.iter().map(|(_, _, s)| s.signature.clone()).collect();
+    let statuses = get_signature_statuses(rpc, "funding_statuses", &signatures).await?;
+    let block_height =
+        get_block_height(rpc, "funding_block_height", commitment.confirmation).await?;
+
+    let mut in_flight = Vec::new();
+    for ((i, transfer_status, sent), status) in sent.into_iter().zip(statuses) {
+        match status {
+            // Failed on chain, only the fee was paid and it is sent again.
+            Some(status) if status.err.is_some() => {}
+            Some(status)
+                if status
+                    .confirmation_status
+                    .is_some_and(|reached| reached >= commitment.confirmation) =>
+            {
+                println!("Funded: {}", sent.signature);
+                *transfer_status = TransferStatus::Confirmed(sent.signature.clone());
+            }
+            // Landed but not settled yet, or still valid and may land.
+            Some(_) => {
+                *transfer_status = TransferStatus::Pending;
+                in_flight.push(i);
+            }
+            None if block_height <= sent.last_valid_block_height => {
+                *transfer_status = TransferStatus::Pending;
+                in_flight.push(i);
+            }
+            // Expired without landing, safe to send again.
+            None => {}
+        }
+    }
+
+    Ok(in_flight)
+}
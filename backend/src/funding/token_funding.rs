@@ -0,0 +1,450 @@
+use std::sync::Arc;
+
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use spl_associated_token_account_interface::address::get_associated_token_address_with_program_id;
+use tokio::task::JoinSet;
+
+use crate::{
+    Blockhashes, Rpc, Websocket,
+    config::CommitmentConfig,
+    errors::errors::Error,
+    funding::{
+        funding::{SentTransfer, TokenFundingBatch, TokenFundingJob, TransferStatus},
+        resume::settle_sent_transfers,
+    },
+    rpc::{
+        read::{
+            MAX_ACCOUNTS_PER_REQUEST, get_balance, get_minimum_balance_for_rent_exemption,
+            get_multiple_accounts,
+        },
+        send::send_and_confirm,
+    },
+    tokens::{
+        mints::{MintInfo, get_mint},
+        token_accounts::{get_token_accounts, token_account_len},
+    },
+    txn_factory::{
+        rent::{PlannedTransfers, check_rent_exemption},
+        simulation::{SimulationReport, simulate},
+        token_transfer::{TokenRecipient, batch_token_recipients, build_token_transfers},
+        util::LAMPORTS_PER_SIGNATURE,
+    },
+};
+
+/// Plans the transfers of a token job and returns it with the lamports the
+/// distribution wallet needs for fees and the rent of missing token accounts.
+pub async fn plan_token_funding(
+    rpc: &Rpc,
+    distro_wallet: &Pubkey,
+    target_pubkeys: &[Pubkey],
+    mint: &Pubkey,
+    amount_per_wallet: u64,
+    commitment: CommitmentConfig,
+) -> Result<(TokenFundingJob, u128), Error> {
+    let mint = get_mint(rpc, mint, commitment.read).await?;
+
+    let mut recipients = Vec::new();
+    for chunk in target_pubkeys.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+        let token_accounts: Vec<String> = chunk
+            .iter()
+            .map(|owner| {
+                get_associated_token_address_with_program_id(owner, &mint.mint, &mint.program_id)
+                    .to_string()
+            })
+            .collect();
+        let accounts = get_multiple_accounts(
+            rpc,
+            "plan_token_funding",
+            &token_accounts,
+            None,
+            commitment.read,
+        )
+        .await?;
+
+        for (owner, account) in chunk.iter().zip(accounts.iter()) {
+            recipients.push(TokenRecipient {
+                owner: *owner,
+                create_token_account: account.is_none(),
+            });
+        }
+    }
+
+    let batches: Vec<TokenFundingBatch> =
+        batch_token_recipients(distro_wallet, &mint, &recipients, amount_per_wallet)
+            .into_iter()
+            .map(|recipients| TokenFundingBatch {
+                recipients,
+                status: TransferStatus::Pending,
+                sent: None,
+            })
+            .collect();
+
+    let total_funding_lamports =
+        remaining_lamports(rpc, &mint, batches.iter(), "plan_token_funding", commitment).await?;
+
+    let job = TokenFundingJob {
+        mint,
+        amount_per_wallet,
+        total_funding_amount: amount_per_wallet as u128 * target_pubkeys.len() as u128,
+        source_token_account: get_associated_token_address_with_program_id(
+            distro_wallet,
+            &mint.mint,
+            &mint.program_id,
+        ),
+        batches,
+    };
+
+    Ok((job, total_funding_lamports))
+}
+
+/// Lamports the distribution wallet needs for the fees and the new token accounts of
+/// `batches`, and the rent it keeps.
+async fn remaining_lamports(
+    rpc: &Rpc,
+    mint: &MintInfo,
+    batches: impl Iterator<Item = &TokenFundingBatch>,
+    request_id: &str,
+    commitment: CommitmentConfig,
+) -> Result<u128, Error> {
+    let (transactions, missing_token_accounts) = batches.fold((0u128, 0u128), |(t, m), b| {
+        let missing = b
+            .recipients
+            .iter()
+            .filter(|r| r.create_token_account)
+            .count();
+        (t + 1, m + missing as u128)
+    });
+    if transactions == 0 {
+        return Ok(0);
+    }
+
+    let token_account_rent = get_minimum_balance_for_rent_exemption(
+        rpc,
+        request_id,
+        token_account_len(&mint.program_id) as u128,
+        commitment.read,
+    )
+    .await?;
+    let distro_rent =
+        get_minimum_balance_for_rent_exemption(rpc, request_id, 0, commitment.read).await?;
+
+    Ok(LAMPORTS_PER_SIGNATURE as u128 * transactions
+        + token_account_rent * missing_token_accounts
+        + distro_rent)
+}
+
+fn unconfirmed(batch: &TokenFundingBatch) -> bool {
+    !matches!(batch.status, TransferStatus::Confirmed(_))
+}
+
+/// Checks both deposits cover what isn't confirmed yet, then sends those batches
+/// concurrently. The outcome of every batch is recorded on it.
+pub async fn complete_token_funding(
+    rpc: Rpc,
+    websocket_service: Websocket,
+    blockhashes: Blockhashes,
+    distro_wallet: &Keypair,
+    job: &mut TokenFundingJob,
+    commitment: CommitmentConfig,
+) -> Result<(), Error> {
+    let sent = job
+        .batches
+        .iter_mut()
+        .enumerate()
+        .filter(|(_, b)| unconfirmed(b))
+        .filter_map(|(i, b)| b.sent.as_ref().map(|sent| (i, &mut b.status, sent)))
+        .collect();
+    let in_flight = settle_sent_transfers(&rpc, sent, commitment).await?;
+
+    let total_funding_lamports = remaining_lamports(
+        &rpc,
+        &job.mint,
+        job.batches.iter().filter(|b| unconfirmed(b)),
+        "complete_token_funding",
+        commitment,
+    )
+    .await?;
+    if total_funding_lamports == 0 {
+        return Ok(());
+    }
+
+    let provided_lamports = get_balance(
+        &rpc,
+        "complete_token_funding_balance_req",
+        &distro_wallet.pubkey().to_string(),
+        commitment.read,
+    )
+    .await?;
+    if (provided_lamports as u128) < total_funding_lamports {
+        eprintln!("We dont have enough funding {}", provided_lamports);
+        return Err(Error::InsufficientFunding(String::from(
+            "Insufficient SOL to pay for the token funding job",
+        )));
+    }
+
+    let provided_tokens: u64 = get_token_accounts(&rpc, &distro_wallet.pubkey(), commitment.read)
+        .await?
+        .iter()
+        .filter(|a| a.pubkey == job.source_token_account)
+        .map(|a| a.amount)
+        .sum();
+    let remaining_tokens = job
+        .batches
+        .iter()
+        .filter(|b| unconfirmed(b))
+        .map(|b| b.recipients.len() as u128)
+        .sum::<u128>()
+        * job.amount_per_wallet as u128;
+    if (provided_tokens as u128) < remaining_tokens {
+        eprintln!("We dont have enough tokens {}", provided_tokens);
+        return Err(Error::InsufficientFunding(String::from(
+            "Insufficient tokens to execute funding job",
+        )));
+    }
+
+    // The rent of every token account a batch creates is paid like a transfer to it.
+    let token_account_rent = get_minimum_balance_for_rent_exemption(
+        &rpc,
+        "complete_token_funding",
+        token_account_len(&job.mint.program_id) as u128,
+        commitment.read,
+    )
+    .await? as u64;
+    let rent_payments: Vec<Vec<(Pubkey, u64)>> = job
+        .batches
+        .iter()
+        .filter(|b| unconfirmed(b))
+        .map(|b| {
+            b.recipients
+                .iter()
+                .filter(|r| r.create_token_account)
+                .map(|r| {
+                    let account = get_associated_token_address_with_program_id(
+                        &r.owner,
+                        &job.mint.mint,
+                        &job.mint.program_id,
+                    );
+                    (account, token_account_rent)
+                })
+                .collect()
+        })
+        .collect();
+    let planned: Vec<PlannedTransfers> = rent_payments
+        .iter()
+        .map(|transfers| PlannedTransfers {
+            payer: distro_wallet.pubkey(),
+            fee_payer: None,
+            transfers,
+        })
+        .collect();
+    check_rent_exemption(&rpc, &planned, &[], commitment).await?;
+
+    let mut send_set: JoinSet<(usize, Result<String, Error>)> = JoinSet::new();
+
+    let distro_wallet = Arc::new(distro_wallet.insecure_clone());
+    for i in 0..job.batches.len() {
+        if !unconfirmed(&job.batches[i]) || in_flight.contains(&i) {
+            continue;
+        }
+        let batch = &job.batches[i];
+        let signed = async {
+            let blockhash = blockhashes.get().await?;
+            let txn = build_token_transfers(
+                &distro_wallet,
+                &job.mint,
+                &batch.recipients,
+                job.amount_per_wallet,
+                &blockhash.hash,
+            )
+            .await?;
+            Ok::<_, Error>((txn, blockhash.last_valid_block_height))
+        };
+        let (txn, last_valid_block_height) = match signed.await {
+            Ok(signed) => signed,
+            Err(e) => {
+                eprintln!("Token funding task error: {:?}", e);
+                job.batches[i].status = TransferStatus::Failed(e.to_string());
+                continue;
+            }
+        };
+        println!(
+            "Built token txn {} for {} wallets",
+            &txn.signature[..6],
+            batch.recipients.len()
+        );
+        // Remembered before sending, a resumed job looks it up instead of paying twice.
+        job.batches[i].sent = Some(SentTransfer {
+            signature: txn.signature.clone(),
+            last_valid_block_height,
+        });
+
+        let rpc = Arc::clone(&rpc);
+        let websocket_service_arc = websocket_service.clone();
+
+        send_set.spawn(async move {
+            let sig = txn.signature[..6].to_string();
+            let result = send_and_confirm(
+                &rpc,
+                websocket_service_arc,
+                "token_funding",
+                &txn,
+                last_valid_block_height,
+                commitment,
+            )
+            .await;
+            if result.is_ok() {
+                println!("Confirmed {}", sig);
+            }
+
+            (i, result)
+        });
+    }
+
+    while let Some(res) = send_set.join_next().await {
+        match res {
+            Ok((i, Ok(txn_hash))) => {
+                println!("Funded: {}", txn_hash);
+                job.batches[i].status = TransferStatus::Confirmed(txn_hash);
+            }
+            Ok((i, Err(e))) => {
+                eprintln!("Token funding task error: {:?}", e);
+                job.batches[i].status = TransferStatus::Failed(e.to_string());
+            }
+            Err(e) => eprintln!("Join error: {:?}", e),
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn dry_run_token_funding(
+    rpc: &Rpc,
+    blockhashes: &Blockhashes,
+    distro_wallet: &Keypair,
+    job: &TokenFundingJob,
+    commitment: CommitmentConfig,
+) -> Result<Vec<SimulationReport>, Error> {
+    let latest_hash = blockhashes.get().await?.hash;
+
+    let mut reports = Vec::new();
+    for batch in job.batches.iter().filter(|b| unconfirmed(b)) {
+        let txn = build_token_transfers(
+            distro_wallet,
+            &job.mint,
+            &batch.recipients,
+            job.amount_per_wallet,
+            &latest_hash,
+        )
+        .await?;
+
+        reports.push(simulate(rpc, "token_funding_dry_run", &txn, commitment).await?);
+    }
+
+    Ok(reports)
+}
+
+#[cfg(test)]
+mod tests {
+    use mock_node::MockNode;
+
+    use super::*;
+    use crate::rpc::mock::{mock_mint, mock_services, mock_token_account, mock_token_amount};
+
+    const AMOUNT_PER_WALLET: u64 = 250_000;
+
+    #[tokio::test]
+    async fn funds_every_target_creating_missing_token_accounts() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let distro_wallet = Keypair::new();
+        let mint = mock_mint(&node, &Pubkey::new_unique(), 6);
+        let targets: Vec<Pubkey> = (0..3).map(|_| Pubkey::new_unique()).collect();
+        // One target already holds the mint, only the other two need a token account.
+        mock_token_account(&node, &targets[0], &mint, 10);
+
+        let (mut job, lamports) = plan_token_funding(
+            &rpc,
+            &distro_wallet.pubkey(),
+            &targets,
+            &mint,
+            AMOUNT_PER_WALLET,
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
+        node.airdrop(&distro_wallet.pubkey(), lamports as u64)
+            .unwrap();
+        mock_token_account(
+            &node,
+            &distro_wallet.pubkey(),
+            &mint,
+            job.total_funding_amount as u64,
+        );
+
+        complete_token_funding(
+            rpc,
+            websocket,
+            blockhashes,
+            &distro_wallet,
+            &mut job,
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(
+            job.batches
+                .iter()
+                .all(|b| matches!(b.status, TransferStatus::Confirmed(_)))
+        );
+        assert_eq!(mock_token_amount(&node, &job.source_token_account), 0);
+        for (i, target) in targets.iter().enumerate() {
+            let account = get_associated_token_address_with_program_id(
+                target,
+                &mint,
+                &spl_token_interface::ID,
+            );
+            let held = if i == 0 { 10 } else { 0 };
+            assert_eq!(mock_token_amount(&node, &account), held + AMOUNT_PER_WALLET);
+        }
+    }
+
+    #[tokio::test]
+    async fn refuses_to_complete_without_the_token_deposit() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let distro_wallet = Keypair::new();
+        let mint = mock_mint(&node, &Pubkey::new_unique(), 6);
+        let targets: Vec<Pubkey> = (0..2).map(|_| Pubkey::new_unique()).collect();
+
+        let (mut job, lamports) = plan_token_funding(
+            &rpc,
+            &distro_wallet.pubkey(),
+            &targets,
+            &mint,
+            AMOUNT_PER_WALLET,
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
+        node.airdrop(&distro_wallet.pubkey(), lamports as u64)
+            .unwrap();
+        mock_token_account(&node, &distro_wallet.pubkey(), &mint, AMOUNT_PER_WALLET);
+
+        let result = complete_token_funding(
+            rpc,
+            websocket,
+            blockhashes,
+            &distro_wallet,
+            &mut job,
+            CommitmentConfig::default(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(Error::InsufficientFunding(_))));
+        assert_eq!(
+            mock_token_amount(&node, &job.source_token_account),
+            AMOUNT_PER_WALLET
+        );
+    }
+}
@@ -0,0 +1,37 @@
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    Rpc,
+    config::CommitmentConfig,
+    errors::errors::Error,
+    funding::funding::FundingTarget,
+    rpc::read::{MAX_ACCOUNTS_PER_REQUEST, get_multiple_accounts},
+};
+
+/// The lamports each wallet is short of `target_balance`. Wallets already at or
+/// above it are left out.
+pub async fn top_up_targets(
+    rpc: &Rpc,
+    pubkeys: &[Pubkey],
+    target_balance: u64,
+    commitment: CommitmentConfig,
+) -> Result<Vec<FundingTarget>, Error> {
+    let mut targets = Vec::new();
+    for chunk in pubkeys.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+        let chunk_pubkeys: Vec<String> = chunk.iter().map(|p| p.to_string()).collect();
+        let accounts =
+            get_multiple_accounts(rpc, "top_up", &chunk_pubkeys, None, commitment.read).await?;
+
+        for (pubkey, account) in chunk.iter().zip(accounts.iter()) {
+            let balance = account.as_ref().map(|a| a.lamports).unwrap_or(0);
+            if balance < target_balance {
+                targets.push(FundingTarget {
+                    pubkey: *pubkey,
+                    lamports: target_balance - balance,
+                });
+            }
+        }
+    }
+
+    Ok(targets)
+}
@@ -0,0 +1,41 @@
+use solana_sdk::signer::Signer;
+
+use crate::{
+    Rpc, config::CommitmentConfig, errors::errors::Error, funding::funding::FundingJob,
+    rpc::read::get_balance, tokens::token_accounts::get_token_accounts,
+};
+
+/// What still has to be deposited into the job's funding wallet: lamports and, for
+/// token jobs, tokens. Zero for both when a treasury holds enough to run the job.
+pub async fn funding_shortfall(
+    rpc: &Rpc,
+    job: &FundingJob,
+    commitment: CommitmentConfig,
+) -> Result<(u128, u128), Error> {
+    let pubkey = job.distro_wallet.pubkey();
+    let lamports = get_balance(
+        rpc,
+        "funding_shortfall",
+        &pubkey.to_string(),
+        commitment.read,
+    )
+    .await?;
+    let lamports_short = job.total_funding_lamports.saturating_sub(lamports as u128);
+
+    let Some(token_job) = &job.token else {
+        return Ok((lamports_short, 0));
+    };
+    let tokens: u64 = get_token_accounts(rpc, &pubkey, commitment.read)
+        .await?
+        .iter()
+        .filter(|a| a.pubkey == token_job.source_token_account)
+        .map(|a| a.amount)
+        .sum();
+
+    Ok((
+        lamports_short,
+        token_job
+            .total_funding_amount
+            .saturating_sub(tokens as u128),
+    ))
+}
@@ -9,7 +9,19 @@ use tokio::sync::RwLock;
 use tower_http::cors::{Any, CorsLayer};
 
 use crate::{
-    config::Config, txn_factory::swaps::swap_jupiter, websocket::solana_websocket::SolanaWebsocket,
+    config::{CommitmentConfig, Config, SwapConfig},
+    rpc::transport::{HttpTransport, RpcTransport},
+    swapping::{
+        jupiter_swap::JupiterSwap, jupiter_ultra::JupiterUltra, raydium::Raydium,
+        router::SwapRouter, swap_provider::SwapProvider,
+    },
+    txn_factory::blockhash::BlockhashCache,
+    websocket::solana_websocket::SolanaWebsocket,
+};
+#[cfg(feature = "mock")]
+use crate::{
+    paper::{paper_ledger::PaperLedger, paper_swaps::PaperSwaps},
+    rpc::mock::MockTransport,
 };
 
 mod collecting;
@@ -17,25 +29,56 @@ mod config;
 mod endpoints;
 mod errors;
 mod funding;
+#[cfg(feature = "mock")]
+mod paper;
+mod rebalancing;
 mod rpc;
 mod storage;
+mod swapping;
+mod tokens;
 mod txn_factory;
 mod websocket;
 
 #[derive(Clone)]
 pub struct AppState {
     pub services: AppServices,
-    pub rpc_url: String,
+    pub rpc: Rpc,
     pub config: Arc<RwLock<crate::config::Config>>,
 }
 
+pub type Rpc = Arc<dyn RpcTransport>;
 pub type Websocket = Arc<RwLock<Box<SolanaWebsocket>>>;
+pub type Blockhashes = Arc<BlockhashCache>;
+pub type Swaps = Arc<SwapRouter>;
 
 #[derive(Clone)]
 pub struct AppServices {
     pub funding: Arc<RwLock<Box<dyn funding::funding::Funding>>>,
     pub websocket: Websocket,
+    pub blockhashes: Blockhashes,
+    pub swaps: Swaps,
     pub database: Arc<RwLock<tokio_rusqlite::Connection>>,
+    #[cfg(feature = "mock")]
+    pub paper: Option<paper::Paper>,
+}
+
+fn helius_transport() -> Option<Rpc> {
+    let helius_api_key = env::var("HELIUS_API_KEY").ok()?;
+
+    let rpc_url = format!("https://mainnet.helius-rpc.com/?api-key={}", helius_api_key);
+    let websocket_url = format!("wss://mainnet.helius-rpc.com/?api-key={}", helius_api_key);
+
+    Some(Arc::new(
+        HttpTransport::new(&rpc_url, &websocket_url).expect("failed to create rpc transport"),
+    ))
+}
+
+fn swap_providers(config: &SwapConfig) -> Vec<Arc<dyn SwapProvider>> {
+    vec![
+        Arc::new(JupiterUltra::new(&config.jupiter_ultra_url)),
+        Arc::new(JupiterSwap::new(&config.jupiter_swap_url)),
+        Arc::new(Raydium::new(&config.raydium_url)),
+    ]
 }
 
 async fn health() -> Json<serde_json::Value> {
@@ -48,27 +91,76 @@ async fn health() -> Json<serde_json::Value> {
 async fn main() {
     dotenv().ok();
 
-    let helius_api_key =
-        env::var("HELIUS_API_KEY").expect("Missing HELIUS_API_KEY in environment or .env file");
+    let db_path = env::var("DATABASE_PATH").expect("no database path in env");
+    let db_path = Path::new(&db_path);
 
-    let rpc_url = format!("https://mainnet.helius-rpc.com/?api-key={}", helius_api_key);
-    let websocket_url = format!("wss://mainnet.helius-rpc.com/?api-key={}", helius_api_key);
+    let database = tokio_rusqlite::Connection::open(db_path)
+        .await
+        .expect("failed to connect to db");
+
+    #[cfg(feature = "mock")]
+    let mut paper = None;
+    let rpc: Rpc = match env::var("RPC_TRANSPORT").as_deref() {
+        #[cfg(feature = "mock")]
+        Ok("mock") => {
+            println!("Using the in-process mock node, nothing is sent to a cluster.");
+            Arc::new(MockTransport::new(mock_node::MockNode::new()))
+        }
+        #[cfg(feature = "mock")]
+        Ok("paper") => {
+            println!("Paper trading, transactions run in an embedded SVM.");
+            let ledger = PaperLedger::open(&database)
+                .await
+                .expect("failed to open paper ledger");
+            let node = mock_node::MockNode::with_ledger(Box::new(ledger));
+            paper = Some(paper::Paper {
+                node: node.clone(),
+                cluster: helius_transport(),
+            });
+            Arc::new(MockTransport::new(node))
+        }
+        #[cfg(not(feature = "mock"))]
+        Ok(transport @ ("mock" | "paper")) => {
+            panic!("RPC_TRANSPORT={transport} needs the backend built with --features mock")
+        }
+        _ => helius_transport().expect("Missing HELIUS_API_KEY in environment or .env file"),
+    };
 
-    let ws = SolanaWebsocket::new(&websocket_url).await;
+    let commitment = CommitmentConfig::from_env();
+    let swap_config = SwapConfig::from_env();
+    let ws = SolanaWebsocket::new(Arc::clone(&rpc)).await;
+
+    let providers = swap_providers(&swap_config);
+    // Paper swaps are built like real ones, then run against pools copied into the paper ledger.
+    #[cfg(feature = "mock")]
+    let providers = match &paper {
+        Some(paper) => providers
+            .into_iter()
+            .map(|p| {
+                Arc::new(PaperSwaps::new(p, paper.clone(), commitment.read))
+                    as Arc<dyn SwapProvider>
+            })
+            .collect(),
+        None => providers,
+    };
 
-    let db_path = env::var("DATABASE_PATH").expect("no database path in env");
-    let db_path = Path::new(&db_path);
+    storage::swap_storage::create_swaps_table(&database)
+        .await
+        .expect("failed to create the swaps table");
+    storage::mnemonic_wallet_storage::create_wallet_tags_table(&database)
+        .await
+        .expect("failed to create the wallet tags table");
 
     let services = AppServices {
         funding: Arc::new(tokio::sync::RwLock::new(Box::new(
             funding::local_funding::LocalFunding::new(),
         ))),
         websocket: Arc::new(RwLock::new(Box::new(ws))),
-        database: Arc::new(RwLock::new(
-            tokio_rusqlite::Connection::open(db_path)
-                .await
-                .expect("failed to connect to db"),
-        )),
+        blockhashes: BlockhashCache::spawn(Arc::clone(&rpc), commitment.blockhash),
+        swaps: Arc::new(SwapRouter::new(providers)),
+        database: Arc::new(RwLock::new(database)),
+        #[cfg(feature = "mock")]
+        paper,
     };
 
     let mut bytes: Vec<u8> = env::var("MNEMONIC")
@@ -82,8 +174,13 @@ async fn main() {
 
     let state = AppState {
         services,
-        rpc_url: rpc_url,
-        config: Arc::new(RwLock::new(Config { wallet_seed: bytes })),
+        rpc,
+        config: Arc::new(RwLock::new(Config {
+            wallet_seed: bytes,
+            treasury_enabled: env::var("TREASURY_ENABLED").is_ok_and(|v| v == "true"),
+            commitment,
+            swap: swap_config,
+        })),
     };
 
     let cors = CorsLayer::new()
@@ -93,13 +190,39 @@ async fn main() {
 
     let app = Router::new()
         .route("/health", get(health))
+        .route("/blockhash", get(endpoints::blockhash::latest_blockhash))
         .route("/wallets/create", post(endpoints::wallet::create_wallets))
         .route("/wallets/list", get(endpoints::wallet::list_wallets))
+        .route("/wallets/airdrop", post(endpoints::wallet::airdrop))
+        .route("/wallets/tag", post(endpoints::wallet::tag_wallets))
+        .route(
+            "/wallets/reclaim-rent",
+            post(endpoints::wallet::reclaim_rent_job),
+        )
+        .route("/wallets/wrap-sol", post(endpoints::wsol::wrap_sol))
+        .route("/wallets/unwrap-sol", post(endpoints::wsol::unwrap_sol))
         .route("/funding/initiate", post(endpoints::funding::initiate_job))
+        .route(
+            "/funding/initiate/csv",
+            post(endpoints::funding::initiate_csv_job),
+        )
+        .route(
+            "/funding/token/initiate",
+            post(endpoints::funding::initiate_token_job),
+        )
         .route("/funding/complete", post(endpoints::funding::complete_job))
         .route("/collect", post(endpoints::collecting::collect_sol))
-        .with_state(state)
-        .layer(cors);
+        .route("/treasury", get(endpoints::treasury::treasury))
+        .route(
+            "/fleet/rebalance",
+            post(endpoints::rebalancing::rebalance_fleet),
+        )
+        .route("/swap", post(endpoints::swap::swap))
+        .route("/swap/quote", get(endpoints::swap::quote))
+        .route("/swap/liquidate", post(endpoints::swap::liquidate));
+    #[cfg(feature = "mock")]
+    let app = app.route("/paper/sync", post(endpoints::paper::sync_fleet));
+    let app = app.with_state(state).layer(cors);
 
     let addr = SocketAddr::from(([127, 0, 0, 1], 8764));
     println!("listening on {}", addr);
@@ -0,0 +1,104 @@
+pub mod paper_ledger;
+pub mod paper_swaps;
+
+use std::str::FromStr;
+
+use mock_node::MockNode;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+
+use crate::{
+    Rpc,
+    errors::errors::Error,
+    rpc::{
+        commitment::Commitment,
+        read::{
+            AccountEncoding, MAX_ACCOUNTS_PER_REQUEST, RpcAccount, get_multiple_accounts,
+            get_token_accounts_by_owner,
+        },
+    },
+    tokens::token_accounts::TOKEN_PROGRAM_IDS,
+};
+
+/// Services only present when the backend runs in paper trading mode.
+#[derive(Clone)]
+pub struct Paper {
+    pub node: MockNode,
+    /// The real cluster, used to copy the fleet's balances and the pools of swaps
+    /// into the paper ledger.
+    pub cluster: Option<Rpc>,
+}
+
+/// The cluster's account as the paper ledger stores it, `None` if it can't be decoded.
+pub fn paper_account(account: &RpcAccount) -> Option<Account> {
+    Some(Account {
+        lamports: account.lamports,
+        data: account.decoded_data().ok()?,
+        owner: Pubkey::from_str(&account.owner).ok()?,
+        executable: account.executable,
+        rent_epoch: account.rent_epoch,
+    })
+}
+
+/// The accounts among `pubkeys` that exist on the cluster, with their data.
+pub async fn cluster_accounts(
+    cluster: &Rpc,
+    request_id: &str,
+    pubkeys: &[Pubkey],
+    commitment: Commitment,
+) -> Result<Vec<(Pubkey, Account)>, Error> {
+    let mut accounts = Vec::new();
+    for chunk in pubkeys.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+        let keys: Vec<String> = chunk.iter().map(|p| p.to_string()).collect();
+        let fetched = get_multiple_accounts(
+            cluster,
+            request_id,
+            &keys,
+            Some(AccountEncoding::Base64),
+            commitment,
+        )
+        .await?;
+
+        for (pubkey, account) in chunk.iter().zip(fetched.iter()) {
+            let Some(account) = account else {
+                continue;
+            };
+            match paper_account(account) {
+                Some(account) => accounts.push((*pubkey, account)),
+                None => eprintln!("Skipping undecodable cluster account {}", pubkey),
+            }
+        }
+    }
+
+    Ok(accounts)
+}
+
+/// Every Token and Token-2022 account `owner` has on the cluster, with their data.
+pub async fn cluster_token_accounts(
+    cluster: &Rpc,
+    owner: &Pubkey,
+    commitment: Commitment,
+) -> Result<Vec<(Pubkey, Account)>, Error> {
+    let mut accounts = Vec::new();
+    for program_id in TOKEN_PROGRAM_IDS {
+        let keyed = get_token_accounts_by_owner(
+            cluster,
+            "paper_sync",
+            &owner.to_string(),
+            &program_id.to_string(),
+            commitment,
+        )
+        .await?;
+
+        for keyed in keyed {
+            match (
+                Pubkey::from_str(&keyed.pubkey),
+                paper_account(&keyed.account),
+            ) {
+                (Ok(pubkey), Some(account)) => accounts.push((pubkey, account)),
+                _ => eprintln!("Skipping undecodable cluster account {}", keyed.pubkey),
+            }
+        }
+    }
+
+    Ok(accounts)
+}
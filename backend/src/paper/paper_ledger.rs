@@ -0,0 +1,204 @@
+use std::str::FromStr;
+
+use mock_node::{Execution, Ledger, ProcessedTransaction, SignatureStatus, SvmLedger};
+use rusqlite::params;
+use solana_sdk::{
+    account::Account, hash::Hash, pubkey::Pubkey, signature::Signature,
+    transaction::VersionedTransaction,
+};
+use tokio::sync::mpsc::{UnboundedSender, unbounded_channel};
+use tokio_rusqlite::Connection;
+
+use crate::errors::errors::Error;
+
+/// A change to the `paper_accounts` table, `None` removes the account.
+type PaperWrite = (Pubkey, Option<Account>);
+
+/// Runs transactions in the mock node's LiteSVM ledger and keeps every account it
+/// touches in the `paper_accounts` table, so the paper fleet survives restarts.
+pub struct PaperLedger {
+    svm: SvmLedger,
+    /// Writes go to a background task on the app's database connection, in order,
+    /// so transactions never wait on the disk.
+    writes: UnboundedSender<PaperWrite>,
+}
+
+impl PaperLedger {
+    pub async fn open(database: &Connection) -> Result<Self, Error> {
+        let mut svm = SvmLedger::new();
+
+        let accounts = database
+            .call(|conn| {
+                conn.execute(
+                    "
+                    CREATE TABLE IF NOT EXISTS paper_accounts (
+                        pubkey TEXT PRIMARY KEY,
+                        lamports INTEGER NOT NULL,
+                        owner TEXT NOT NULL,
+                        data BLOB NOT NULL,
+                        executable INTEGER NOT NULL,
+                        rent_epoch INTEGER NOT NULL
+                    );
+                    ",
+                    [],
+                )?;
+
+                let mut stmt = conn.prepare(
+                    "SELECT pubkey, lamports, owner, data, executable, rent_epoch FROM paper_accounts;",
+                )?;
+                let accounts = stmt
+                    .query_map([], |row| {
+                        Ok((
+                            row.get::<usize, String>(0)?,
+                            row.get::<usize, i64>(1)?,
+                            row.get::<usize, String>(2)?,
+                            row.get::<usize, Vec<u8>>(3)?,
+                            row.get::<usize, bool>(4)?,
+                            row.get::<usize, i64>(5)?,
+                        ))
+                    })?
+                    .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+
+                Ok(accounts)
+            })
+            .await?;
+
+        // Programs copied for paper swaps load from their program data, restored first.
+        let (programs, accounts): (Vec<_>, Vec<_>) = accounts
+            .into_iter()
+            .partition(|(.., executable, _)| *executable);
+        for (pubkey, lamports, owner, data, executable, rent_epoch) in
+            accounts.into_iter().chain(programs)
+        {
+            let (Ok(pubkey), Ok(owner)) = (Pubkey::from_str(&pubkey), Pubkey::from_str(&owner))
+            else {
+                eprintln!("Skipping invalid paper account {}", pubkey);
+                continue;
+            };
+            let account = Account {
+                lamports: lamports as u64,
+                data,
+                owner,
+                executable,
+                rent_epoch: rent_epoch as u64,
+            };
+            svm.set_account(pubkey, account);
+        }
+
+        Ok(Self {
+            svm,
+            writes: spawn_writer(database.clone()),
+        })
+    }
+
+    fn persist(&self, pubkeys: &[Pubkey]) {
+        for pubkey in pubkeys {
+            let account = self.svm.account(pubkey);
+            if self.writes.send((*pubkey, account)).is_err() {
+                eprintln!(
+                    "Failed to persist paper account {}: the writer stopped",
+                    pubkey
+                );
+            }
+        }
+    }
+}
+
+/// Applies the ledger's writes to the `paper_accounts` table one after another.
+fn spawn_writer(database: Connection) -> UnboundedSender<PaperWrite> {
+    let (sender, mut receiver) = unbounded_channel::<PaperWrite>();
+    tokio::spawn(async move {
+        while let Some((pubkey, account)) = receiver.recv().await {
+            let result = database
+                .call(move |conn| {
+                    match account {
+                        Some(account) => conn.execute(
+                            "
+                            INSERT OR REPLACE INTO paper_accounts
+                                (pubkey, lamports, owner, data, executable, rent_epoch)
+                            VALUES (?1, ?2, ?3, ?4, ?5, ?6);
+                            ",
+                            params![
+                                pubkey.to_string(),
+                                account.lamports as i64,
+                                account.owner.to_string(),
+                                account.data,
+                                account.executable,
+                                account.rent_epoch as i64
+                            ],
+                        )?,
+                        None => conn.execute(
+                            "DELETE FROM paper_accounts WHERE pubkey = ?1;",
+                            params![pubkey.to_string()],
+                        )?,
+                    };
+
+                    Ok(())
+                })
+                .await;
+
+            if let Err(err) = result {
+                eprintln!("Failed to persist paper account {}: {}", pubkey, err);
+            }
+        }
+    });
+
+    sender
+}
+
+impl Ledger for PaperLedger {
+    fn slot(&self) -> u64 {
+        self.svm.slot()
+    }
+
+    fn block_height(&self) -> u64 {
+        self.svm.block_height()
+    }
+
+    fn latest_blockhash(&self) -> (Hash, u64) {
+        self.svm.latest_blockhash()
+    }
+
+    fn minimum_balance_for_rent_exemption(&self, data_len: usize) -> u64 {
+        self.svm.minimum_balance_for_rent_exemption(data_len)
+    }
+
+    fn account(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.svm.account(pubkey)
+    }
+
+    fn program_accounts(&self, program_id: &Pubkey) -> Vec<(Pubkey, Account)> {
+        self.svm.program_accounts(program_id)
+    }
+
+    fn set_account(&mut self, pubkey: Pubkey, account: Account) {
+        self.svm.set_account(pubkey, account);
+        self.persist(&[pubkey]);
+    }
+
+    fn airdrop(&mut self, pubkey: &Pubkey, lamports: u64) -> Result<Signature, String> {
+        let signature = self.svm.airdrop(pubkey, lamports)?;
+        self.persist(&[*pubkey]);
+
+        Ok(signature)
+    }
+
+    fn signature_status(&self, signature: &Signature) -> Option<SignatureStatus> {
+        self.svm.signature_status(signature)
+    }
+
+    fn transaction(&self, signature: &Signature) -> Option<ProcessedTransaction> {
+        self.svm.transaction(signature)
+    }
+
+    fn simulate(&self, txn: &VersionedTransaction) -> Result<Execution, String> {
+        self.svm.simulate(txn)
+    }
+
+    fn process(&mut self, txn: &VersionedTransaction) -> Result<Execution, String> {
+        let execution = self.svm.process(txn)?;
+        self.persist(&execution.changed_accounts());
+
+        Ok(execution)
+    }
+}
@@ -0,0 +1,346 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use mock_node::MockNode;
+use solana_sdk::{account::Account, message::VersionedMessage, program_pack::Pack, pubkey::Pubkey};
+use spl_token_interface::state::Account as SplTokenAccount;
+
+use crate::{
+    Rpc,
+    errors::errors::Error,
+    paper::{Paper, cluster_accounts},
+    rpc::commitment::Commitment,
+    swapping::swap_provider::{SwapOrder, SwapProvider, SwapQuote, SwapTransaction},
+    tokens::token_accounts::TOKEN_PROGRAM_IDS,
+};
+
+const ADDRESS_LOOKUP_TABLE_PROGRAM_ID: Pubkey =
+    Pubkey::from_str_const("AddressLookupTab1e1111111111111111111111111");
+const UPGRADEABLE_LOADER_ID: Pubkey =
+    Pubkey::from_str_const("BPFLoaderUpgradeab1e11111111111111111111111");
+const SYSVAR_OWNER_ID: Pubkey =
+    Pubkey::from_str_const("Sysvar1111111111111111111111111111111111111");
+
+/// A lookup table's metadata, its addresses follow it.
+const LOOKUP_TABLE_META_SIZE: usize = 56;
+/// Where the slot the table was last extended at sits in the metadata.
+const LOOKUP_TABLE_LAST_EXTENDED_SLOT: std::ops::Range<usize> = 12..20;
+/// An upgradeable program account is its state tag followed by its program data address.
+const PROGRAM_DATA_ADDRESS: std::ops::Range<usize> = 4..36;
+/// The token account layout starts with the mint followed by the owner.
+const TOKEN_ACCOUNT_OWNER: std::ops::Range<usize> = 32..64;
+
+/// Quotes through the real provider, then copies every account its swap transaction
+/// reads or writes from the cluster into the paper ledger, so the swap runs there
+/// against the pools as they are on mainnet. The fleet's own wallets and token
+/// accounts are never copied, they keep their paper balances.
+pub struct PaperSwaps {
+    provider: Arc<dyn SwapProvider>,
+    paper: Paper,
+    commitment: Commitment,
+}
+
+impl PaperSwaps {
+    pub fn new(provider: Arc<dyn SwapProvider>, paper: Paper, commitment: Commitment) -> Self {
+        Self {
+            provider,
+            paper,
+            commitment,
+        }
+    }
+}
+
+#[async_trait]
+impl SwapProvider for PaperSwaps {
+    fn name(&self) -> &'static str {
+        self.provider.name()
+    }
+
+    async fn quote(&self, order: &SwapOrder, taker: Option<&Pubkey>) -> Result<SwapQuote, Error> {
+        self.provider.quote(order, taker).await
+    }
+
+    async fn build_transaction(
+        &self,
+        quote: &SwapQuote,
+        taker: &Pubkey,
+    ) -> Result<SwapTransaction, Error> {
+        let Some(cluster) = &self.paper.cluster else {
+            return Err(Error::Swap(
+                "No cluster to copy the swap's pools from, set HELIUS_API_KEY.".to_owned(),
+            ));
+        };
+
+        let mut built = self.provider.build_transaction(quote, taker).await?;
+        fork_accounts(
+            cluster,
+            &self.paper.node,
+            &built.transaction.message,
+            self.commitment,
+        )
+        .await?;
+
+        // The provider picked a mainnet blockhash, the paper ledger only takes its own.
+        let (blockhash, last_valid_block_height) = self.paper.node.latest_blockhash();
+        built.transaction.message.set_recent_blockhash(blockhash);
+        built.last_valid_block_height = Some(last_valid_block_height);
+
+        Ok(built)
+    }
+}
+
+/// Copies the accounts of the message, including the ones it loads from lookup tables
+/// and the program data of upgradeable programs, from the cluster into the paper ledger.
+async fn fork_accounts(
+    cluster: &Rpc,
+    node: &MockNode,
+    message: &VersionedMessage,
+    commitment: Commitment,
+) -> Result<(), Error> {
+    let keys = message.static_account_keys();
+    let signers = &keys[..message.header().num_required_signatures as usize];
+    let mut pubkeys = keys.to_vec();
+
+    let lookups = message.address_table_lookups().unwrap_or_default();
+    let tables: Vec<Pubkey> = lookups.iter().map(|l| l.account_key).collect();
+    for (table, mut account) in cluster_accounts(cluster, "paper_swap", &tables, commitment).await?
+    {
+        let addresses: Vec<Pubkey> = account
+            .data
+            .get(LOOKUP_TABLE_META_SIZE..)
+            .unwrap_or_default()
+            .chunks_exact(32)
+            .map(|a| Pubkey::try_from(a).expect("chunks are 32 bytes"))
+            .collect();
+        for lookup in lookups.iter().filter(|l| l.account_key == table) {
+            let indexes = lookup
+                .writable_indexes
+                .iter()
+                .chain(&lookup.readonly_indexes);
+            pubkeys.extend(indexes.filter_map(|i| addresses.get(*i as usize)));
+        }
+
+        // Addresses extended after the paper ledger's slot would not be usable yet.
+        if account.owner == ADDRESS_LOOKUP_TABLE_PROGRAM_ID
+            && let Some(last_extended_slot) = account.data.get_mut(LOOKUP_TABLE_LAST_EXTENDED_SLOT)
+        {
+            last_extended_slot.fill(0);
+        }
+        node.set_account(table, account);
+    }
+
+    let accounts: Vec<(Pubkey, Account)> =
+        cluster_accounts(cluster, "paper_swap", &pubkeys, commitment)
+            .await?
+            .into_iter()
+            .filter(|(pubkey, account)| copied(node, signers, pubkey, account))
+            .collect();
+
+    // Upgradeable programs are loaded from their program data, which has to be there first.
+    let program_data: Vec<Pubkey> = accounts
+        .iter()
+        .filter(|(_, a)| a.executable && a.owner == UPGRADEABLE_LOADER_ID)
+        .filter_map(|(_, a)| a.data.get(PROGRAM_DATA_ADDRESS))
+        .filter_map(|address| Pubkey::try_from(address).ok())
+        .collect();
+    for (pubkey, account) in
+        cluster_accounts(cluster, "paper_swap", &program_data, commitment).await?
+    {
+        node.set_account(pubkey, account);
+    }
+    for (pubkey, account) in accounts {
+        node.set_account(pubkey, account);
+    }
+
+    Ok(())
+}
+
+/// Whether the cluster's `account` replaces the paper ledger's. Signers and their token
+/// accounts keep their paper state, sysvars and programs already there are left alone.
+fn copied(node: &MockNode, signers: &[Pubkey], pubkey: &Pubkey, account: &Account) -> bool {
+    if signers.contains(pubkey) || account.owner == SYSVAR_OWNER_ID {
+        return false;
+    }
+    if account.executable && node.account(pubkey).is_some_and(|a| a.executable) {
+        return false;
+    }
+    let signers_token_account = TOKEN_PROGRAM_IDS.contains(&account.owner)
+        && account.data.len() >= SplTokenAccount::LEN
+        && account
+            .data
+            .get(TOKEN_ACCOUNT_OWNER)
+            .is_some_and(|owner| signers.iter().any(|s| s.as_ref() == owner));
+
+    !signers_token_account
+}
+
+#[cfg(test)]
+mod tests {
+    use mock_node::MockNode;
+    use serde_json::Value;
+    use solana_sdk::{
+        message::{AddressLookupTableAccount, v0},
+        signature::Keypair,
+        signer::Signer,
+        transaction::VersionedTransaction,
+    };
+    use spl_associated_token_account_interface::{
+        address::get_associated_token_address,
+        instruction::create_associated_token_account_idempotent,
+    };
+
+    use super::*;
+    use crate::{
+        config::CommitmentConfig,
+        rpc::mock::{
+            MockTransport, mock_mint, mock_services, mock_token_account, mock_token_amount,
+        },
+        swapping::{router::SwapRouter, swap::swap, swap_provider::WSOL_MINT},
+    };
+
+    const POOL_LAMPORTS: u64 = 1_000_000_000;
+    const TAKEN_TOKENS: u64 = 500;
+
+    /// Pays the pool, found through a lookup table, and mints the taker the output like
+    /// the aggregator routes do. Builds against the cluster, not the paper ledger.
+    struct ClusterPool {
+        cluster: MockNode,
+        table: Pubkey,
+        pool: Pubkey,
+        mint: Pubkey,
+    }
+
+    #[async_trait]
+    impl SwapProvider for ClusterPool {
+        fn name(&self) -> &'static str {
+            "cluster_pool"
+        }
+
+        async fn quote(&self, order: &SwapOrder, _: Option<&Pubkey>) -> Result<SwapQuote, Error> {
+            Ok(SwapQuote {
+                provider: self.name(),
+                input_mint: order.input_mint.clone(),
+                output_mint: order.output_mint.clone(),
+                in_amount: order.amount,
+                out_amount: order.amount,
+                min_out_amount: order.amount,
+                slippage_bps: order.slippage_bps,
+                price_impact_pct: 0.0,
+                route: Value::Null,
+            })
+        }
+
+        async fn build_transaction(
+            &self,
+            quote: &SwapQuote,
+            taker: &Pubkey,
+        ) -> Result<SwapTransaction, Error> {
+            let instructions = vec![
+                solana_system_interface::instruction::transfer(taker, &self.pool, quote.in_amount),
+                create_associated_token_account_idempotent(
+                    taker,
+                    taker,
+                    &self.mint,
+                    &spl_token_interface::ID,
+                ),
+                spl_token_interface::instruction::mint_to(
+                    &spl_token_interface::ID,
+                    &self.mint,
+                    &get_associated_token_address(taker, &self.mint),
+                    taker,
+                    &[],
+                    quote.out_amount,
+                )
+                .expect("mint_to accepts the token program id"),
+            ];
+            let table = AddressLookupTableAccount {
+                key: self.table,
+                addresses: vec![self.pool],
+            };
+            let (blockhash, last_valid_block_height) = self.cluster.latest_blockhash();
+            let message = v0::Message::try_compile(taker, &instructions, &[table], blockhash)?;
+
+            Ok(SwapTransaction {
+                transaction: VersionedTransaction {
+                    signatures: vec![Default::default()],
+                    message: VersionedMessage::V0(message),
+                },
+                last_valid_block_height: Some(last_valid_block_height),
+            })
+        }
+    }
+
+    /// A lookup table holding `addresses`, last extended at a slot the paper ledger is far behind.
+    fn lookup_table(addresses: &[Pubkey]) -> Account {
+        let mut data = vec![0; LOOKUP_TABLE_META_SIZE];
+        data[..4].copy_from_slice(&1u32.to_le_bytes());
+        data[4..12].copy_from_slice(&u64::MAX.to_le_bytes());
+        data[LOOKUP_TABLE_LAST_EXTENDED_SLOT].copy_from_slice(&1_000_000u64.to_le_bytes());
+        data.extend(addresses.iter().flat_map(|a| a.to_bytes()));
+
+        Account {
+            lamports: 10_000_000,
+            data,
+            owner: ADDRESS_LOOKUP_TABLE_PROGRAM_ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    #[tokio::test]
+    async fn swaps_against_the_clusters_pool_without_touching_the_fleets_balances() {
+        let wallet = Keypair::new();
+        let cluster = MockNode::new();
+        let mint = mock_mint(&cluster, &wallet.pubkey(), 6);
+        let cluster_ata = mock_token_account(&cluster, &wallet.pubkey(), &mint, TAKEN_TOKENS);
+        let pool = Pubkey::new_unique();
+        cluster.airdrop(&pool, POOL_LAMPORTS).unwrap();
+        let table = Pubkey::new_unique();
+        cluster.set_account(table, lookup_table(&[pool]));
+
+        let node = MockNode::new();
+        node.airdrop(&wallet.pubkey(), 100_000_000).unwrap();
+        let (rpc, websocket, _) = mock_services(&node).await;
+        let paper = Paper {
+            node: node.clone(),
+            cluster: Some(Arc::new(MockTransport::new(cluster.clone()))),
+        };
+        let provider = Arc::new(ClusterPool {
+            cluster: cluster.clone(),
+            table,
+            pool,
+            mint,
+        });
+        let router = SwapRouter::new(vec![Arc::new(PaperSwaps::new(
+            provider,
+            paper,
+            Commitment::Processed,
+        ))]);
+        let order = SwapOrder {
+            input_mint: WSOL_MINT.to_owned(),
+            output_mint: mint.to_string(),
+            amount: 10_000,
+            slippage_bps: 50,
+            max_price_impact_pct: None,
+        };
+
+        let result = swap(
+            &rpc,
+            &router,
+            &order,
+            &wallet,
+            websocket,
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(result.fill.err.is_none());
+        assert_eq!(result.fill.out_amount, 10_000);
+        assert_eq!(node.balance(&pool), POOL_LAMPORTS + 10_000);
+        assert_eq!(mock_token_amount(&node, &cluster_ata), 10_000);
+        assert_eq!(cluster.balance(&pool), POOL_LAMPORTS);
+        assert_eq!(cluster.balance(&wallet.pubkey()), 0);
+        assert_eq!(mock_token_amount(&cluster, &cluster_ata), TAKEN_TOKENS);
+    }
+}
@@ -0,0 +1,469 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::Arc,
+};
+
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+use tokio::task::JoinSet;
+
+use crate::{
+    Blockhashes, Rpc, Websocket,
+    collecting::collecting::source_wallet_lamports,
+    config::CommitmentConfig,
+    errors::errors::Error,
+    rpc::send::send_and_confirm,
+    txn_factory::{
+        rent::{PlannedTransfers, check_rent_exemption},
+        simulation::{SimulationReport, simulate},
+        transfer::{batch_sol_transfers, build_sol_transfers},
+        util::LAMPORTS_PER_SIGNATURE,
+    },
+};
+
+/// Fees change who gives how much, which can change the fees again. Plans settle
+/// after a round or two.
+const MAX_PLANNING_ROUNDS: usize = 4;
+
+/// The balances a rebalance aims for.
+#[derive(Debug, Clone)]
+pub enum RebalanceTarget {
+    /// The wallets' lamports split evenly, after fees.
+    Equal,
+    /// Every wallet holds at least this much, taken from the wallets with the most to spare.
+    Minimum(u64),
+    /// The wallets' lamports split by weight after fees, one weight per wallet.
+    Weights(Vec<u64>),
+}
+
+impl RebalanceTarget {
+    /// Each wallet's target balance once `fees` are paid. `None` for a minimum, where
+    /// wallets above it keep whatever they don't give.
+    fn balances(&self, balances: &[u64], fees: u64) -> Option<Vec<u64>> {
+        let total = balances.iter().sum::<u64>().saturating_sub(fees);
+        let weights = match self {
+            RebalanceTarget::Minimum(_) => return None,
+            RebalanceTarget::Equal => vec![1; balances.len()],
+            RebalanceTarget::Weights(weights) => weights.clone(),
+        };
+
+        let weight_sum: u128 = weights.iter().map(|w| *w as u128).sum();
+        let mut targets: Vec<u64> = weights
+            .iter()
+            .map(|w| (total as u128 * *w as u128 / weight_sum.max(1)) as u64)
+            .collect();
+        // Rounding down leaves less than a lamport per wallet, handed out in order.
+        let mut remainder = total - targets.iter().sum::<u64>();
+        for (target, weight) in targets.iter_mut().zip(weights.iter()) {
+            if remainder == 0 {
+                break;
+            }
+            if *weight > 0 {
+                *target += 1;
+                remainder -= 1;
+            }
+        }
+
+        Some(targets)
+    }
+}
+
+/// One transaction of a rebalance, a wallet paying one or more others.
+#[derive(Debug, Clone)]
+pub struct RebalanceTransaction {
+    pub from: Pubkey,
+    pub transfers: Vec<(Pubkey, u64)>,
+}
+
+/// A rebalance transaction and how it went.
+pub struct RebalanceBatch<T> {
+    pub transaction: RebalanceTransaction,
+    pub result: Result<T, Error>,
+}
+
+/// Matches what wallets can give with what others lack, largest first, so every step
+/// settles a giver or a receiver and there are fewer transfers than wallets. Wallets
+/// short by at most `tolerance` are left alone. `fees` is what each giver pays, by index.
+fn match_transfers(
+    balances: &[u64],
+    target: &RebalanceTarget,
+    fees: &HashMap<usize, u64>,
+    tolerance: u64,
+) -> Result<Vec<(usize, usize, u64)>, Error> {
+    let fee = |i: usize| fees.get(&i).copied().unwrap_or(LAMPORTS_PER_SIGNATURE);
+    let targets = target.balances(balances, fees.values().sum());
+
+    let mut givers = Vec::new();
+    let mut receivers = Vec::new();
+    for (i, balance) in balances.iter().enumerate() {
+        let floor = match (&targets, target) {
+            (Some(targets), _) => targets[i],
+            (None, RebalanceTarget::Minimum(minimum)) => *minimum,
+            (None, _) => unreachable!("only minimums have no fixed targets"),
+        };
+        if *balance < floor {
+            if floor - balance > tolerance {
+                receivers.push((i, floor - balance));
+            }
+        } else if balance - floor > fee(i) {
+            givers.push((i, balance - floor - fee(i)));
+        }
+    }
+    givers.sort_by_key(|(_, give)| std::cmp::Reverse(*give));
+    receivers.sort_by_key(|(_, need)| std::cmp::Reverse(*need));
+
+    if let RebalanceTarget::Minimum(minimum) = target {
+        let needed: u128 = receivers.iter().map(|(_, n)| *n as u128).sum();
+        let spare: u128 = givers.iter().map(|(_, g)| *g as u128).sum();
+        if needed > spare {
+            return Err(Error::InsufficientSol(format!(
+                "Need {} lamports to bring every wallet to {} but the others only spare {}.",
+                needed, minimum, spare
+            )));
+        }
+    }
+
+    let mut transfers = Vec::new();
+    let (mut g, mut r) = (0, 0);
+    while g < givers.len() && r < receivers.len() {
+        let lamports = givers[g].1.min(receivers[r].1);
+        transfers.push((givers[g].0, receivers[r].0, lamports));
+        givers[g].1 -= lamports;
+        receivers[r].1 -= lamports;
+        if givers[g].1 == 0 {
+            g += 1;
+        }
+        if receivers[r].1 == 0 {
+            r += 1;
+        }
+    }
+
+    Ok(transfers)
+}
+
+/// Works out the wallet-to-wallet transactions reaching `target`. Every giver pays the
+/// fees of its own transactions out of what it would otherwise give, unless a
+/// `fee_payer` sponsors them all.
+pub async fn plan_rebalance(
+    rpc: &Rpc,
+    wallets: &[Keypair],
+    fee_payer: Option<&Pubkey>,
+    target: &RebalanceTarget,
+    tolerance: u64,
+    commitment: CommitmentConfig,
+) -> Result<Vec<RebalanceTransaction>, Error> {
+    let balances: Vec<u64> = source_wallet_lamports(rpc, wallets, commitment)
+        .await?
+        .into_iter()
+        .map(|(_, lamports)| lamports)
+        .collect();
+
+    let mut fees: HashMap<usize, u64> = HashMap::new();
+    let mut plan = Vec::new();
+    for _ in 0..MAX_PLANNING_ROUNDS {
+        let transfers = match_transfers(&balances, target, &fees, tolerance)?;
+
+        let mut by_giver: BTreeMap<usize, Vec<(Pubkey, u64)>> = BTreeMap::new();
+        for (giver, receiver, lamports) in transfers {
+            by_giver
+                .entry(giver)
+                .or_default()
+                .push((wallets[receiver].pubkey(), lamports));
+        }
+
+        plan.clear();
+        let mut round_fees = HashMap::new();
+        for (giver, transfers) in by_giver {
+            let from = wallets[giver].pubkey();
+            let batches = batch_sol_transfers(&from, fee_payer, &transfers);
+            let fees = match fee_payer {
+                Some(_) => 0,
+                None => LAMPORTS_PER_SIGNATURE * batches.len() as u64,
+            };
+            round_fees.insert(giver, fees);
+            plan.extend(
+                batches
+                    .into_iter()
+                    .map(|transfers| RebalanceTransaction { from, transfers }),
+            );
+        }
+
+        if round_fees == fees {
+            break;
+        }
+        fees = round_fees;
+    }
+
+    let planned: Vec<PlannedTransfers> = plan
+        .iter()
+        .map(|t| PlannedTransfers {
+            payer: t.from,
+            fee_payer: fee_payer.copied(),
+            transfers: &t.transfers,
+        })
+        .collect();
+    check_rent_exemption(rpc, &planned, &[], commitment).await?;
+
+    Ok(plan)
+}
+
+/// Sends every transaction of the plan, givers in parallel, and waits for the confirmations.
+pub async fn rebalance(
+    rpc: &Rpc,
+    websocket_service: Websocket,
+    blockhashes: Blockhashes,
+    wallets: &[Keypair],
+    fee_payer: Option<&Keypair>,
+    plan: Vec<RebalanceTransaction>,
+    commitment: CommitmentConfig,
+) -> Vec<RebalanceBatch<String>> {
+    let fee_payer = fee_payer.map(|f| Arc::new(f.insecure_clone()));
+    let mut send_set = JoinSet::new();
+    for transaction in plan {
+        let wallet = wallets
+            .iter()
+            .find(|w| w.pubkey() == transaction.from)
+            .expect("every giver of a rebalance is one of its wallets")
+            .insecure_clone();
+        let rpc = Arc::clone(rpc);
+        let websocket_service = websocket_service.clone();
+        let blockhashes = Arc::clone(&blockhashes);
+        let fee_payer = fee_payer.clone();
+
+        send_set.spawn(async move {
+            let result = async {
+                let blockhash = blockhashes.get().await?;
+                let (latest_hash, last_valid_block_height) =
+                    (blockhash.hash, blockhash.last_valid_block_height);
+                let txn = build_sol_transfers(
+                    &wallet,
+                    fee_payer.as_deref(),
+                    &transaction.transfers,
+                    &latest_hash,
+                )
+                .await?;
+
+                let txn_hash = send_and_confirm(
+                    &rpc,
+                    websocket_service,
+                    "rebalance",
+                    &txn,
+                    last_valid_block_height,
+                    commitment,
+                )
+                .await?;
+                println!("Rebalanced: {}", txn_hash);
+
+                Ok(txn_hash)
+            }
+            .await;
+
+            RebalanceBatch {
+                transaction,
+                result,
+            }
+        });
+    }
+
+    let mut results = Vec::new();
+    while let Some(res) = send_set.join_next().await {
+        match res {
+            Ok(batch) => results.push(batch),
+            Err(err) => eprintln!("Join error: {:?}", err),
+        }
+    }
+
+    results
+}
+
+/// Builds the same transactions as `rebalance` and simulates them instead of sending.
+pub async fn dry_run_rebalance(
+    rpc: &Rpc,
+    blockhashes: Blockhashes,
+    wallets: &[Keypair],
+    fee_payer: Option<&Keypair>,
+    plan: Vec<RebalanceTransaction>,
+    commitment: CommitmentConfig,
+) -> Result<Vec<RebalanceBatch<SimulationReport>>, Error> {
+    let latest_hash = blockhashes.get().await?.hash;
+
+    let mut results = Vec::new();
+    for transaction in plan {
+        let result = async {
+            let wallet = wallets
+                .iter()
+                .find(|w| w.pubkey() == transaction.from)
+                .expect("every giver of a rebalance is one of its wallets");
+            let txn = build_sol_transfers(wallet, fee_payer, &transaction.transfers, &latest_hash)
+                .await?;
+            simulate(rpc, "rebalance_dry_run", &txn, commitment).await
+        }
+        .await;
+
+        results.push(RebalanceBatch {
+            transaction,
+            result,
+        });
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use mock_node::MockNode;
+
+    use super::*;
+    use crate::rpc::mock::mock_services;
+
+    fn fees(paid: &[(usize, u64)]) -> HashMap<usize, u64> {
+        paid.iter().copied().collect()
+    }
+
+    #[test]
+    fn equal_targets_hand_out_the_rounding_remainder_in_order() {
+        let targets = RebalanceTarget::Equal.balances(&[10, 0, 0], 0).unwrap();
+
+        assert_eq!(targets, vec![4, 3, 3]);
+    }
+
+    #[test]
+    fn equal_split_comes_from_the_richest_wallet_after_its_fee() {
+        let balances = [4_005_000, 0, 1_000_000, 1_000_000];
+
+        let transfers = match_transfers(
+            &balances,
+            &RebalanceTarget::Equal,
+            &fees(&[(0, LAMPORTS_PER_SIGNATURE)]),
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(
+            transfers,
+            vec![(0, 1, 1_500_000), (0, 2, 500_000), (0, 3, 500_000)]
+        );
+    }
+
+    #[test]
+    fn small_shortfalls_within_the_tolerance_are_left_alone() {
+        let balances = [1_010_000, 1_000_000];
+
+        let transfers = match_transfers(
+            &balances,
+            &RebalanceTarget::Equal,
+            &fees(&[(0, LAMPORTS_PER_SIGNATURE)]),
+            5_000,
+        )
+        .unwrap();
+
+        assert!(transfers.is_empty());
+    }
+
+    #[test]
+    fn minimum_only_tops_up_wallets_below_it() {
+        let balances = [3_000_000, 100_000, 2_000_000];
+
+        let transfers = match_transfers(
+            &balances,
+            &RebalanceTarget::Minimum(1_000_000),
+            &HashMap::new(),
+            0,
+        )
+        .unwrap();
+
+        assert_eq!(
+            RebalanceTarget::Minimum(1_000_000).balances(&balances, 0),
+            None
+        );
+        assert_eq!(transfers, vec![(0, 1, 900_000)]);
+    }
+
+    #[test]
+    fn minimum_fails_when_the_others_cannot_spare_enough() {
+        let result = match_transfers(
+            &[1_000_000, 0],
+            &RebalanceTarget::Minimum(1_000_000),
+            &HashMap::new(),
+            0,
+        );
+
+        assert!(matches!(result, Err(Error::InsufficientSol(_))));
+    }
+
+    #[test]
+    fn weights_split_what_is_left_after_fees() {
+        let target = RebalanceTarget::Weights(vec![2, 1]);
+        let balances = [0, 9_000_000];
+
+        let targets = target.balances(&balances, LAMPORTS_PER_SIGNATURE).unwrap();
+        let transfers =
+            match_transfers(&balances, &target, &fees(&[(1, LAMPORTS_PER_SIGNATURE)]), 0).unwrap();
+
+        assert_eq!(targets, vec![5_996_667, 2_998_333]);
+        assert_eq!(transfers, vec![(1, 0, 5_996_667)]);
+    }
+
+    #[test]
+    fn zero_weight_wallets_give_everything_but_their_fee() {
+        let target = RebalanceTarget::Weights(vec![1, 0]);
+        let balances = [0, 1_000_000];
+
+        let transfers =
+            match_transfers(&balances, &target, &fees(&[(1, LAMPORTS_PER_SIGNATURE)]), 0).unwrap();
+
+        assert_eq!(transfers, vec![(1, 0, 995_000)]);
+    }
+
+    #[test]
+    fn all_zero_weights_move_nothing() {
+        let target = RebalanceTarget::Weights(vec![0, 0]);
+        let balances = [1_000_000, 2_000_000];
+
+        let transfers = match_transfers(&balances, &target, &HashMap::new(), 0).unwrap();
+
+        assert_eq!(target.balances(&balances, 0), Some(vec![0, 0]));
+        assert!(transfers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn evens_out_the_wallets() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallets: Vec<Keypair> = (0..4).map(|_| Keypair::new()).collect();
+        for (wallet, lamports) in wallets
+            .iter()
+            .zip([10_000_000, 1_000_000, 2_000_000, 3_000_000])
+        {
+            node.airdrop(&wallet.pubkey(), lamports).unwrap();
+        }
+
+        let plan = plan_rebalance(
+            &rpc,
+            &wallets,
+            None,
+            &RebalanceTarget::Equal,
+            0,
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
+        let fees = plan.len() as u64 * LAMPORTS_PER_SIGNATURE;
+        let batches = rebalance(
+            &rpc,
+            websocket,
+            blockhashes,
+            &wallets,
+            None,
+            plan,
+            CommitmentConfig::default(),
+        )
+        .await;
+
+        assert!(batches.iter().all(|b| b.result.is_ok()));
+        let total = 16_000_000 - fees;
+        for wallet in &wallets {
+            let balance = node.balance(&wallet.pubkey());
+            assert!(balance.abs_diff(total / 4) <= 1, "{} is uneven", balance);
+        }
+    }
+}
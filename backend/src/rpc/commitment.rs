@@ -0,0 +1,26 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// How settled the state a node answers from, or waits for, has to be. Ordered
+/// from least to most settled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Commitment {
+    Processed,
+    Confirmed,
+    Finalized,
+}
+
+impl FromStr for Commitment {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "processed" => Ok(Commitment::Processed),
+            "confirmed" => Ok(Commitment::Confirmed),
+            "finalized" => Ok(Commitment::Finalized),
+            other => Err(format!("unknown commitment {}", other)),
+        }
+    }
+}
@@ -1,11 +1,10 @@
-use reqwest::{Client, header};
 use serde::de::DeserializeOwned;
 use serde_json::{Value, json};
 
-use crate::errors::errors::Error;
+use crate::{Rpc, errors::errors::Error};
 
 pub async fn make_rpc_request<T>(
-    rpc_url: &str,
+    rpc: &Rpc,
     request_id: &str,
     rpc_method: &str,
     params: Value,
@@ -13,9 +12,6 @@ pub async fn make_rpc_request<T>(
 where
     T: DeserializeOwned,
 {
-    let mut headers = header::HeaderMap::new();
-    headers.insert("Content-Type", "application/json".parse().unwrap());
-
     let body = json!({
         "jsonrpc": "2.0",
         "id": request_id,
@@ -23,24 +19,14 @@ where
         "params": params
     });
 
-    let client = Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .build()?;
-
-    let resp = client
-        .post(rpc_url)
-        .headers(headers)
-        .body(body.to_string())
-        .send()
-        .await?;
+    let text = rpc.send_request(&body).await?;
 
-    let status = resp.status();
-    let text = resp.text().await?;
-    if !status.is_success() {
-        return Err(Error::RpcError(format!("http {}: {}", status, text)));
+    let response: Value = serde_json::from_str(&text)?;
+    if let Some(err) = response.get("error") {
+        return Err(Error::RpcError(format!("{} failed: {}", rpc_method, err)));
     }
 
-    let parsed: T = serde_json::from_str(&text)?;
+    let parsed: T = serde_json::from_value(response)?;
 
     Ok(parsed)
 }
@@ -0,0 +1,137 @@
+use async_trait::async_trait;
+use mock_node::MockNode;
+use serde_json::Value;
+
+use crate::{
+    errors::errors::Error,
+    rpc::transport::{RpcTransport, SubscriptionChannel},
+};
+
+/// Serves every RPC call from an in-process `MockNode`, no network involved.
+pub struct MockTransport {
+    node: MockNode,
+}
+
+impl MockTransport {
+    pub fn new(node: MockNode) -> Self {
+        Self { node }
+    }
+}
+
+#[async_trait]
+impl RpcTransport for MockTransport {
+    async fn send_request(&self, body: &Value) -> Result<String, Error> {
+        Ok(self.node.handle_request(body).to_string())
+    }
+
+    async fn open_subscription(&self) -> Result<SubscriptionChannel, Error> {
+        let (sender, receiver) = self.node.connect();
+
+        Ok(SubscriptionChannel { sender, receiver })
+    }
+}
+
+/// Services running against a fresh mock node, for tests driving whole jobs.
+#[cfg(test)]
+pub async fn mock_services(node: &MockNode) -> (crate::Rpc, crate::Websocket, crate::Blockhashes) {
+    use std::sync::Arc;
+
+    use tokio::sync::RwLock;
+
+    use crate::{
+        rpc::commitment::Commitment, txn_factory::blockhash::BlockhashCache,
+        websocket::solana_websocket::SolanaWebsocket,
+    };
+
+    let rpc: crate::Rpc = Arc::new(MockTransport::new(node.clone()));
+    let websocket = SolanaWebsocket::new(Arc::clone(&rpc)).await;
+    let blockhashes = BlockhashCache::spawn(Arc::clone(&rpc), Commitment::Processed);
+
+    (rpc, Arc::new(RwLock::new(Box::new(websocket))), blockhashes)
+}
+
+/// Creates a Token program mint on the node, with `mint_authority` allowed to mint more.
+#[cfg(test)]
+pub fn mock_mint(
+    node: &MockNode,
+    mint_authority: &solana_sdk::pubkey::Pubkey,
+    decimals: u8,
+) -> solana_sdk::pubkey::Pubkey {
+    use solana_sdk::{program_pack::Pack, pubkey::Pubkey, rent::Rent};
+    use spl_token_interface::state::Mint;
+
+    let mint = Pubkey::new_unique();
+    let mut data = vec![0; Mint::LEN];
+    Mint {
+        mint_authority: Some(*mint_authority).into(),
+        decimals,
+        is_initialized: true,
+        ..Mint::default()
+    }
+    .pack_into_slice(&mut data);
+    node.set_account(
+        mint,
+        solana_sdk::account::Account {
+            lamports: Rent::default().minimum_balance(Mint::LEN),
+            data,
+            owner: spl_token_interface::ID,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+
+    mint
+}
+
+/// Gives `owner` an associated token account of `mint` holding `amount`, adding it to the supply.
+#[cfg(test)]
+pub fn mock_token_account(
+    node: &MockNode,
+    owner: &solana_sdk::pubkey::Pubkey,
+    mint: &solana_sdk::pubkey::Pubkey,
+    amount: u64,
+) -> solana_sdk::pubkey::Pubkey {
+    use solana_sdk::{program_pack::Pack, rent::Rent};
+    use spl_associated_token_account_interface::address::get_associated_token_address;
+    use spl_token_interface::state::{Account as SplTokenAccount, AccountState, Mint};
+
+    let mut mint_account = node.account(mint).unwrap();
+    let mut mint_state = Mint::unpack(&mint_account.data).unwrap();
+    mint_state.supply += amount;
+    mint_state.pack_into_slice(&mut mint_account.data);
+    node.set_account(*mint, mint_account);
+
+    let token_account = get_associated_token_address(owner, mint);
+    let mut data = vec![0; SplTokenAccount::LEN];
+    SplTokenAccount {
+        mint: *mint,
+        owner: *owner,
+        amount,
+        state: AccountState::Initialized,
+        ..SplTokenAccount::default()
+    }
+    .pack_into_slice(&mut data);
+    node.set_account(
+        token_account,
+        solana_sdk::account::Account {
+            lamports: Rent::default().minimum_balance(SplTokenAccount::LEN),
+            data,
+            owner: spl_token_interface::ID,
+            executable: false,
+            rent_epoch: 0,
+        },
+    );
+
+    token_account
+}
+
+/// The amount held by a token account on the node, 0 if it doesn't exist.
+#[cfg(test)]
+pub fn mock_token_amount(node: &MockNode, token_account: &solana_sdk::pubkey::Pubkey) -> u64 {
+    use solana_sdk::program_pack::Pack;
+    use spl_token_interface::state::Account as SplTokenAccount;
+
+    node.account(token_account)
+        .map(|a| SplTokenAccount::unpack(&a.data).unwrap().amount)
+        .unwrap_or(0)
+}
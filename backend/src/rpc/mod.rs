@@ -1,3 +1,7 @@
+pub mod commitment;
 mod core;
+#[cfg(any(test, feature = "mock"))]
+pub mod mock;
 pub mod read;
 pub mod send;
+pub mod transport;
@@ -1,7 +1,13 @@
-use serde::Deserialize;
-use serde_json::json;
+use base64::{Engine, engine::general_purpose};
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use solana_system_interface::program as system_program;
 
-use crate::{errors::errors::Error, rpc::core::make_rpc_request};
+use crate::{
+    Rpc,
+    errors::errors::Error,
+    rpc::{commitment::Commitment, core::make_rpc_request},
+};
 
 #[derive(Debug, Deserialize)]
 struct RpcBalanceResponse {
@@ -24,12 +30,18 @@ struct RpcContext {
 }
 
 pub async fn get_balance(
-    rpc_url: &str,
+    rpc: &Rpc,
     request_id: &str,
     account_pubkey: &str,
+    commitment: Commitment,
 ) -> Result<u64, Error> {
-    let rpc_result: RpcBalanceResponse =
-        make_rpc_request(rpc_url, request_id, "getBalance", json!([account_pubkey])).await?;
+    let rpc_result: RpcBalanceResponse = make_rpc_request(
+        rpc,
+        request_id,
+        "getBalance",
+        json!([account_pubkey, { "commitment": commitment }]),
+    )
+    .await?;
 
     Ok(rpc_result.result.value)
 }
@@ -51,18 +63,76 @@ struct RpcLatestBlockhashResult {
 pub struct RpcLatestBlockhash {
     pub blockhash: String,
     #[serde(rename = "lastValidBlockHeight")]
-    pub last_valid_block_height: u32,
+    pub last_valid_block_height: u64,
 }
 
 pub async fn get_latest_blockhash(
-    rpc_url: &str,
+    rpc: &Rpc,
     request_id: &str,
+    commitment: Commitment,
 ) -> Result<RpcLatestBlockhash, Error> {
     let rpc_result: RpcLatestBlockhashResponse = make_rpc_request(
-        rpc_url,
+        rpc,
         request_id,
         "getLatestBlockhash",
-        json!([{"commitment": "processed", "minContextSlot": 1000}]),
+        json!([{ "commitment": commitment }]),
+    )
+    .await?;
+
+    Ok(rpc_result.result.value)
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcBlockHeightResponse {
+    result: u64,
+}
+
+pub async fn get_block_height(
+    rpc: &Rpc,
+    request_id: &str,
+    commitment: Commitment,
+) -> Result<u64, Error> {
+    let rpc_result: RpcBlockHeightResponse = make_rpc_request(
+        rpc,
+        request_id,
+        "getBlockHeight",
+        json!([{ "commitment": commitment }]),
+    )
+    .await?;
+
+    Ok(rpc_result.result)
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcSignatureStatusesResponse {
+    result: RpcSignatureStatusesResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcSignatureStatusesResult {
+    value: Vec<Option<RpcSignatureStatus>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcSignatureStatus {
+    /// Set when the transaction landed but failed.
+    pub err: Option<Value>,
+    #[serde(rename = "confirmationStatus")]
+    pub confirmation_status: Option<Commitment>,
+}
+
+/// Statuses of the signatures in the same order, `None` for ones the node has never
+/// seen. Searches the full history, not only recent blocks.
+pub async fn get_signature_statuses(
+    rpc: &Rpc,
+    request_id: &str,
+    signatures: &[String],
+) -> Result<Vec<Option<RpcSignatureStatus>>, Error> {
+    let rpc_result: RpcSignatureStatusesResponse = make_rpc_request(
+        rpc,
+        request_id,
+        "getSignatureStatuses",
+        json!([signatures, { "searchTransactionHistory": true }]),
     )
     .await?;
 
@@ -77,15 +147,16 @@ pub struct RpcMinimumBalanceForRentExemptionResponse {
 }
 
 pub async fn get_minimum_balance_for_rent_exemption(
-    rpc_url: &str,
+    rpc: &Rpc,
     request_id: &str,
     bytes: u128,
+    commitment: Commitment,
 ) -> Result<u128, Error> {
     let rpc_result: RpcMinimumBalanceForRentExemptionResponse = make_rpc_request(
-        rpc_url,
+        rpc,
         request_id,
         "getMinimumBalanceForRentExemption",
-        json!([bytes]),
+        json!([bytes, { "commitment": commitment }]),
     )
     .await?;
 
@@ -108,15 +179,248 @@ pub struct RpcMultipleAccountsResult {
 #[derive(Debug, Deserialize)]
 pub struct RpcAccount {
     pub lamports: u64,
+    pub owner: String,
+    pub executable: bool,
+    #[serde(rename = "rentEpoch")]
+    pub rent_epoch: u64,
+    /// Length of the account data, independent of whether the data itself was requested.
+    pub space: u64,
+    /// `[data, encoding]`, the data is empty unless it was requested.
+    pub data: (String, AccountEncoding),
+}
+
+impl RpcAccount {
+    /// True for accounts owned by the system program that hold no data, i.e. what
+    /// every fleet wallet should look like. Anything else was taken over by a program.
+    pub fn is_plain_system_account(&self) -> bool {
+        self.owner == system_program::ID.to_string() && !self.executable && self.space == 0
+    }
+
+    pub fn decoded_data(&self) -> Result<Vec<u8>, Error> {
+        let (data, encoding) = &self.data;
+        let bytes = match encoding {
+            AccountEncoding::Base58 => bs58::decode(data).into_vec()?,
+            AccountEncoding::Base64 => general_purpose::STANDARD.decode(data)?,
+        };
+
+        Ok(bytes)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum AccountEncoding {
+    Base58,
+    Base64,
 }
 
+impl AccountEncoding {
+    pub fn encode(&self, data: &[u8]) -> String {
+        match self {
+            AccountEncoding::Base58 => bs58::encode(data).into_string(),
+            AccountEncoding::Base64 => general_purpose::STANDARD.encode(data),
+        }
+    }
+}
+
+/// `getMultipleAccounts` takes at most this many accounts.
+pub const MAX_ACCOUNTS_PER_REQUEST: usize = 100;
+
+/// Fetches the accounts in one request. The data is only transferred when `data_encoding`
+/// is set, otherwise the node is asked for an empty slice and only `space` reports its length.
 pub async fn get_multiple_accounts(
-    rpc_url: &str,
+    rpc: &Rpc,
     request_id: &str,
     pubkeys: &Vec<String>,
+    data_encoding: Option<AccountEncoding>,
+    commitment: Commitment,
 ) -> Result<Vec<Option<RpcAccount>>, Error> {
-    let rpc_result: RpcMultipleAccountsResponse =
-        make_rpc_request(rpc_url, request_id, "getMultipleAccounts", json!([pubkeys])).await?;
+    let config = match data_encoding {
+        Some(encoding) => json!({ "encoding": encoding, "commitment": commitment }),
+        None => json!({
+            "encoding": AccountEncoding::Base64,
+            "commitment": commitment,
+            "dataSlice": { "offset": 0, "length": 0 }
+        }),
+    };
+
+    let rpc_result: RpcMultipleAccountsResponse = make_rpc_request(
+        rpc,
+        request_id,
+        "getMultipleAccounts",
+        json!([pubkeys, config]),
+    )
+    .await?;
+
+    Ok(rpc_result.result.value)
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcFeeForMessageResponse {
+    result: RpcFeeForMessageResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcFeeForMessageResult {
+    value: Option<u64>,
+}
+
+/// Returns `None` when the message's blockhash has expired.
+pub async fn get_fee_for_message(
+    rpc: &Rpc,
+    request_id: &str,
+    message: &str,
+    commitment: Commitment,
+) -> Result<Option<u64>, Error> {
+    let rpc_result: RpcFeeForMessageResponse = make_rpc_request(
+        rpc,
+        request_id,
+        "getFeeForMessage",
+        json!([message, { "commitment": commitment }]),
+    )
+    .await?;
+
+    Ok(rpc_result.result.value)
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcTransactionResponse {
+    result: Option<RpcConfirmedTransaction>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcConfirmedTransaction {
+    pub slot: u64,
+    #[serde(rename = "blockTime")]
+    pub block_time: Option<i64>,
+    pub transaction: RpcTransaction,
+    pub meta: Option<RpcTransactionMeta>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcTransaction {
+    pub message: RpcTransactionMessage,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcTransactionMessage {
+    #[serde(rename = "accountKeys")]
+    pub account_keys: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTransactionMeta {
+    pub err: Option<serde_json::Value>,
+    pub fee: u64,
+    pub pre_balances: Vec<u64>,
+    pub post_balances: Vec<u64>,
+    #[serde(default)]
+    pub pre_token_balances: Vec<RpcTokenBalance>,
+    #[serde(default)]
+    pub post_token_balances: Vec<RpcTokenBalance>,
+    pub loaded_addresses: Option<RpcLoadedAddresses>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcLoadedAddresses {
+    pub writable: Vec<String>,
+    pub readonly: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RpcTokenBalance {
+    pub account_index: usize,
+    pub mint: String,
+    pub owner: Option<String>,
+    pub ui_token_amount: RpcTokenAmount,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcTokenAmount {
+    pub amount: String,
+    pub decimals: u8,
+}
+
+impl RpcConfirmedTransaction {
+    /// Every account key in the order the balances are listed in,
+    /// the static keys followed by the ones loaded from lookup tables.
+    pub fn account_keys(&self) -> Vec<&str> {
+        let mut keys: Vec<&str> = self
+            .transaction
+            .message
+            .account_keys
+            .iter()
+            .map(String::as_str)
+            .collect();
+        if let Some(loaded) = self.meta.as_ref().and_then(|m| m.loaded_addresses.as_ref()) {
+            keys.extend(loaded.writable.iter().map(String::as_str));
+            keys.extend(loaded.readonly.iter().map(String::as_str));
+        }
+
+        keys
+    }
+}
+
+/// Returns `None` while the node doesn't know the transaction (yet).
+/// Nodes only serve transactions at `confirmed` or `finalized`.
+pub async fn get_transaction(
+    rpc: &Rpc,
+    request_id: &str,
+    signature: &str,
+    commitment: Commitment,
+) -> Result<Option<RpcConfirmedTransaction>, Error> {
+    let rpc_result: RpcTransactionResponse = make_rpc_request(
+        rpc,
+        request_id,
+        "getTransaction",
+        json!([signature, {
+            "encoding": "json",
+            "commitment": commitment,
+            "maxSupportedTransactionVersion": 0
+        }]),
+    )
+    .await?;
+
+    Ok(rpc_result.result)
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcTokenAccountsResponse {
+    result: RpcTokenAccountsResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcTokenAccountsResult {
+    value: Vec<RpcKeyedAccount>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcKeyedAccount {
+    pub pubkey: String,
+    pub account: RpcAccount,
+}
+
+/// Token accounts of `owner` under the given token program, with base64 data.
+pub async fn get_token_accounts_by_owner(
+    rpc: &Rpc,
+    request_id: &str,
+    owner: &str,
+    program_id: &str,
+    commitment: Commitment,
+) -> Result<Vec<RpcKeyedAccount>, Error> {
+    let rpc_result: RpcTokenAccountsResponse = make_rpc_request(
+        rpc,
+        request_id,
+        "getTokenAccountsByOwner",
+        json!([
+            owner,
+            { "programId": program_id },
+            { "encoding": AccountEncoding::Base64, "commitment": commitment }
+        ]),
+    )
+    .await?;
 
     Ok(rpc_result.result.value)
 }
@@ -1,7 +1,18 @@
 use serde::Deserialize;
-use serde_json::json;
+use serde_json::{Value, json};
+use tokio::time::timeout;
 
-use crate::{errors::errors::Error, rpc::core::make_rpc_request};
+use crate::{
+    Rpc, Websocket,
+    config::CommitmentConfig,
+    errors::errors::Error,
+    rpc::{
+        commitment::Commitment,
+        core::make_rpc_request,
+        read::{RpcAccount, get_block_height, get_signature_statuses},
+    },
+    txn_factory::{blockhash::SLOT_DURATION, util::SimpleTransaction},
+};
 
 #[derive(Debug, Deserialize)]
 struct RpcSendTransactionResponse {
@@ -11,18 +22,19 @@ struct RpcSendTransactionResponse {
 }
 
 pub async fn send_transaction(
-    rpc_url: &str,
+    rpc: &Rpc,
     request_id: &str,
     transaction: &str,
+    preflight_commitment: Commitment,
 ) -> Result<String, Error> {
     let rpc_result: RpcSendTransactionResponse = make_rpc_request(
-        rpc_url,
+        rpc,
         request_id,
         "sendTransaction",
         json!([transaction,
         {
             "encoding": "base58",
-            "preflightCommitment": "processed",
+            "preflightCommitment": preflight_commitment,
             "skipPreflight": true,
             "maxRetries": 3
         }
@@ -32,3 +44,192 @@ pub async fn send_transaction(
 
     Ok(rpc_result.result)
 }
+
+/// Sends the transaction and waits until it reached `commitment.confirmation`, or its
+/// blockhash expired at `last_valid_block_height`. A transaction that landed but failed
+/// is an error.
+pub async fn send_and_confirm(
+    rpc: &Rpc,
+    websocket_service: Websocket,
+    request_id: &str,
+    txn: &SimpleTransaction,
+    last_valid_block_height: u64,
+    commitment: CommitmentConfig,
+) -> Result<String, Error> {
+    let signature = &txn.signature;
+    let mut confirmation = websocket_service
+        .read()
+        .await
+        .subscribe_signature(signature, commitment.confirmation)
+        .await?;
+
+    let txn_hash =
+        match send_transaction(rpc, request_id, &txn.transaction, commitment.preflight).await {
+            Ok(txn_hash) => txn_hash,
+            Err(err) => {
+                let websocket = websocket_service.read().await;
+                websocket.unsubscribe_signature(signature).await;
+                return Err(err);
+            }
+        };
+
+    loop {
+        let block_height = get_block_height(rpc, request_id, commitment.confirmation).await?;
+        if block_height > last_valid_block_height {
+            break;
+        }
+        let blocks_left = (last_valid_block_height - block_height + 1) as u32;
+        match timeout(SLOT_DURATION * blocks_left, &mut confirmation).await {
+            Ok(Ok(result)) => {
+                return result
+                    .map(|_| txn_hash)
+                    .map_err(|err| failed(signature, err));
+            }
+            // The connection dropped, the status below tells what happened.
+            Ok(Err(_)) => break,
+            Err(_) => continue,
+        }
+    }
+
+    let websocket = websocket_service.read().await;
+    websocket.unsubscribe_signature(signature).await;
+    drop(websocket);
+
+    let statuses = get_signature_statuses(rpc, request_id, std::slice::from_ref(signature)).await?;
+    match statuses.into_iter().next().flatten() {
+        Some(status) if status.err.is_some() => Err(failed(
+            signature,
+            status.err.map(|err| err.to_string()).unwrap_or_default(),
+        )),
+        Some(status)
+            if status
+                .confirmation_status
+                .is_some_and(|reached| reached >= commitment.confirmation) =>
+        {
+            Ok(txn_hash)
+        }
+        _ => Err(Error::ConfirmationTimeout(format!(
+            "Transaction {} was not confirmed before its blockhash expired",
+            signature
+        ))),
+    }
+}
+
+fn failed(signature: &str, err: String) -> Error {
+    Error::TransactionFailed(format!("Transaction {} failed: {}", signature, err))
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcRequestAirdropResponse {
+    result: String,
+}
+
+pub async fn request_airdrop(
+    rpc: &Rpc,
+    request_id: &str,
+    account_pubkey: &str,
+    lamports: u64,
+    commitment: Commitment,
+) -> Result<String, Error> {
+    let rpc_result: RpcRequestAirdropResponse = make_rpc_request(
+        rpc,
+        request_id,
+        "requestAirdrop",
+        json!([account_pubkey, lamports, { "commitment": commitment }]),
+    )
+    .await?;
+
+    Ok(rpc_result.result)
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcSimulateTransactionResponse {
+    result: RpcSimulateTransactionResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct RpcSimulateTransactionResult {
+    value: RpcSimulatedTransaction,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RpcSimulatedTransaction {
+    pub err: Option<Value>,
+    pub logs: Option<Vec<String>>,
+    /// State of `account_pubkeys` after the transaction, in the same order.
+    pub accounts: Option<Vec<Option<RpcAccount>>>,
+    #[serde(rename = "unitsConsumed")]
+    pub units_consumed: Option<u64>,
+}
+
+pub async fn simulate_transaction(
+    rpc: &Rpc,
+    request_id: &str,
+    transaction: &str,
+    account_pubkeys: &Vec<String>,
+    commitment: Commitment,
+) -> Result<RpcSimulatedTransaction, Error> {
+    let rpc_result: RpcSimulateTransactionResponse = make_rpc_request(
+        rpc,
+        request_id,
+        "simulateTransaction",
+        json!([transaction,
+        {
+            "encoding": "base58",
+            "commitment": commitment,
+            "sigVerify": true,
+            "accounts": {
+                "encoding": "base64",
+                "addresses": account_pubkeys
+            }
+        }
+        ]),
+    )
+    .await?;
+
+    Ok(rpc_result.result.value)
+}
+
+#[cfg(test)]
+mod tests {
+    use mock_node::{LAMPORTS_PER_SIGNATURE, MockNode};
+    use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+
+    use super::*;
+    use crate::{rpc::mock::mock_services, txn_factory::transfer::build_sol_transfer};
+
+    #[tokio::test]
+    async fn reports_transactions_that_failed_on_chain() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallet = Keypair::new();
+        node.airdrop(&wallet.pubkey(), 1_000_000).unwrap();
+
+        let blockhash = blockhashes.get().await.unwrap();
+        let txn = build_sol_transfer(
+            &wallet,
+            None,
+            2_000_000,
+            &Pubkey::new_unique(),
+            &blockhash.hash,
+        )
+        .await
+        .unwrap();
+        let result = send_and_confirm(
+            &rpc,
+            websocket,
+            "test",
+            &txn,
+            blockhash.last_valid_block_height,
+            CommitmentConfig::default(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(Error::TransactionFailed(_))));
+        // It landed, so the fee was still taken.
+        assert_eq!(
+            node.balance(&wallet.pubkey()),
+            1_000_000 - LAMPORTS_PER_SIGNATURE
+        );
+    }
+}
@@ -0,0 +1,119 @@
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use reqwest::{Client, StatusCode, header};
+use serde_json::Value;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio_tungstenite::connect_async;
+use tungstenite::{Message, client::IntoClientRequest};
+
+use crate::errors::errors::Error;
+
+/// Text frames of a subscription connection. Requests are written to `sender`,
+/// responses and notifications arrive on `receiver`, which closes with the connection.
+pub struct SubscriptionChannel {
+    pub sender: UnboundedSender<String>,
+    pub receiver: UnboundedReceiver<String>,
+}
+
+#[async_trait]
+pub trait RpcTransport: Send + Sync {
+    /// Sends a JSON-RPC request body and returns the raw response body.
+    async fn send_request(&self, body: &Value) -> Result<String, Error>;
+    async fn open_subscription(&self) -> Result<SubscriptionChannel, Error>;
+}
+
+pub struct HttpTransport {
+    rpc_url: String,
+    websocket_url: String,
+    client: Client,
+}
+
+impl HttpTransport {
+    pub fn new(rpc_url: &str, websocket_url: &str) -> Result<Self, Error> {
+        let client = Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .build()?;
+
+        Ok(Self {
+            rpc_url: rpc_url.to_owned(),
+            websocket_url: websocket_url.to_owned(),
+            client,
+        })
+    }
+}
+
+#[async_trait]
+impl RpcTransport for HttpTransport {
+    async fn send_request(&self, body: &Value) -> Result<String, Error> {
+        let mut headers = header::HeaderMap::new();
+        headers.insert("Content-Type", "application/json".parse().unwrap());
+
+        let resp = self
+            .client
+            .post(&self.rpc_url)
+            .headers(headers)
+            .body(body.to_string())
+            .send()
+            .await?;
+
+        let status = resp.status();
+        let text = resp.text().await?;
+        if !status.is_success() {
+            return Err(Error::RpcError(format!("http {}: {}", status, text)));
+        }
+
+        Ok(text)
+    }
+
+    async fn open_subscription(&self) -> Result<SubscriptionChannel, Error> {
+        let request = self
+            .websocket_url
+            .clone()
+            .into_client_request()
+            .map_err(Box::new)?;
+        let (stream, res) = connect_async(request).await.map_err(Box::new)?;
+
+        if res.status() == StatusCode::SWITCHING_PROTOCOLS {
+            println!("Established websocket connection successfully.");
+        }
+
+        let (mut write, mut read) = stream.split();
+        let (request_tx, mut request_rx) = mpsc::unbounded_channel::<String>();
+        let (response_tx, response_rx) = mpsc::unbounded_channel::<String>();
+
+        tokio::spawn(async move {
+            while let Some(text) = request_rx.recv().await {
+                if let Err(err) = write.send(Message::text(text)).await {
+                    eprintln!("Error sending: {err}");
+                    break;
+                }
+            }
+        });
+
+        tokio::spawn(async move {
+            while let Some(received) = read.next().await {
+                let message = match received {
+                    Err(err) => {
+                        if matches!(err, tungstenite::Error::ConnectionClosed) {
+                            eprintln!("Connection closed")
+                        }
+                        eprintln!("Error receiving websocket data: {err}");
+                        break;
+                    }
+                    Ok(msg) => msg,
+                };
+
+                if let Message::Text(text) = message
+                    && response_tx.send(text.to_string()).is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        Ok(SubscriptionChannel {
+            sender: request_tx,
+            receiver: response_rx,
+        })
+    }
+}
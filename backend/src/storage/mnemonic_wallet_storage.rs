@@ -1,3 +1,5 @@
+use std::collections::HashMap;
+
 use crate::{config::Config, errors::errors::Error};
 use rusqlite::params;
 use solana_sdk::{
@@ -6,6 +8,9 @@ use solana_sdk::{
 };
 use tokio_rusqlite::Connection;
 
+/// Index the treasury wallet is derived at, fleet wallets start at 1.
+pub const TREASURY_INDEX: u64 = 0;
+
 fn get_wallet(seed: &Vec<u8>, index: u64) -> Keypair {
     let index_bytes = index.to_be_bytes();
     let mut seed_with_index = seed.clone();
@@ -22,6 +27,13 @@ fn get_wallet(seed: &Vec<u8>, index: u64) -> Keypair {
     return keypair;
 }
 
+/// The treasury wallet, `None` unless it is enabled.
+pub fn get_treasury_wallet(config: &Config) -> Option<Keypair> {
+    config
+        .treasury_enabled
+        .then(|| get_wallet(&config.wallet_seed, TREASURY_INDEX))
+}
+
 pub async fn create_new_wallet(database: &Connection, config: &Config) -> Result<Keypair, Error> {
     let wallet_index = database
         .call(|conn| {
@@ -122,3 +134,156 @@ pub async fn get_wallets_by_pubkey(
 
     return Ok(keypairs);
 }
+
+pub async fn create_wallet_tags_table(database: &Connection) -> Result<(), Error> {
+    database
+        .call(|conn| {
+            conn.execute(
+                "
+                CREATE TABLE IF NOT EXISTS wallet_tags (
+                    seed INTEGER NOT NULL REFERENCES wallets(seed),
+                    tag TEXT NOT NULL,
+                    PRIMARY KEY (seed, tag)
+                );
+                ",
+                [],
+            )?;
+
+            Ok(())
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Adds the tag to every given fleet wallet, returns how many were newly tagged.
+pub async fn tag_wallets(
+    database: &Connection,
+    pubkeys: Vec<String>,
+    tag: String,
+) -> Result<usize, Error> {
+    let tagged = database
+        .call(move |conn| {
+            let mut stmt = conn.prepare(
+                "
+                INSERT OR IGNORE INTO wallet_tags (seed, tag)
+                SELECT seed, ?2 FROM wallets WHERE pubkey = ?1;
+                ",
+            )?;
+            let mut tagged = 0;
+            for pubkey in pubkeys.iter() {
+                tagged += stmt.execute(params![pubkey, tag])?;
+            }
+
+            Ok(tagged)
+        })
+        .await?;
+
+    Ok(tagged)
+}
+
+async fn get_wallets_by_query(
+    database: &Connection,
+    config: &Config,
+    sql: &'static str,
+    query_params: Vec<rusqlite::types::Value>,
+) -> Result<Vec<Keypair>, Error> {
+    let wallet_seeds = database
+        .call(move |conn| {
+            let mut stmt = conn.prepare(sql)?;
+            let wallet_seeds = stmt
+                .query_map(rusqlite::params_from_iter(query_params), |row| {
+                    row.get::<usize, u64>(0)
+                })?
+                .collect::<Result<Vec<u64>, rusqlite::Error>>()?;
+
+            Ok(wallet_seeds)
+        })
+        .await?;
+
+    Ok(wallet_seeds
+        .into_iter()
+        .map(|i| get_wallet(&config.wallet_seed, i))
+        .collect())
+}
+
+/// Wallets whose index lies within `start..=end`.
+pub async fn get_wallets_by_index_range(
+    database: &Connection,
+    config: &Config,
+    start: u64,
+    end: u64,
+) -> Result<Vec<Keypair>, Error> {
+    get_wallets_by_query(
+        database,
+        config,
+        "SELECT seed FROM wallets WHERE seed BETWEEN ?1 AND ?2 ORDER BY seed;",
+        vec![
+            (start as i64).into(),
+            (end.min(i64::MAX as u64) as i64).into(),
+        ],
+    )
+    .await
+}
+
+pub async fn get_wallets_by_tag(
+    database: &Connection,
+    config: &Config,
+    tag: &str,
+) -> Result<Vec<Keypair>, Error> {
+    get_wallets_by_query(
+        database,
+        config,
+        "SELECT seed FROM wallet_tags WHERE tag = ?1 ORDER BY seed;",
+        vec![tag.to_owned().into()],
+    )
+    .await
+}
+
+pub struct WalletLabels {
+    pub index: u64,
+    pub tags: Vec<String>,
+}
+
+/// Index and tags of every wallet, by pubkey.
+pub async fn get_wallet_labels(
+    database: &Connection,
+) -> Result<HashMap<String, WalletLabels>, Error> {
+    let rows = database
+        .call(|conn| {
+            let mut stmt = conn.prepare(
+                "
+                SELECT wallets.pubkey, wallets.seed, wallet_tags.tag
+                FROM wallets
+                LEFT JOIN wallet_tags ON wallet_tags.seed = wallets.seed
+                ORDER BY wallets.seed, wallet_tags.tag;
+                ",
+            )?;
+            let rows = stmt
+                .query_map([], |row| {
+                    Ok((
+                        row.get::<usize, Option<String>>(0)?,
+                        row.get::<usize, u64>(1)?,
+                        row.get::<usize, Option<String>>(2)?,
+                    ))
+                })?
+                .collect::<Result<Vec<_>, rusqlite::Error>>()?;
+
+            Ok(rows)
+        })
+        .await?;
+
+    let mut labels: HashMap<String, WalletLabels> = HashMap::new();
+    for (pubkey, index, tag) in rows {
+        let Some(pubkey) = pubkey else {
+            continue;
+        };
+        let entry = labels.entry(pubkey).or_insert_with(|| WalletLabels {
+            index,
+            tags: Vec::new(),
+        });
+        entry.tags.extend(tag);
+    }
+
+    Ok(labels)
+}
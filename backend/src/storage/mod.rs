@@ -1 +1,2 @@
 pub mod mnemonic_wallet_storage;
+pub mod swap_storage;
@@ -0,0 +1,99 @@
+use rusqlite::params;
+use tokio_rusqlite::Connection;
+
+use crate::{
+    errors::errors::Error,
+    swapping::{swap::SwapResult, swap_provider::SwapOrder},
+};
+
+pub async fn create_swaps_table(database: &Connection) -> Result<(), Error> {
+    database
+        .call(|conn| {
+            conn.execute(
+                "
+                CREATE TABLE IF NOT EXISTS swaps (
+                    signature TEXT PRIMARY KEY,
+                    wallet TEXT NOT NULL,
+                    provider TEXT NOT NULL,
+                    input_mint TEXT NOT NULL,
+                    output_mint TEXT NOT NULL,
+                    quoted_in_amount INTEGER NOT NULL,
+                    quoted_out_amount INTEGER NOT NULL,
+                    min_out_amount INTEGER NOT NULL,
+                    in_amount INTEGER NOT NULL,
+                    out_amount INTEGER NOT NULL,
+                    effective_price REAL,
+                    fee_lamports INTEGER NOT NULL,
+                    rent_lamports INTEGER NOT NULL,
+                    slot INTEGER NOT NULL,
+                    block_time INTEGER,
+                    err TEXT,
+                    created_at INTEGER NOT NULL DEFAULT (strftime('%s', 'now'))
+                );
+                ",
+                [],
+            )?;
+
+            Ok(())
+        })
+        .await?;
+
+    Ok(())
+}
+
+/// Records the realized fill of a landed swap.
+pub async fn save_swap(
+    database: &Connection,
+    wallet: &str,
+    order: &SwapOrder,
+    swap: &SwapResult,
+) -> Result<(), Error> {
+    let wallet = wallet.to_owned();
+    let order = order.clone();
+    let signature = swap.signature.clone();
+    let provider = swap.provider;
+    let quoted = (
+        swap.quoted_in_amount as i64,
+        swap.quoted_out_amount as i64,
+        swap.min_out_amount as i64,
+    );
+    let fill = swap.fill.clone();
+
+    database
+        .call(move |conn| {
+            conn.execute(
+                "
+                INSERT OR REPLACE INTO swaps (
+                    signature, wallet, provider, input_mint, output_mint,
+                    quoted_in_amount, quoted_out_amount, min_out_amount,
+                    in_amount, out_amount, effective_price, fee_lamports, rent_lamports,
+                    slot, block_time, err
+                )
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16);
+                ",
+                params![
+                    signature,
+                    wallet,
+                    provider,
+                    order.input_mint,
+                    order.output_mint,
+                    quoted.0,
+                    quoted.1,
+                    quoted.2,
+                    fill.in_amount as i64,
+                    fill.out_amount as i64,
+                    fill.effective_price,
+                    fill.fee_lamports as i64,
+                    fill.rent_lamports,
+                    fill.slot as i64,
+                    fill.block_time,
+                    fill.err
+                ],
+            )?;
+
+            Ok(())
+        })
+        .await?;
+
+    Ok(())
+}
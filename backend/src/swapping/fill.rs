@@ -0,0 +1,260 @@
+use crate::{
+    rpc::read::{RpcConfirmedTransaction, RpcTokenBalance},
+    swapping::swap_provider::{SwapOrder, WSOL_MINT},
+};
+
+const SOL_DECIMALS: u8 = 9;
+
+/// What a landed swap actually did to the taker's balances.
+#[derive(Debug, Clone)]
+pub struct SwapFill {
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    /// Set when the transaction landed but failed, nothing was swapped then.
+    pub err: Option<String>,
+    /// Input spent, in base units.
+    pub in_amount: u64,
+    /// Output received, in base units.
+    pub out_amount: u64,
+    /// Output per input in whole tokens, `None` if a mint's decimals are unknown.
+    pub effective_price: Option<f64>,
+    /// Network fee, base plus priority fee, if the taker paid it.
+    pub fee_lamports: u64,
+    /// Rent the taker put into token accounts the swap opened, minus rent
+    /// it got back from closed ones.
+    pub rent_lamports: i64,
+}
+
+impl SwapFill {
+    /// Reads the fill from the pre/post balances of the confirmed transaction.
+    /// Native SOL and wrapped SOL count as one asset, so either side of the
+    /// order may use the wSOL mint for SOL.
+    pub fn from_transaction(
+        txn: &RpcConfirmedTransaction,
+        taker: &str,
+        order: &SwapOrder,
+    ) -> Option<Self> {
+        let meta = txn.meta.as_ref()?;
+        let keys = txn.account_keys();
+        let taker_index = keys.iter().position(|k| *k == taker)?;
+
+        let lamports_at = |balances: &[u64], index: usize| -> i128 {
+            balances.get(index).copied().unwrap_or(0) as i128
+        };
+        let owned = |balances: &'_ [RpcTokenBalance]| -> Vec<(usize, String, i128, u8)> {
+            balances
+                .iter()
+                .filter(|b| b.owner.as_deref() == Some(taker))
+                .map(|b| {
+                    (
+                        b.account_index,
+                        b.mint.clone(),
+                        b.ui_token_amount.amount.parse::<i128>().unwrap_or(0),
+                        b.ui_token_amount.decimals,
+                    )
+                })
+                .collect()
+        };
+        let pre_tokens = owned(&meta.pre_token_balances);
+        let post_tokens = owned(&meta.post_token_balances);
+
+        let fee_lamports = if taker_index == 0 { meta.fee } else { 0 };
+
+        // Lamports parked in token accounts opened or closed by the swap are rent, not
+        // proceeds. For wSOL accounts only the part above the wrapped amount is rent.
+        let rent_of = |index: usize, mint: &str, amount: i128, balances: &[u64]| -> i128 {
+            let lamports = lamports_at(balances, index);
+            if mint == WSOL_MINT {
+                lamports - amount
+            } else {
+                lamports
+            }
+        };
+        let opened: i128 = post_tokens
+            .iter()
+            .filter(|(index, ..)| !pre_tokens.iter().any(|(i, ..)| i == index))
+            .map(|(index, mint, amount, _)| rent_of(*index, mint, *amount, &meta.post_balances))
+            .sum();
+        let closed: i128 = pre_tokens
+            .iter()
+            .filter(|(index, ..)| !post_tokens.iter().any(|(i, ..)| i == index))
+            .map(|(index, mint, amount, _)| rent_of(*index, mint, *amount, &meta.pre_balances))
+            .sum();
+        let rent_lamports = opened - closed;
+
+        let sol_change = lamports_at(&meta.post_balances, taker_index)
+            - lamports_at(&meta.pre_balances, taker_index)
+            + fee_lamports as i128
+            + rent_lamports;
+
+        let token_change = |mint: &str| -> i128 {
+            let total = |tokens: &[(usize, String, i128, u8)]| -> i128 {
+                tokens
+                    .iter()
+                    .filter(|(_, m, ..)| m == mint)
+                    .map(|(.., amount, _)| amount)
+                    .sum()
+            };
+            let change = total(&post_tokens) - total(&pre_tokens);
+            if mint == WSOL_MINT {
+                change + sol_change
+            } else {
+                change
+            }
+        };
+        let decimals = |mint: &str| -> Option<u8> {
+            if mint == WSOL_MINT {
+                return Some(SOL_DECIMALS);
+            }
+            pre_tokens
+                .iter()
+                .chain(post_tokens.iter())
+                .find(|(_, m, ..)| m == mint)
+                .map(|(.., decimals)| *decimals)
+        };
+
+        let err = meta.err.as_ref().map(|err| match err.as_str() {
+            Some(err) => err.to_owned(),
+            None => err.to_string(),
+        });
+        let (in_amount, out_amount) = match err {
+            Some(_) => (0, 0),
+            None => (
+                (-token_change(&order.input_mint)).max(0) as u64,
+                token_change(&order.output_mint).max(0) as u64,
+            ),
+        };
+
+        let effective_price = match (decimals(&order.input_mint), decimals(&order.output_mint)) {
+            (Some(input_decimals), Some(output_decimals)) if in_amount > 0 => Some(
+                (out_amount as f64 / 10f64.powi(output_decimals as i32))
+                    / (in_amount as f64 / 10f64.powi(input_decimals as i32)),
+            ),
+            _ => None,
+        };
+
+        Some(Self {
+            slot: txn.slot,
+            block_time: txn.block_time,
+            err,
+            in_amount,
+            out_amount,
+            effective_price,
+            fee_lamports,
+            rent_lamports: rent_lamports as i64,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USDC_MINT: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const BONK_MINT: &str = "DezXAZ8z7PnrnRJjz3wXBoRgixCa6xjnB7YaB1pPB263";
+    const TOKEN_ACCOUNT_RENT: i64 = 2_039_280;
+
+    // getTransaction results in the RPC's json encoding, one per kind of swap, with
+    // mainnet mints and programs and the balances of the pools' vaults around the taker's.
+    const SOL_TO_TOKEN: &str = include_str!("fixtures/sol_to_token.json");
+    const TOKEN_TO_SOL: &str = include_str!("fixtures/token_to_sol.json");
+    const TOKEN_TO_TOKEN: &str = include_str!("fixtures/token_to_token.json");
+    const GASLESS_TOKEN_TO_SOL: &str = include_str!("fixtures/gasless_token_to_sol.json");
+    const FAILED: &str = include_str!("fixtures/failed.json");
+
+    fn order(input_mint: &str, output_mint: &str, amount: u64) -> SwapOrder {
+        SwapOrder {
+            input_mint: input_mint.to_owned(),
+            output_mint: output_mint.to_owned(),
+            amount,
+            slippage_bps: 50,
+            max_price_impact_pct: None,
+        }
+    }
+
+    /// The fill of the fixture for the taker at `taker_index` among its account keys.
+    fn fill(fixture: &str, taker_index: usize, order: &SwapOrder) -> SwapFill {
+        let txn: RpcConfirmedTransaction = serde_json::from_str(fixture).unwrap();
+        let taker = txn.account_keys()[taker_index].to_owned();
+
+        SwapFill::from_transaction(&txn, &taker, order).unwrap()
+    }
+
+    #[test]
+    fn sol_to_token_counts_the_opened_account_as_rent() {
+        let fill = fill(SOL_TO_TOKEN, 0, &order(WSOL_MINT, USDC_MINT, 100_000_000));
+
+        assert!(fill.err.is_none());
+        assert_eq!(fill.in_amount, 100_000_000);
+        assert_eq!(fill.out_amount, 15_234_567);
+        assert_eq!(fill.fee_lamports, 20_000);
+        assert_eq!(fill.rent_lamports, TOKEN_ACCOUNT_RENT);
+        assert!((fill.effective_price.unwrap() - 152.34567).abs() < 1e-9);
+    }
+
+    #[test]
+    fn token_to_sol_ignores_the_wsol_account_opened_and_closed_in_the_swap() {
+        let fill = fill(TOKEN_TO_SOL, 0, &order(USDC_MINT, WSOL_MINT, 50_000_000));
+
+        assert!(fill.err.is_none());
+        assert_eq!(fill.in_amount, 50_000_000);
+        assert_eq!(fill.out_amount, 328_123_456);
+        assert_eq!(fill.fee_lamports, 7_500);
+        assert_eq!(fill.rent_lamports, 0);
+        assert!((fill.effective_price.unwrap() - 0.006_562_469_12).abs() < 1e-12);
+    }
+
+    #[test]
+    fn token_to_token_reads_balances_of_loaded_accounts() {
+        let fill = fill(TOKEN_TO_TOKEN, 0, &order(USDC_MINT, BONK_MINT, 25_000_000));
+
+        assert!(fill.err.is_none());
+        assert_eq!(fill.in_amount, 25_000_000);
+        assert_eq!(fill.out_amount, 1_234_567_890);
+        assert_eq!(fill.fee_lamports, 5_000);
+        assert_eq!(fill.rent_lamports, 0);
+        assert!((fill.effective_price.unwrap() - 493.827_156).abs() < 1e-6);
+    }
+
+    #[test]
+    fn gasless_taker_pays_no_fee() {
+        let fill = fill(
+            GASLESS_TOKEN_TO_SOL,
+            1,
+            &order(USDC_MINT, WSOL_MINT, 10_000_000),
+        );
+
+        assert!(fill.err.is_none());
+        assert_eq!(fill.in_amount, 10_000_000);
+        assert_eq!(fill.out_amount, 61_000_000);
+        assert_eq!(fill.fee_lamports, 0);
+        assert_eq!(fill.rent_lamports, 0);
+    }
+
+    #[test]
+    fn failed_swap_only_paid_the_fee() {
+        let fill = fill(FAILED, 0, &order(WSOL_MINT, USDC_MINT, 100_000_000));
+
+        assert_eq!(
+            fill.err.as_deref(),
+            Some(r#"{"InstructionError":[3,{"Custom":6001}]}"#)
+        );
+        assert_eq!(fill.in_amount, 0);
+        assert_eq!(fill.out_amount, 0);
+        assert_eq!(fill.fee_lamports, 10_000);
+        assert_eq!(fill.effective_price, None);
+    }
+
+    #[test]
+    fn no_fill_for_a_wallet_outside_the_transaction() {
+        let txn: RpcConfirmedTransaction = serde_json::from_str(SOL_TO_TOKEN).unwrap();
+
+        let fill = SwapFill::from_transaction(
+            &txn,
+            "11111111111111111111111111111112",
+            &order(WSOL_MINT, USDC_MINT, 100_000_000),
+        );
+
+        assert!(fill.is_none());
+    }
+}
@@ -0,0 +1,93 @@
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    errors::errors::Error,
+    swapping::swap_provider::{
+        SwapOrder, SwapProvider, SwapQuote, SwapTransaction, decode_transaction, parse_amount,
+        parse_block_height, parse_decimal,
+    },
+};
+
+/// Jupiter's quote and swap API, the transaction is built from a separate quote.
+pub struct JupiterSwap {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl JupiterSwap {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl SwapProvider for JupiterSwap {
+    fn name(&self) -> &'static str {
+        "jupiter_swap"
+    }
+
+    async fn quote(&self, order: &SwapOrder, _taker: Option<&Pubkey>) -> Result<SwapQuote, Error> {
+        let url = format!(
+            "{}/quote?inputMint={}&outputMint={}&amount={}&slippageBps={}",
+            self.base_url, order.input_mint, order.output_mint, order.amount, order.slippage_bps
+        );
+        let quote: Value = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(SwapQuote {
+            provider: self.name(),
+            input_mint: order.input_mint.clone(),
+            output_mint: order.output_mint.clone(),
+            in_amount: parse_amount(&quote["inAmount"], "inAmount")?,
+            out_amount: parse_amount(&quote["outAmount"], "outAmount")?,
+            min_out_amount: parse_amount(&quote["otherAmountThreshold"], "otherAmountThreshold")?,
+            slippage_bps: order.slippage_bps,
+            // Jupiter reports the impact as a fraction.
+            price_impact_pct: parse_decimal(&quote["priceImpactPct"], "priceImpactPct")? * 100.0,
+            route: quote,
+        })
+    }
+
+    async fn build_transaction(
+        &self,
+        quote: &SwapQuote,
+        taker: &Pubkey,
+    ) -> Result<SwapTransaction, Error> {
+        let swap: Value = self
+            .client
+            .post(format!("{}/swap", self.base_url))
+            .json(&json!({
+                "quoteResponse": quote.route,
+                "userPublicKey": taker.to_string(),
+                "wrapAndUnwrapSol": true,
+                "dynamicComputeUnitLimit": true
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        match swap["swapTransaction"].as_str() {
+            Some(transaction) => Ok(SwapTransaction {
+                transaction: decode_transaction(transaction)?,
+                last_valid_block_height: parse_block_height(&swap["lastValidBlockHeight"]),
+            }),
+            None => Err(Error::Swap(format!(
+                "jupiter swap returned no transaction: {}",
+                swap
+            ))),
+        }
+    }
+}
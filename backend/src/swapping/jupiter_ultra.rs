@@ -0,0 +1,197 @@
+use async_trait::async_trait;
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    errors::errors::Error,
+    swapping::swap_provider::{
+        SwapOrder, SwapProvider, SwapQuote, SwapTransaction, decode_transaction, parse_amount,
+        parse_block_height, parse_decimal,
+    },
+};
+
+/// Jupiter Ultra, whose order already contains the transaction for the taker.
+pub struct JupiterUltra {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl JupiterUltra {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl SwapProvider for JupiterUltra {
+    fn name(&self) -> &'static str {
+        "jupiter_ultra"
+    }
+
+    async fn quote(&self, order: &SwapOrder, taker: Option<&Pubkey>) -> Result<SwapQuote, Error> {
+        // Ultra picks the slippage itself, the guards reject orders above the requested one.
+        let mut url = format!(
+            "{}/order?inputMint={}&outputMint={}&amount={}",
+            self.base_url, order.input_mint, order.output_mint, order.amount
+        );
+        if let Some(taker) = taker {
+            url.push_str(&format!("&taker={}", taker));
+        }
+        let ultra_order: Value = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(SwapQuote {
+            provider: self.name(),
+            input_mint: order.input_mint.clone(),
+            output_mint: order.output_mint.clone(),
+            in_amount: parse_amount(&ultra_order["inAmount"], "inAmount")?,
+            out_amount: parse_amount(&ultra_order["outAmount"], "outAmount")?,
+            min_out_amount: parse_amount(
+                &ultra_order["otherAmountThreshold"],
+                "otherAmountThreshold",
+            )?,
+            slippage_bps: ultra_order["slippageBps"]
+                .as_u64()
+                .ok_or_else(|| Error::Swap("missing or invalid slippageBps in quote".to_owned()))?
+                as u16,
+            // Like the quote API, Ultra reports the impact as a fraction.
+            price_impact_pct: parse_decimal(&ultra_order["priceImpactPct"], "priceImpactPct")?
+                * 100.0,
+            route: ultra_order,
+        })
+    }
+
+    async fn build_transaction(
+        &self,
+        quote: &SwapQuote,
+        taker: &Pubkey,
+    ) -> Result<SwapTransaction, Error> {
+        let txn = match quote.route["transaction"].as_str() {
+            Some(transaction) if !transaction.is_empty() => decode_transaction(transaction)?,
+            // Ultra leaves the transaction empty when it can't build it, e.g. for
+            // a taker without enough of the input mint, and says why.
+            _ => {
+                let reason = quote.route["errorMessage"]
+                    .as_str()
+                    .or(quote.route["error"].as_str())
+                    .unwrap_or("no reason given");
+                return Err(Error::Swap(format!(
+                    "jupiter ultra returned no transaction: {}",
+                    reason
+                )));
+            }
+        };
+
+        // RFQ and gasless routes also need a market maker's or Jupiter's signature, which
+        // only Ultra's own execution adds. Refusing them here lets the router fall back.
+        let signers = txn.message.header().num_required_signatures as usize;
+        if signers != 1 || txn.message.static_account_keys().first() != Some(taker) {
+            return Err(Error::Swap(format!(
+                "jupiter ultra route needs {} signers, only the taker can sign",
+                signers
+            )));
+        }
+
+        Ok(SwapTransaction {
+            transaction: txn,
+            last_valid_block_height: parse_block_height(&quote.route["lastValidBlockHeight"]),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::{Engine, engine::general_purpose};
+    use serde_json::json;
+    use solana_sdk::{
+        hash::Hash,
+        message::{VersionedMessage, v0},
+        transaction::VersionedTransaction,
+    };
+
+    use super::*;
+
+    fn quote(route: Value) -> SwapQuote {
+        SwapQuote {
+            provider: "jupiter_ultra",
+            input_mint: String::new(),
+            output_mint: String::new(),
+            in_amount: 0,
+            out_amount: 0,
+            min_out_amount: 0,
+            slippage_bps: 0,
+            price_impact_pct: 0.0,
+            route,
+        }
+    }
+
+    /// An unsigned transfer from `from` to `to`, encoded like Ultra's orders. `to_signs`
+    /// makes it a second signer, like the market maker of an RFQ route.
+    fn encoded_transfer(from: &Pubkey, to: &Pubkey, to_signs: bool) -> String {
+        let mut transfer = solana_system_interface::instruction::transfer(from, to, 1);
+        transfer.accounts[1].is_signer = to_signs;
+        let message = v0::Message::try_compile(from, &[transfer], &[], Hash::default()).unwrap();
+        let signers = message.header.num_required_signatures as usize;
+
+        let txn = VersionedTransaction {
+            signatures: vec![Default::default(); signers],
+            message: VersionedMessage::V0(message),
+        };
+        general_purpose::STANDARD.encode(bincode::serialize(&txn).unwrap())
+    }
+
+    #[tokio::test]
+    async fn builds_orders_only_the_taker_signs_with_their_block_height() {
+        let taker = Pubkey::new_unique();
+        let route = json!({
+            "transaction": encoded_transfer(&taker, &Pubkey::new_unique(), false),
+            "lastValidBlockHeight": "350000150"
+        });
+
+        let built = JupiterUltra::new("")
+            .build_transaction(&quote(route), &taker)
+            .await
+            .unwrap();
+
+        assert_eq!(built.transaction.message.static_account_keys()[0], taker);
+        assert_eq!(built.last_valid_block_height, Some(350_000_150));
+    }
+
+    #[tokio::test]
+    async fn refuses_orders_needing_another_signer() {
+        let taker = Pubkey::new_unique();
+        let maker = Pubkey::new_unique();
+        let ultra = JupiterUltra::new("");
+
+        let rfq = json!({ "transaction": encoded_transfer(&taker, &maker, true) });
+        let result = ultra.build_transaction(&quote(rfq), &taker).await;
+        assert!(matches!(result, Err(Error::Swap(_))));
+
+        let gasless = json!({ "transaction": encoded_transfer(&maker, &taker, false) });
+        let result = ultra.build_transaction(&quote(gasless), &taker).await;
+        assert!(matches!(result, Err(Error::Swap(_))));
+    }
+
+    #[tokio::test]
+    async fn reports_why_the_order_has_no_transaction() {
+        let route = json!({ "transaction": "", "errorMessage": "Insufficient funds" });
+
+        let result = JupiterUltra::new("")
+            .build_transaction(&quote(route), &Pubkey::new_unique())
+            .await;
+
+        match result {
+            Err(Error::Swap(message)) => assert!(message.contains("Insufficient funds")),
+            _ => panic!("expected a swap error"),
+        }
+    }
+}
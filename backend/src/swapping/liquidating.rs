@@ -0,0 +1,106 @@
+use std::collections::BTreeMap;
+
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use crate::{
+    Rpc, Websocket,
+    config::CommitmentConfig,
+    errors::errors::Error,
+    swapping::{
+        router::SwapRouter,
+        swap::{SwapResult, swap},
+        swap_provider::{SwapOrder, WSOL_MINT},
+    },
+    tokens::token_accounts::get_token_accounts,
+};
+
+/// What a wallet's token holdings are swapped into.
+#[derive(Debug, Clone)]
+pub struct LiquidationOrder {
+    pub output_mint: String,
+    pub slippage_bps: u16,
+    pub max_price_impact_pct: Option<f64>,
+    /// Holdings quoted at less than this many lamports are left alone.
+    pub dust_lamports: u64,
+}
+
+pub enum LiquidationOutcome {
+    Swapped(SwapResult),
+    Skipped(String),
+    Failed(Error),
+}
+
+/// The outcome for one mint the wallet held.
+pub struct LiquidationResult {
+    pub order: SwapOrder,
+    pub outcome: LiquidationOutcome,
+}
+
+/// Swaps every token the wallet holds into the output mint, one mint after another.
+/// Holdings already in the output mint are not touched.
+pub async fn liquidate(
+    rpc: &Rpc,
+    router: &SwapRouter,
+    wallet: &Keypair,
+    liquidation: &LiquidationOrder,
+    websocket_service: Websocket,
+    commitment: CommitmentConfig,
+) -> Result<Vec<LiquidationResult>, Error> {
+    let token_accounts = get_token_accounts(rpc, &wallet.pubkey(), commitment.read).await?;
+
+    let mut holdings: BTreeMap<String, u64> = BTreeMap::new();
+    for account in token_accounts.iter().filter(|a| a.amount > 0) {
+        *holdings.entry(account.mint.to_string()).or_default() += account.amount;
+    }
+    holdings.remove(&liquidation.output_mint);
+
+    let mut results = Vec::new();
+    for (mint, amount) in holdings {
+        let order = SwapOrder {
+            input_mint: mint,
+            output_mint: liquidation.output_mint.clone(),
+            amount,
+            slippage_bps: liquidation.slippage_bps,
+            max_price_impact_pct: liquidation.max_price_impact_pct,
+        };
+
+        // Wrapped SOL is only left alone when liquidating into SOL, it was removed above then.
+        let outcome = match value_in_lamports(router, &order).await {
+            Err(err) => LiquidationOutcome::Failed(err),
+            Ok(value) if value < liquidation.dust_lamports => {
+                LiquidationOutcome::Skipped(format!("Dust, worth {} lamports.", value))
+            }
+            Ok(_) => match swap(
+                rpc,
+                router,
+                &order,
+                wallet,
+                websocket_service.clone(),
+                commitment,
+            )
+            .await
+            {
+                Ok(result) => LiquidationOutcome::Swapped(result),
+                Err(err) => LiquidationOutcome::Failed(err),
+            },
+        };
+
+        results.push(LiquidationResult { order, outcome });
+    }
+
+    Ok(results)
+}
+
+/// What the order's input is worth in SOL according to the best quote.
+async fn value_in_lamports(router: &SwapRouter, order: &SwapOrder) -> Result<u64, Error> {
+    if order.input_mint == WSOL_MINT {
+        return Ok(order.amount);
+    }
+    let sol_order = SwapOrder {
+        output_mint: WSOL_MINT.to_owned(),
+        ..order.clone()
+    };
+    let quotes = router.quotes(&sol_order, None).await?;
+
+    Ok(quotes.first().map(|q| q.out_amount).unwrap_or(0))
+}
@@ -0,0 +1,8 @@
+pub mod fill;
+pub mod jupiter_swap;
+pub mod jupiter_ultra;
+pub mod liquidating;
+pub mod raydium;
+pub mod router;
+pub mod swap;
+pub mod swap_provider;
@@ -0,0 +1,115 @@
+use async_trait::async_trait;
+use serde_json::{Value, json};
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    errors::errors::Error,
+    swapping::swap_provider::{
+        SwapOrder, SwapProvider, SwapQuote, SwapTransaction, WSOL_MINT, decode_transaction,
+        parse_amount, parse_decimal,
+    },
+};
+
+const COMPUTE_UNIT_PRICE_MICRO_LAMPORTS: u64 = 10_000;
+
+/// Direct route through Raydium's AMM pools via its trade API.
+pub struct Raydium {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl Raydium {
+    pub fn new(base_url: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_owned(),
+        }
+    }
+}
+
+#[async_trait]
+impl SwapProvider for Raydium {
+    fn name(&self) -> &'static str {
+        "raydium"
+    }
+
+    async fn quote(&self, order: &SwapOrder, _taker: Option<&Pubkey>) -> Result<SwapQuote, Error> {
+        let url = format!(
+            "{}/compute/swap-base-in?inputMint={}&outputMint={}&amount={}&slippageBps={}&txVersion=V0",
+            self.base_url, order.input_mint, order.output_mint, order.amount, order.slippage_bps
+        );
+        let compute: Value = self
+            .client
+            .get(url)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        if compute["success"].as_bool() != Some(true) {
+            return Err(Error::Swap(format!("raydium has no route: {}", compute)));
+        }
+
+        Ok(SwapQuote {
+            provider: self.name(),
+            input_mint: order.input_mint.clone(),
+            output_mint: order.output_mint.clone(),
+            in_amount: parse_amount(&compute["data"]["inputAmount"], "inputAmount")?,
+            out_amount: parse_amount(&compute["data"]["outputAmount"], "outputAmount")?,
+            min_out_amount: parse_amount(
+                &compute["data"]["otherAmountThreshold"],
+                "otherAmountThreshold",
+            )?,
+            slippage_bps: order.slippage_bps,
+            // Raydium already reports the impact in percent.
+            price_impact_pct: parse_decimal(&compute["data"]["priceImpactPct"], "priceImpactPct")?,
+            route: compute,
+        })
+    }
+
+    async fn build_transaction(
+        &self,
+        quote: &SwapQuote,
+        taker: &Pubkey,
+    ) -> Result<SwapTransaction, Error> {
+        let built: Value = self
+            .client
+            .post(format!("{}/transaction/swap-base-in", self.base_url))
+            .json(&json!({
+                "computeUnitPriceMicroLamports": COMPUTE_UNIT_PRICE_MICRO_LAMPORTS.to_string(),
+                "swapResponse": quote.route,
+                "txVersion": "V0",
+                "wallet": taker.to_string(),
+                "wrapSol": quote.input_mint == WSOL_MINT,
+                "unwrapSol": quote.output_mint == WSOL_MINT
+            }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let transactions = built["data"].as_array().cloned().unwrap_or_default();
+        match transactions.as_slice() {
+            // Raydium doesn't say until when its blockhash is valid.
+            [single] => match single["transaction"].as_str() {
+                Some(transaction) => Ok(SwapTransaction {
+                    transaction: decode_transaction(transaction)?,
+                    last_valid_block_height: None,
+                }),
+                None => Err(Error::Swap(format!(
+                    "raydium returned no transaction: {}",
+                    built
+                ))),
+            },
+            [] => Err(Error::Swap(format!(
+                "raydium returned no transaction: {}",
+                built
+            ))),
+            _ => Err(Error::Swap(
+                "raydium routes spanning several transactions are not supported".to_owned(),
+            )),
+        }
+    }
+}
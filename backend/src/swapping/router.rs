@@ -0,0 +1,92 @@
+use std::{cmp::Reverse, sync::Arc};
+
+use solana_sdk::pubkey::Pubkey;
+use tokio::task::JoinSet;
+
+use crate::{
+    errors::errors::Error,
+    swapping::swap_provider::{SwapOrder, SwapProvider, SwapQuote, SwapTransaction},
+};
+
+/// Asks every provider for a quote and swaps through the one paying out the most.
+pub struct SwapRouter {
+    providers: Vec<Arc<dyn SwapProvider>>,
+}
+
+impl SwapRouter {
+    pub fn new(providers: Vec<Arc<dyn SwapProvider>>) -> Self {
+        Self { providers }
+    }
+
+    /// Quotes of all providers that answered, best output first. Guards are not applied.
+    pub async fn quotes(
+        &self,
+        order: &SwapOrder,
+        taker: Option<&Pubkey>,
+    ) -> Result<Vec<SwapQuote>, Error> {
+        let mut quote_set = JoinSet::new();
+        for provider in self.providers.iter() {
+            let provider = Arc::clone(provider);
+            let order = order.clone();
+            let taker = taker.copied();
+            quote_set.spawn(async move {
+                let quote = provider.quote(&order, taker.as_ref()).await;
+                (provider.name(), quote)
+            });
+        }
+
+        let mut quotes = Vec::new();
+        let mut errors = Vec::new();
+        while let Some(res) = quote_set.join_next().await {
+            match res? {
+                (_, Ok(quote)) => quotes.push(quote),
+                (name, Err(err)) => {
+                    eprintln!("Quote from {} failed: {}", name, err);
+                    errors.push(format!("{}: {}", name, err));
+                }
+            }
+        }
+
+        if quotes.is_empty() {
+            return Err(Error::Swap(format!(
+                "No provider could quote the swap. {}",
+                errors.join(", ")
+            )));
+        }
+
+        quotes.sort_by_key(|q| Reverse(q.out_amount));
+        Ok(quotes)
+    }
+
+    /// Builds the transaction of the best quote within the order's guards. When its
+    /// provider fails to build, the next best quote is used.
+    pub async fn build_best(
+        &self,
+        order: &SwapOrder,
+        taker: &Pubkey,
+    ) -> Result<(SwapQuote, SwapTransaction), Error> {
+        let quotes = self.quotes(order, Some(taker)).await?;
+
+        let mut last_err = None;
+        for quote in quotes {
+            if let Err(err) = quote.check_guards(order) {
+                eprintln!("Skipping quote: {}", err);
+                last_err.get_or_insert(err);
+                continue;
+            }
+
+            let Some(provider) = self.providers.iter().find(|p| p.name() == quote.provider) else {
+                continue;
+            };
+            match provider.build_transaction(&quote, taker).await {
+                Ok(txn) => return Ok((quote, txn)),
+                Err(err) => {
+                    eprintln!("Building swap with {} failed: {}", quote.provider, err);
+                    last_err = Some(err);
+                }
+            }
+        }
+
+        Err(last_err.unwrap_or_else(|| Error::Swap("No provider built the swap.".to_owned())))
+    }
+}
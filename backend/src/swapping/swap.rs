@@ -0,0 +1,305 @@
+use std::time::Duration;
+
+use solana_sdk::{signature::Keypair, signer::Signer, transaction::VersionedTransaction};
+
+use crate::{
+    Rpc, Websocket,
+    config::CommitmentConfig,
+    errors::errors::Error,
+    rpc::{
+        commitment::Commitment,
+        read::{get_block_height, get_transaction},
+        send::send_and_confirm,
+    },
+    swapping::{fill::SwapFill, router::SwapRouter, swap_provider::SwapOrder},
+    txn_factory::{
+        blockhash::MAX_PROCESSING_AGE,
+        util::{SimpleTransaction, encode_versioned_transaction},
+    },
+};
+
+/// How often a confirmed swap is looked up before giving up, nodes may lag
+/// behind the one that sent the confirmation.
+const FILL_LOOKUP_ATTEMPTS: u32 = 10;
+const FILL_LOOKUP_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A confirmed swap, with what the provider quoted and what actually landed.
+pub struct SwapResult {
+    pub signature: String,
+    pub provider: &'static str,
+    pub quoted_in_amount: u64,
+    pub quoted_out_amount: u64,
+    pub min_out_amount: u64,
+    pub fill: SwapFill,
+}
+
+/// Swaps the order on `wallet` through the best quoting provider, waits for
+/// the confirmation and reads the fill from the landed transaction.
+pub async fn swap(
+    rpc: &Rpc,
+    router: &SwapRouter,
+    order: &SwapOrder,
+    wallet: &Keypair,
+    websocket_service: Websocket,
+    commitment: CommitmentConfig,
+) -> Result<SwapResult, Error> {
+    let (quote, built) = router.build_best(order, &wallet.pubkey()).await?;
+    let txn = VersionedTransaction::try_new(built.transaction.message, &[wallet])?;
+    let signature = txn.signatures[0].to_string();
+
+    println!("Swapping via {}: {}", quote.provider, signature);
+
+    let txn = SimpleTransaction {
+        transaction: encode_versioned_transaction(&txn),
+        signature: signature.clone(),
+    };
+    // Without the provider's word the blockhash expires at most this many blocks from now.
+    let last_valid_block_height = match built.last_valid_block_height {
+        Some(height) => height,
+        None => get_block_height(rpc, "swap", commitment.blockhash).await? + MAX_PROCESSING_AGE,
+    };
+    let confirmation = send_and_confirm(
+        rpc,
+        websocket_service,
+        "swap",
+        &txn,
+        last_valid_block_height,
+        commitment,
+    )
+    .await;
+    // A swap that failed on chain still paid its fees, its fill reports the error.
+    match confirmation {
+        Ok(_) | Err(Error::TransactionFailed(_)) => {}
+        Err(err) => return Err(err),
+    }
+
+    let fill = get_fill(
+        rpc,
+        &signature,
+        &wallet.pubkey().to_string(),
+        order,
+        commitment,
+    )
+    .await?;
+
+    Ok(SwapResult {
+        signature,
+        provider: quote.provider,
+        quoted_in_amount: quote.in_amount,
+        quoted_out_amount: quote.out_amount,
+        min_out_amount: quote.min_out_amount,
+        fill,
+    })
+}
+
+async fn get_fill(
+    rpc: &Rpc,
+    signature: &str,
+    taker: &str,
+    order: &SwapOrder,
+    commitment: CommitmentConfig,
+) -> Result<SwapFill, Error> {
+    // Transactions can't be looked up at processed.
+    let lookup_commitment = match commitment.confirmation {
+        Commitment::Processed => Commitment::Confirmed,
+        c => c,
+    };
+
+    for _ in 0..FILL_LOOKUP_ATTEMPTS {
+        if let Some(txn) = get_transaction(rpc, "swap_fill", signature, lookup_commitment).await? {
+            return SwapFill::from_transaction(&txn, taker, order).ok_or_else(|| {
+                Error::Swap(format!(
+                    "Swap {} landed without balances for {}.",
+                    signature, taker
+                ))
+            });
+        }
+        tokio::time::sleep(FILL_LOOKUP_INTERVAL).await;
+    }
+
+    Err(Error::Swap(format!(
+        "Swap {} was confirmed but the transaction could not be fetched.",
+        signature
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use async_trait::async_trait;
+    use mock_node::MockNode;
+    use serde_json::Value;
+    use solana_sdk::{
+        message::{VersionedMessage, v0},
+        pubkey::Pubkey,
+        rent::Rent,
+    };
+    use spl_associated_token_account_interface::{
+        address::get_associated_token_address,
+        instruction::create_associated_token_account_idempotent,
+    };
+
+    use super::*;
+    use crate::{
+        Blockhashes,
+        rpc::mock::{mock_mint, mock_services, mock_token_amount},
+        swapping::swap_provider::{SwapProvider, SwapQuote, SwapTransaction, WSOL_MINT},
+        tokens::token_accounts::token_account_len,
+        txn_factory::util::LAMPORTS_PER_SIGNATURE,
+    };
+
+    const WALLET_LAMPORTS: u64 = 100_000_000;
+    /// Tokens the pool pays per lamport.
+    const RATE: u64 = 3;
+
+    /// Sells `mint` for SOL at `RATE`, minting what it pays out. The taker is the mint's
+    /// authority, so the swap only needs the taker's signature like an aggregator route.
+    struct MintingPool {
+        pool: Pubkey,
+        mint: Pubkey,
+        blockhashes: Blockhashes,
+        slippage_bps: u16,
+    }
+
+    #[async_trait]
+    impl SwapProvider for MintingPool {
+        fn name(&self) -> &'static str {
+            "minting_pool"
+        }
+
+        async fn quote(&self, order: &SwapOrder, _: Option<&Pubkey>) -> Result<SwapQuote, Error> {
+            if order.input_mint != WSOL_MINT || order.output_mint != self.mint.to_string() {
+                return Err(Error::Swap("pair not supported".to_owned()));
+            }
+
+            Ok(SwapQuote {
+                provider: self.name(),
+                input_mint: order.input_mint.clone(),
+                output_mint: order.output_mint.clone(),
+                in_amount: order.amount,
+                out_amount: order.amount * RATE,
+                min_out_amount: order.amount * RATE,
+                slippage_bps: self.slippage_bps,
+                price_impact_pct: 0.0,
+                route: Value::Null,
+            })
+        }
+
+        async fn build_transaction(
+            &self,
+            quote: &SwapQuote,
+            taker: &Pubkey,
+        ) -> Result<SwapTransaction, Error> {
+            let instructions = vec![
+                solana_system_interface::instruction::transfer(taker, &self.pool, quote.in_amount),
+                create_associated_token_account_idempotent(
+                    taker,
+                    taker,
+                    &self.mint,
+                    &spl_token_interface::ID,
+                ),
+                spl_token_interface::instruction::mint_to(
+                    &spl_token_interface::ID,
+                    &self.mint,
+                    &get_associated_token_address(taker, &self.mint),
+                    taker,
+                    &[],
+                    quote.out_amount,
+                )
+                .expect("mint_to accepts the token program id"),
+            ];
+            let blockhash = self.blockhashes.get().await?;
+            let message = v0::Message::try_compile(taker, &instructions, &[], blockhash.hash)?;
+
+            Ok(SwapTransaction {
+                transaction: VersionedTransaction {
+                    signatures: vec![Default::default()],
+                    message: VersionedMessage::V0(message),
+                },
+                last_valid_block_height: Some(blockhash.last_valid_block_height),
+            })
+        }
+    }
+
+    fn order(mint: &Pubkey, amount: u64) -> SwapOrder {
+        SwapOrder {
+            input_mint: WSOL_MINT.to_owned(),
+            output_mint: mint.to_string(),
+            amount,
+            slippage_bps: 50,
+            max_price_impact_pct: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn swaps_sol_for_tokens_and_reads_the_fill() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallet = Keypair::new();
+        node.airdrop(&wallet.pubkey(), WALLET_LAMPORTS).unwrap();
+        let mint = mock_mint(&node, &wallet.pubkey(), 6);
+        let pool = Pubkey::new_unique();
+        let router = SwapRouter::new(vec![Arc::new(MintingPool {
+            pool,
+            mint,
+            blockhashes,
+            slippage_bps: 50,
+        })]);
+
+        let result = swap(
+            &rpc,
+            &router,
+            &order(&mint, 10_000_000),
+            &wallet,
+            websocket,
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        let rent = Rent::default().minimum_balance(token_account_len(&spl_token_interface::ID));
+        assert_eq!(result.provider, "minting_pool");
+        assert!(result.fill.err.is_none());
+        assert_eq!(result.fill.in_amount, 10_000_000);
+        assert_eq!(result.fill.out_amount, 10_000_000 * RATE);
+        assert_eq!(result.fill.fee_lamports, LAMPORTS_PER_SIGNATURE);
+        assert_eq!(result.fill.rent_lamports, rent as i64);
+        assert_eq!(node.balance(&pool), 10_000_000);
+        assert_eq!(
+            mock_token_amount(
+                &node,
+                &get_associated_token_address(&wallet.pubkey(), &mint)
+            ),
+            10_000_000 * RATE
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_quotes_outside_the_guards() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallet = Keypair::new();
+        node.airdrop(&wallet.pubkey(), WALLET_LAMPORTS).unwrap();
+        let mint = mock_mint(&node, &wallet.pubkey(), 6);
+        let router = SwapRouter::new(vec![Arc::new(MintingPool {
+            pool: Pubkey::new_unique(),
+            mint,
+            blockhashes,
+            slippage_bps: 100,
+        })]);
+
+        let result = swap(
+            &rpc,
+            &router,
+            &order(&mint, 10_000_000),
+            &wallet,
+            websocket,
+            CommitmentConfig::default(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(Error::SwapGuard(_))));
+        assert_eq!(node.balance(&wallet.pubkey()), WALLET_LAMPORTS);
+    }
+}
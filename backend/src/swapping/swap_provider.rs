@@ -0,0 +1,115 @@
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose};
+use serde_json::Value;
+use solana_sdk::{pubkey::Pubkey, transaction::VersionedTransaction};
+
+use crate::errors::errors::Error;
+
+/// Wrapped SOL, what aggregators use as the mint of native SOL.
+pub const WSOL_MINT: &str = "So11111111111111111111111111111111111111112";
+
+pub const DEFAULT_SLIPPAGE_BPS: u16 = 50;
+
+/// Swap `amount` base units of `input_mint` into `output_mint`.
+#[derive(Debug, Clone)]
+pub struct SwapOrder {
+    pub input_mint: String,
+    pub output_mint: String,
+    pub amount: u64,
+    /// Slippage the swap may have at most.
+    pub slippage_bps: u16,
+    /// Quotes moving the price by more than this many percent are not swapped.
+    pub max_price_impact_pct: Option<f64>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SwapQuote {
+    pub provider: &'static str,
+    pub input_mint: String,
+    pub output_mint: String,
+    pub in_amount: u64,
+    /// Expected output.
+    pub out_amount: u64,
+    /// Output below which the swap fails on chain.
+    pub min_out_amount: u64,
+    pub slippage_bps: u16,
+    /// In percent.
+    pub price_impact_pct: f64,
+    /// Provider specific response the transaction is built from.
+    pub route: Value,
+}
+
+impl SwapQuote {
+    /// Rejects quotes exceeding the order's slippage or price impact limits.
+    pub fn check_guards(&self, order: &SwapOrder) -> Result<(), Error> {
+        if self.slippage_bps > order.slippage_bps {
+            return Err(Error::SwapGuard(format!(
+                "{} quoted {} bps slippage, at most {} bps are allowed.",
+                self.provider, self.slippage_bps, order.slippage_bps
+            )));
+        }
+
+        if let Some(max_price_impact_pct) = order.max_price_impact_pct
+            && self.price_impact_pct > max_price_impact_pct
+        {
+            return Err(Error::SwapGuard(format!(
+                "{} quoted a price impact of {}%, at most {}% is allowed.",
+                self.provider, self.price_impact_pct, max_price_impact_pct
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// A provider's unsigned swap transaction.
+#[derive(Debug, Clone)]
+pub struct SwapTransaction {
+    pub transaction: VersionedTransaction,
+    /// Last block height the provider's blockhash is valid at, if the provider says.
+    pub last_valid_block_height: Option<u64>,
+}
+
+/// A way to swap one mint into another, e.g. an aggregator or a single AMM.
+#[async_trait]
+pub trait SwapProvider: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// `taker` is only needed by providers that build the transaction along with the quote.
+    async fn quote(&self, order: &SwapOrder, taker: Option<&Pubkey>) -> Result<SwapQuote, Error>;
+
+    /// Builds the unsigned swap transaction for a quote of this provider, paid by `taker`.
+    async fn build_transaction(
+        &self,
+        quote: &SwapQuote,
+        taker: &Pubkey,
+    ) -> Result<SwapTransaction, Error>;
+}
+
+pub(crate) fn parse_amount(value: &Value, field: &str) -> Result<u64, Error> {
+    value
+        .as_str()
+        .and_then(|s| s.parse::<u64>().ok())
+        .or_else(|| value.as_u64())
+        .ok_or_else(|| Error::Swap(format!("missing or invalid {} in quote", field)))
+}
+
+pub(crate) fn parse_decimal(value: &Value, field: &str) -> Result<f64, Error> {
+    value
+        .as_str()
+        .and_then(|s| s.parse::<f64>().ok())
+        .or_else(|| value.as_f64())
+        .ok_or_else(|| Error::Swap(format!("missing or invalid {} in quote", field)))
+}
+
+/// Jupiter sends the block height as a number on some endpoints and as a string on others.
+pub(crate) fn parse_block_height(value: &Value) -> Option<u64> {
+    value
+        .as_u64()
+        .or_else(|| value.as_str().and_then(|s| s.parse::<u64>().ok()))
+}
+
+pub(crate) fn decode_transaction(encoded: &str) -> Result<VersionedTransaction, Error> {
+    let decoded = general_purpose::STANDARD.decode(encoded)?;
+    Ok(bincode::deserialize(&decoded)?)
+}
@@ -0,0 +1,65 @@
+use std::collections::{BTreeMap, HashMap};
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::tokens::token_accounts::TokenAccount;
+
+/// Everything held of one mint, summed over the token accounts holding it.
+#[derive(Debug, Clone)]
+pub struct TokenBalance {
+    pub mint: Pubkey,
+    pub program_id: Pubkey,
+    /// In the mint's base units.
+    pub amount: u64,
+    pub decimals: u8,
+    /// Token accounts the amount is spread over.
+    pub accounts: usize,
+}
+
+impl TokenBalance {
+    pub fn ui_amount(&self) -> f64 {
+        self.amount as f64 / 10f64.powi(self.decimals as i32)
+    }
+
+    /// The exact amount in whole tokens, without trailing zeros.
+    pub fn ui_amount_string(&self) -> String {
+        let decimals = self.decimals as usize;
+        if decimals == 0 {
+            return self.amount.to_string();
+        }
+
+        let digits = format!("{:0>width$}", self.amount, width = decimals + 1);
+        let (whole, fraction) = digits.split_at(digits.len() - decimals);
+        match fraction.trim_end_matches('0') {
+            "" => whole.to_owned(),
+            fraction => format!("{}.{}", whole, fraction),
+        }
+    }
+}
+
+/// Sums the accounts per mint, ordered by mint. Accounts of mints without
+/// known decimals are left out.
+pub fn token_balances(
+    accounts: &[TokenAccount],
+    decimals: &HashMap<Pubkey, u8>,
+) -> Vec<TokenBalance> {
+    let mut balances: BTreeMap<String, TokenBalance> = BTreeMap::new();
+    for account in accounts {
+        let Some(decimals) = decimals.get(&account.mint) else {
+            continue;
+        };
+        let balance = balances
+            .entry(account.mint.to_string())
+            .or_insert_with(|| TokenBalance {
+                mint: account.mint,
+                program_id: account.program_id,
+                amount: 0,
+                decimals: *decimals,
+                accounts: 0,
+            });
+        balance.amount += account.amount;
+        balance.accounts += 1;
+    }
+
+    balances.into_values().collect()
+}
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+use spl_token_interface::state::Mint;
+
+use crate::{
+    Rpc,
+    errors::errors::Error,
+    rpc::{
+        commitment::Commitment,
+        read::{AccountEncoding, MAX_ACCOUNTS_PER_REQUEST, RpcAccount, get_multiple_accounts},
+    },
+    tokens::token_accounts::TOKEN_PROGRAM_IDS,
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct MintInfo {
+    pub mint: Pubkey,
+    /// Token or Token-2022, whichever owns the mint.
+    pub program_id: Pubkey,
+    pub decimals: u8,
+}
+
+impl MintInfo {
+    fn parse(mint: &Pubkey, account: &RpcAccount) -> Result<Self, Error> {
+        let not_a_mint = || Error::InvalidMint(format!("{} is not a mint", mint));
+
+        let program_id = TOKEN_PROGRAM_IDS
+            .into_iter()
+            .find(|p| p.to_string() == account.owner)
+            .ok_or_else(not_a_mint)?;
+        let data = account.decoded_data()?;
+        let state = data
+            .get(..Mint::LEN)
+            .and_then(|base| Mint::unpack(base).ok())
+            .ok_or_else(not_a_mint)?;
+
+        Ok(Self {
+            mint: *mint,
+            program_id,
+            decimals: state.decimals,
+        })
+    }
+}
+
+/// Fetches the given mints. Mints that don't exist are left out.
+pub async fn get_mints(
+    rpc: &Rpc,
+    mints: &[Pubkey],
+    commitment: Commitment,
+) -> Result<HashMap<Pubkey, MintInfo>, Error> {
+    let mut infos = HashMap::new();
+    for chunk in mints.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+        let pubkeys: Vec<String> = chunk.iter().map(|m| m.to_string()).collect();
+        let accounts = get_multiple_accounts(
+            rpc,
+            "get_mints",
+            &pubkeys,
+            Some(AccountEncoding::Base64),
+            commitment,
+        )
+        .await?;
+
+        for (mint, account) in chunk.iter().zip(accounts.iter()) {
+            if let Some(account) = account {
+                infos.insert(*mint, MintInfo::parse(mint, account)?);
+            }
+        }
+    }
+
+    Ok(infos)
+}
+
+/// Fetches a single mint, failing if it doesn't exist.
+pub async fn get_mint(rpc: &Rpc, mint: &Pubkey, commitment: Commitment) -> Result<MintInfo, Error> {
+    get_mints(rpc, &[*mint], commitment)
+        .await?
+        .remove(mint)
+        .ok_or_else(|| Error::InvalidMint(format!("Mint {} does not exist", mint)))
+}
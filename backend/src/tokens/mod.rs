@@ -0,0 +1,5 @@
+pub mod balances;
+pub mod mints;
+pub mod reclaiming;
+pub mod token_accounts;
+pub mod wsol;
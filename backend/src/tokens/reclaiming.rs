@@ -0,0 +1,243 @@
+use solana_sdk::{pubkey::Pubkey, signature::Keypair, signer::Signer};
+
+use crate::{
+    Blockhashes, Rpc, Websocket,
+    config::CommitmentConfig,
+    errors::errors::Error,
+    rpc::send::send_and_confirm,
+    tokens::{mints::get_mints, token_accounts::get_token_accounts},
+    txn_factory::{
+        simulation::{SimulationReport, simulate},
+        token_transfer::{
+            ClosedTokenAccount, batch_token_account_closes, build_token_account_closes,
+        },
+    },
+};
+
+/// One transaction of a rent reclaim and how it went.
+pub struct ReclaimBatch<T> {
+    pub accounts: Vec<ClosedTokenAccount>,
+    pub result: Result<T, Error>,
+}
+
+impl<T> ReclaimBatch<T> {
+    pub fn lamports(&self) -> u64 {
+        self.accounts.iter().map(|a| a.lamports).sum()
+    }
+}
+
+/// The owner's token accounts worth closing, batched into transactions. Empty accounts
+/// are always closed, ones holding at most `dust_amount` base units have it burned first.
+/// Wrapped SOL with a balance, frozen accounts and dust of mints that can't be found
+/// are left alone.
+async fn plan_reclaim(
+    rpc: &Rpc,
+    owner: &Pubkey,
+    fee_payer: Option<&Keypair>,
+    dust_amount: Option<u64>,
+    commitment: CommitmentConfig,
+) -> Result<Vec<Vec<ClosedTokenAccount>>, Error> {
+    let closable: Vec<_> = get_token_accounts(rpc, owner, commitment.read)
+        .await?
+        .into_iter()
+        .filter(|a| !a.frozen)
+        .filter(|a| a.amount == 0 || (!a.native && dust_amount.is_some_and(|d| a.amount <= d)))
+        .collect();
+
+    let dusty_mints: Vec<Pubkey> = closable
+        .iter()
+        .filter(|a| a.amount > 0)
+        .map(|a| a.mint)
+        .collect();
+    let mints = get_mints(rpc, &dusty_mints, commitment.read).await?;
+
+    let mut accounts: Vec<ClosedTokenAccount> = Vec::new();
+    for a in &closable {
+        // Burning is checked against the mint's decimals, empty accounts don't burn.
+        let decimals = match mints.get(&a.mint) {
+            Some(mint) => mint.decimals,
+            None if a.amount == 0 => 0,
+            None => {
+                eprintln!(
+                    "Not closing {}, its mint {} could not be found to burn {}.",
+                    a.pubkey, a.mint, a.amount
+                );
+                continue;
+            }
+        };
+        accounts.push(ClosedTokenAccount {
+            pubkey: a.pubkey,
+            mint: a.mint,
+            program_id: a.program_id,
+            decimals,
+            burn: a.amount,
+            lamports: a.lamports,
+        });
+    }
+
+    let fee_payer = fee_payer.map(|f| f.pubkey());
+    Ok(batch_token_account_closes(
+        owner,
+        fee_payer.as_ref(),
+        &accounts,
+    ))
+}
+
+/// Closes the wallet's empty token accounts, and dusty ones if `dust_amount` is set, so
+/// their rent returns to the wallet. Batches are sent one after another, a failed batch
+/// doesn't stop the rest. A `fee_payer` pays the fees, so wallets without SOL can close too.
+pub async fn reclaim_rent(
+    rpc: &Rpc,
+    websocket_service: Websocket,
+    blockhashes: Blockhashes,
+    wallet: &Keypair,
+    fee_payer: Option<&Keypair>,
+    dust_amount: Option<u64>,
+    commitment: CommitmentConfig,
+) -> Result<Vec<ReclaimBatch<String>>, Error> {
+    let batches = plan_reclaim(rpc, &wallet.pubkey(), fee_payer, dust_amount, commitment).await?;
+
+    let mut results = Vec::new();
+    for accounts in batches {
+        let result = async {
+            let blockhash = blockhashes.get().await?;
+            let (latest_hash, last_valid_block_height) =
+                (blockhash.hash, blockhash.last_valid_block_height);
+            let txn =
+                build_token_account_closes(wallet, fee_payer, &accounts, &latest_hash).await?;
+
+            let txn_hash = send_and_confirm(
+                rpc,
+                websocket_service.clone(),
+                "reclaim_rent",
+                &txn,
+                last_valid_block_height,
+                commitment,
+            )
+            .await?;
+            println!("Reclaimed rent: {}", txn_hash);
+
+            Ok(txn_hash)
+        }
+        .await;
+
+        results.push(ReclaimBatch { accounts, result });
+    }
+
+    Ok(results)
+}
+
+/// Builds the same transactions as `reclaim_rent` and simulates them instead of sending.
+pub async fn dry_run_reclaim_rent(
+    rpc: &Rpc,
+    blockhashes: Blockhashes,
+    wallet: &Keypair,
+    fee_payer: Option<&Keypair>,
+    dust_amount: Option<u64>,
+    commitment: CommitmentConfig,
+) -> Result<Vec<ReclaimBatch<SimulationReport>>, Error> {
+    let batches = plan_reclaim(rpc, &wallet.pubkey(), fee_payer, dust_amount, commitment).await?;
+    let latest_hash = blockhashes.get().await?.hash;
+
+    let mut results = Vec::new();
+    for accounts in batches {
+        let result = async {
+            let txn =
+                build_token_account_closes(wallet, fee_payer, &accounts, &latest_hash).await?;
+            simulate(rpc, "reclaim_rent_dry_run", &txn, commitment).await
+        }
+        .await;
+
+        results.push(ReclaimBatch { accounts, result });
+    }
+
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use mock_node::MockNode;
+    use solana_sdk::rent::Rent;
+
+    use super::*;
+    use crate::{
+        rpc::mock::{mock_mint, mock_services, mock_token_account},
+        tokens::token_accounts::token_account_len,
+        txn_factory::util::LAMPORTS_PER_SIGNATURE,
+    };
+
+    const WALLET_LAMPORTS: u64 = 1_000_000;
+
+    #[tokio::test]
+    async fn closes_empty_and_dusty_accounts_only() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallet = Keypair::new();
+        node.airdrop(&wallet.pubkey(), WALLET_LAMPORTS).unwrap();
+        let authority = Pubkey::new_unique();
+        let empty =
+            mock_token_account(&node, &wallet.pubkey(), &mock_mint(&node, &authority, 6), 0);
+        let dusty =
+            mock_token_account(&node, &wallet.pubkey(), &mock_mint(&node, &authority, 6), 5);
+        let held = mock_token_account(
+            &node,
+            &wallet.pubkey(),
+            &mock_mint(&node, &authority, 6),
+            500,
+        );
+
+        let batches = reclaim_rent(
+            &rpc,
+            websocket,
+            blockhashes,
+            &wallet,
+            None,
+            Some(10),
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        assert!(batches.iter().all(|b| b.result.is_ok()));
+        assert!(node.account(&empty).is_none());
+        assert!(node.account(&dusty).is_none());
+        assert!(node.account(&held).is_some());
+        let rent = Rent::default().minimum_balance(token_account_len(&spl_token_interface::ID));
+        assert_eq!(
+            node.balance(&wallet.pubkey()),
+            WALLET_LAMPORTS + 2 * rent - batches.len() as u64 * LAMPORTS_PER_SIGNATURE
+        );
+    }
+
+    #[tokio::test]
+    async fn sponsored_reclaim_works_without_sol() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallet = Keypair::new();
+        let fee_payer = Keypair::new();
+        node.airdrop(&fee_payer.pubkey(), WALLET_LAMPORTS).unwrap();
+        let mint = mock_mint(&node, &Pubkey::new_unique(), 6);
+        let empty = mock_token_account(&node, &wallet.pubkey(), &mint, 0);
+
+        let batches = reclaim_rent(
+            &rpc,
+            websocket,
+            blockhashes,
+            &wallet,
+            Some(&fee_payer),
+            None,
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(batches.len(), 1);
+        assert!(batches[0].result.is_ok());
+        assert!(node.account(&empty).is_none());
+        assert_eq!(node.balance(&wallet.pubkey()), batches[0].lamports());
+        assert_eq!(
+            node.balance(&fee_payer.pubkey()),
+            WALLET_LAMPORTS - 2 * LAMPORTS_PER_SIGNATURE
+        );
+    }
+}
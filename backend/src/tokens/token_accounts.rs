@@ -0,0 +1,99 @@
+use std::str::FromStr;
+
+use solana_sdk::{program_pack::Pack, pubkey::Pubkey};
+use spl_token_interface::state::Account as SplTokenAccount;
+
+use crate::{
+    Rpc,
+    errors::errors::Error,
+    rpc::{
+        commitment::Commitment,
+        read::{RpcAccount, get_token_accounts_by_owner},
+    },
+};
+
+pub const TOKEN_2022_PROGRAM_ID: Pubkey =
+    Pubkey::from_str_const("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Token and Token-2022 share the account and mint layouts, the latter
+/// only appends extensions.
+pub const TOKEN_PROGRAM_IDS: [Pubkey; 2] = [spl_token_interface::ID, TOKEN_2022_PROGRAM_ID];
+
+/// Size of an associated token account of the program. Token-2022 ones carry the
+/// immutable owner extension, mints requiring further account extensions need more.
+pub fn token_account_len(program_id: &Pubkey) -> usize {
+    if *program_id == TOKEN_2022_PROGRAM_ID {
+        SplTokenAccount::LEN + 5
+    } else {
+        SplTokenAccount::LEN
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TokenAccount {
+    pub pubkey: Pubkey,
+    pub mint: Pubkey,
+    /// In the mint's base units.
+    pub amount: u64,
+    pub program_id: Pubkey,
+    pub lamports: u64,
+    /// Wrapped SOL, closing it unwraps the amount.
+    pub native: bool,
+    /// Frozen by the mint's freeze authority, can't be burned or closed.
+    pub frozen: bool,
+}
+
+impl TokenAccount {
+    pub fn parse(pubkey: &str, account: &RpcAccount, program_id: Pubkey) -> Result<Self, Error> {
+        let invalid = |reason: String| {
+            Error::RpcError(format!("Invalid token account {}: {}", pubkey, reason))
+        };
+
+        let data = account.decoded_data()?;
+        let state = data
+            .get(..SplTokenAccount::LEN)
+            .ok_or_else(|| invalid(format!("{} bytes of data", data.len())))
+            .and_then(|base| {
+                SplTokenAccount::unpack(base).map_err(|err| invalid(err.to_string()))
+            })?;
+
+        Ok(Self {
+            pubkey: Pubkey::from_str(pubkey)?,
+            mint: state.mint,
+            amount: state.amount,
+            program_id,
+            lamports: account.lamports,
+            native: state.is_native(),
+            frozen: state.is_frozen(),
+        })
+    }
+}
+
+/// Every Token and Token-2022 account of `owner`, including empty ones.
+pub async fn get_token_accounts(
+    rpc: &Rpc,
+    owner: &Pubkey,
+    commitment: Commitment,
+) -> Result<Vec<TokenAccount>, Error> {
+    let mut token_accounts = Vec::new();
+    for program_id in TOKEN_PROGRAM_IDS {
+        let accounts = get_token_accounts_by_owner(
+            rpc,
+            "token_accounts",
+            &owner.to_string(),
+            &program_id.to_string(),
+            commitment,
+        )
+        .await?;
+
+        for keyed in accounts {
+            token_accounts.push(TokenAccount::parse(
+                &keyed.pubkey,
+                &keyed.account,
+                program_id,
+            )?);
+        }
+    }
+
+    Ok(token_accounts)
+}
@@ -0,0 +1,272 @@
+use solana_sdk::{signature::Keypair, signer::Signer};
+
+use crate::{
+    Blockhashes, Rpc, Websocket,
+    config::CommitmentConfig,
+    errors::errors::Error,
+    rpc::{
+        read::{get_balance, get_minimum_balance_for_rent_exemption, get_multiple_accounts},
+        send::send_and_confirm,
+    },
+    tokens::token_accounts::{get_token_accounts, token_account_len},
+    txn_factory::{
+        rent::{PlannedTransfers, check_rent_exemption},
+        util::LAMPORTS_PER_SIGNATURE,
+        wsol::{build_unwrap_sol, build_wrap_sol, wsol_account},
+    },
+};
+
+pub struct Unwrapped {
+    pub signature: String,
+    /// The wrapped amount returned as native SOL.
+    pub lamports: u64,
+    /// Rent of the closed wSOL accounts, also returned.
+    pub rent_lamports: u64,
+}
+
+/// Wraps `lamports` of the wallet's SOL into its wSOL account. The wallet also has to
+/// cover the fee and, when the account doesn't exist yet, its rent, unless a `fee_payer`
+/// pays both.
+pub async fn wrap_sol(
+    rpc: &Rpc,
+    websocket_service: Websocket,
+    blockhashes: Blockhashes,
+    wallet: &Keypair,
+    fee_payer: Option<&Keypair>,
+    lamports: u64,
+    commitment: CommitmentConfig,
+) -> Result<String, Error> {
+    let pubkey = wallet.pubkey().to_string();
+    let accounts = get_multiple_accounts(
+        rpc,
+        "wrap_sol",
+        &vec![pubkey.clone(), wsol_account(&wallet.pubkey()).to_string()],
+        None,
+        commitment.read,
+    )
+    .await?;
+
+    let balance = match accounts.first() {
+        Some(Some(account)) if !account.is_plain_system_account() => {
+            return Err(Error::CompromisedAccount(format!(
+                "Wallet {} is owned by {} and holds {} bytes of data.",
+                pubkey, account.owner, account.space
+            )));
+        }
+        Some(Some(account)) => account.lamports,
+        _ => 0,
+    };
+    let rent = match accounts.get(1) {
+        Some(Some(_)) => 0,
+        _ => {
+            get_minimum_balance_for_rent_exemption(
+                rpc,
+                "wrap_sol",
+                token_account_len(&spl_token_interface::ID) as u128,
+                commitment.read,
+            )
+            .await? as u64
+        }
+    };
+
+    let mut assumed_balances = Vec::new();
+    let moved = match fee_payer {
+        None => {
+            let needed = lamports as u128 + rent as u128 + LAMPORTS_PER_SIGNATURE as u128;
+            if (balance as u128) < needed {
+                return Err(Error::InsufficientSol(format!(
+                    "Need {} lamports to wrap {} but got {} inside wallet {}.",
+                    needed, lamports, balance, pubkey
+                )));
+            }
+            lamports + rent
+        }
+        Some(fee_payer) => {
+            if balance < lamports {
+                return Err(Error::InsufficientSol(format!(
+                    "Need {} lamports to wrap but got {} inside wallet {}.",
+                    lamports, balance, pubkey
+                )));
+            }
+            let fee_payer_balance = get_balance(
+                rpc,
+                "wrap_sol",
+                &fee_payer.pubkey().to_string(),
+                commitment.read,
+            )
+            .await?;
+            let needed = 2 * LAMPORTS_PER_SIGNATURE + rent;
+            if fee_payer_balance < needed {
+                return Err(Error::InsufficientSol(format!(
+                    "Need {} lamports for the fee and rent but got {} inside fee payer {}.",
+                    needed,
+                    fee_payer_balance,
+                    fee_payer.pubkey()
+                )));
+            }
+            // The rent it puts into the new wSOL account is moved up front.
+            assumed_balances.push((fee_payer.pubkey(), fee_payer_balance - rent));
+            if rent > 0 {
+                assumed_balances.push((wsol_account(&wallet.pubkey()), rent));
+            }
+            lamports
+        }
+    };
+
+    // The wSOL account ends above the minimum, only the payers can be left short.
+    let moved = [(wsol_account(&wallet.pubkey()), moved)];
+    let planned = [PlannedTransfers {
+        payer: wallet.pubkey(),
+        fee_payer: fee_payer.map(|f| f.pubkey()),
+        transfers: &moved,
+    }];
+    check_rent_exemption(rpc, &planned, &assumed_balances, commitment).await?;
+
+    let blockhash = blockhashes.get().await?;
+    let (latest_hash, last_valid_block_height) =
+        (blockhash.hash, blockhash.last_valid_block_height);
+    let txn = build_wrap_sol(wallet, fee_payer, lamports, &latest_hash).await?;
+    let txn_hash = send_and_confirm(
+        rpc,
+        websocket_service,
+        "wrap_sol",
+        &txn,
+        last_valid_block_height,
+        commitment,
+    )
+    .await?;
+    println!("Wrapped SOL: {}", txn_hash);
+
+    Ok(txn_hash)
+}
+
+/// Closes every wSOL account of the wallet. `None` if it has none. A `fee_payer` pays the fee.
+pub async fn unwrap_sol(
+    rpc: &Rpc,
+    websocket_service: Websocket,
+    blockhashes: Blockhashes,
+    wallet: &Keypair,
+    fee_payer: Option<&Keypair>,
+    commitment: CommitmentConfig,
+) -> Result<Option<Unwrapped>, Error> {
+    let wsol_accounts: Vec<_> = get_token_accounts(rpc, &wallet.pubkey(), commitment.read)
+        .await?
+        .into_iter()
+        .filter(|a| a.native && !a.frozen)
+        .collect();
+    if wsol_accounts.is_empty() {
+        return Ok(None);
+    }
+
+    let closes: Vec<_> = wsol_accounts
+        .iter()
+        .map(|a| (a.pubkey, a.program_id))
+        .collect();
+    let blockhash = blockhashes.get().await?;
+    let (latest_hash, last_valid_block_height) =
+        (blockhash.hash, blockhash.last_valid_block_height);
+    let txn = build_unwrap_sol(wallet, fee_payer, &closes, &latest_hash).await?;
+    let txn_hash = send_and_confirm(
+        rpc,
+        websocket_service,
+        "unwrap_sol",
+        &txn,
+        last_valid_block_height,
+        commitment,
+    )
+    .await?;
+    println!("Unwrapped SOL: {}", txn_hash);
+
+    let lamports: u64 = wsol_accounts.iter().map(|a| a.amount).sum();
+    let total: u64 = wsol_accounts.iter().map(|a| a.lamports).sum();
+    Ok(Some(Unwrapped {
+        signature: txn_hash,
+        lamports,
+        rent_lamports: total.saturating_sub(lamports),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use mock_node::MockNode;
+    use solana_sdk::rent::Rent;
+
+    use super::*;
+    use crate::rpc::mock::{mock_services, mock_token_amount};
+
+    const WALLET_LAMPORTS: u64 = 10_000_000;
+
+    #[tokio::test]
+    async fn wraps_and_unwraps_back_to_native_sol() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallet = Keypair::new();
+        node.airdrop(&wallet.pubkey(), WALLET_LAMPORTS).unwrap();
+
+        wrap_sol(
+            &rpc,
+            websocket.clone(),
+            Arc::clone(&blockhashes),
+            &wallet,
+            None,
+            4_000_000,
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap();
+
+        let rent = Rent::default().minimum_balance(token_account_len(&spl_token_interface::ID));
+        assert_eq!(
+            mock_token_amount(&node, &wsol_account(&wallet.pubkey())),
+            4_000_000
+        );
+        assert_eq!(
+            node.balance(&wallet.pubkey()),
+            WALLET_LAMPORTS - 4_000_000 - rent - LAMPORTS_PER_SIGNATURE
+        );
+
+        let unwrapped = unwrap_sol(
+            &rpc,
+            websocket,
+            blockhashes,
+            &wallet,
+            None,
+            CommitmentConfig::default(),
+        )
+        .await
+        .unwrap()
+        .unwrap();
+
+        assert_eq!(unwrapped.lamports, 4_000_000);
+        assert_eq!(unwrapped.rent_lamports, rent);
+        assert!(node.account(&wsol_account(&wallet.pubkey())).is_none());
+        assert_eq!(
+            node.balance(&wallet.pubkey()),
+            WALLET_LAMPORTS - 2 * LAMPORTS_PER_SIGNATURE
+        );
+    }
+
+    #[tokio::test]
+    async fn refuses_to_wrap_more_than_the_wallet_holds() {
+        let node = MockNode::new();
+        let (rpc, websocket, blockhashes) = mock_services(&node).await;
+        let wallet = Keypair::new();
+        node.airdrop(&wallet.pubkey(), WALLET_LAMPORTS).unwrap();
+
+        let result = wrap_sol(
+            &rpc,
+            websocket,
+            blockhashes,
+            &wallet,
+            None,
+            WALLET_LAMPORTS,
+            CommitmentConfig::default(),
+        )
+        .await;
+
+        assert!(matches!(result, Err(Error::InsufficientSol(_))));
+        assert!(node.account(&wsol_account(&wallet.pubkey())).is_none());
+    }
+}
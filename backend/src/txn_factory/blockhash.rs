@@ -1,11 +1,131 @@
-use std::str::FromStr;
+use std::{
+    collections::VecDeque,
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, Instant},
+};
 
 use solana_sdk::hash::Hash;
+use tokio::{
+    sync::RwLock,
+    time::{MissedTickBehavior, interval},
+};
 
-use crate::{errors::errors::Error, rpc::read::get_latest_blockhash};
+use crate::{
+    Rpc,
+    errors::errors::Error,
+    rpc::{commitment::Commitment, read::get_latest_blockhash},
+};
 
-pub async fn get_blockhash(rpc_url: &str, request_id: &str) -> Result<Hash, Error> {
-    let latest_blockhash = get_latest_blockhash(rpc_url, request_id).await?;
-    let parsed_hash = Hash::from_str(&latest_blockhash.blockhash)?;
-    Ok(parsed_hash)
+/// Target slot time of the cluster.
+pub const SLOT_DURATION: Duration = Duration::from_millis(400);
+/// The cache refreshes every this many slots.
+const REFRESH_SLOTS: u32 = 5;
+/// A blockhash stays usable for this many blocks after the block height it was fetched at.
+pub const MAX_PROCESSING_AGE: u64 = 150;
+/// Handed out hashes that are remembered to answer `is_valid`, enough to cover their lifetime.
+const HISTORY_LEN: usize = (MAX_PROCESSING_AGE / REFRESH_SLOTS as u64) as usize + 1;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CachedBlockhash {
+    pub hash: Hash,
+    pub last_valid_block_height: u64,
+}
+
+struct BlockhashState {
+    /// Oldest first, the back is the latest blockhash.
+    history: VecDeque<CachedBlockhash>,
+    block_height: u64,
+    refreshed_at: Instant,
+}
+
+/// Keeps a recent blockhash around so signers don't each fetch their own.
+pub struct BlockhashCache {
+    rpc: Rpc,
+    commitment: Commitment,
+    refresh_interval: Duration,
+    state: RwLock<Option<BlockhashState>>,
+}
+
+impl BlockhashCache {
+    /// Creates the cache and starts refreshing it in the background.
+    pub fn spawn(rpc: Rpc, commitment: Commitment) -> Arc<Self> {
+        let cache = Arc::new(Self {
+            rpc,
+            commitment,
+            refresh_interval: SLOT_DURATION * REFRESH_SLOTS,
+            state: RwLock::new(None),
+        });
+
+        let refresher = Arc::clone(&cache);
+        tokio::spawn(async move {
+            let mut ticker = interval(refresher.refresh_interval);
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                if let Err(err) = refresher.refresh().await {
+                    eprintln!("Failed to refresh blockhash: {}", err);
+                }
+            }
+        });
+
+        cache
+    }
+
+    async fn refresh(&self) -> Result<CachedBlockhash, Error> {
+        let latest = get_latest_blockhash(&self.rpc, "blockhash_cache", self.commitment).await?;
+        let blockhash = CachedBlockhash {
+            hash: Hash::from_str(&latest.blockhash)?,
+            last_valid_block_height: latest.last_valid_block_height,
+        };
+
+        let mut state = self.state.write().await;
+        let state = state.get_or_insert_with(|| BlockhashState {
+            history: VecDeque::new(),
+            block_height: 0,
+            refreshed_at: Instant::now(),
+        });
+        if state.history.back().map(|b| b.hash) != Some(blockhash.hash) {
+            state.history.push_back(blockhash);
+            if state.history.len() > HISTORY_LEN {
+                state.history.pop_front();
+            }
+        }
+        state.block_height = blockhash
+            .last_valid_block_height
+            .saturating_sub(MAX_PROCESSING_AGE);
+        state.refreshed_at = Instant::now();
+
+        Ok(blockhash)
+    }
+
+    /// The blockhash to sign with. Fetched on the spot when the background
+    /// refresh has fallen behind, e.g. because the RPC failed.
+    pub async fn get(&self) -> Result<CachedBlockhash, Error> {
+        {
+            let state = self.state.read().await;
+            if let Some(state) = state.as_ref()
+                && state.refreshed_at.elapsed() < self.refresh_interval * 2
+                && let Some(latest) = state.history.back()
+            {
+                return Ok(*latest);
+            }
+        }
+
+        self.refresh().await
+    }
+
+    /// Whether a transaction signed with `hash` can still land, judged by the block
+    /// height at the last refresh. Hashes this cache didn't hand out count as invalid.
+    pub async fn is_valid(&self, hash: &Hash) -> bool {
+        let state = self.state.read().await;
+        let Some(state) = state.as_ref() else {
+            return false;
+        };
+
+        state
+            .history
+            .iter()
+            .any(|b| &b.hash == hash && b.last_valid_block_height > state.block_height)
+    }
 }
@@ -1,4 +1,7 @@
 pub mod blockhash;
-pub mod swaps;
+pub mod rent;
+pub mod simulation;
+pub mod token_transfer;
 pub mod transfer;
 pub mod util;
+pub mod wsol;
@@ -0,0 +1,146 @@
+use std::collections::HashMap;
+
+use solana_sdk::pubkey::Pubkey;
+
+use crate::{
+    Rpc,
+    config::CommitmentConfig,
+    errors::errors::Error,
+    rpc::read::{
+        MAX_ACCOUNTS_PER_REQUEST, get_minimum_balance_for_rent_exemption, get_multiple_accounts,
+    },
+    txn_factory::util::LAMPORTS_PER_SIGNATURE,
+};
+
+/// A planned transaction of SOL transfers, `payer` signs it and sends. It pays the fee
+/// too unless a `fee_payer` sponsors it.
+#[derive(Debug, Clone, Copy)]
+pub struct PlannedTransfers<'a> {
+    pub payer: Pubkey,
+    pub fee_payer: Option<Pubkey>,
+    pub transfers: &'a [(Pubkey, u64)],
+}
+
+/// Takes `lamports` from `pubkey`, reporting an overdraw or a balance left below `rent_minimum`.
+fn debit(
+    balances: &mut HashMap<Pubkey, u64>,
+    pubkey: &Pubkey,
+    lamports: u64,
+    what: &str,
+    rent_minimum: u64,
+) -> Option<String> {
+    let balance = balances.entry(*pubkey).or_default();
+    let Some(left) = balance.checked_sub(lamports) else {
+        return Some(format!(
+            "Wallet {} would pay {} lamports for {} but holds {}.",
+            pubkey, lamports, what, balance
+        ));
+    };
+    *balance = left;
+    if left > 0 && left < rent_minimum {
+        return Some(format!(
+            "Wallet {} would be left with {} lamports, below the rent-exempt minimum of {}. Send {} lamports less or empty it.",
+            pubkey,
+            left,
+            rent_minimum,
+            rent_minimum - left
+        ));
+    }
+
+    None
+}
+
+/// Applies the transactions in order and reports the first account left with more than
+/// zero but less than `rent_minimum` lamports, which the runtime rejects.
+fn find_rent_violation(
+    planned: &[PlannedTransfers],
+    balances: &mut HashMap<Pubkey, u64>,
+    rent_minimum: u64,
+) -> Option<String> {
+    for transaction in planned {
+        let sent: u64 = transaction.transfers.iter().map(|(_, l)| l).sum();
+        let debits = match transaction.fee_payer {
+            // Both signatures are charged to the fee payer.
+            Some(fee_payer) if fee_payer != transaction.payer => vec![
+                (fee_payer, 2 * LAMPORTS_PER_SIGNATURE, "the fee"),
+                (transaction.payer, sent, "transfers"),
+            ],
+            _ => vec![(
+                transaction.payer,
+                sent + LAMPORTS_PER_SIGNATURE,
+                "transfers and the fee",
+            )],
+        };
+        for (pubkey, lamports, what) in debits {
+            if let Some(violation) = debit(balances, &pubkey, lamports, what, rent_minimum) {
+                return Some(violation);
+            }
+        }
+
+        for (to, lamports) in transaction.transfers {
+            let balance = balances.entry(*to).or_default();
+            *balance += lamports;
+            if *balance < rent_minimum {
+                return Some(format!(
+                    "Wallet {} would end with {} lamports, below the rent-exempt minimum of {}. Send it at least {} lamports more.",
+                    to,
+                    balance,
+                    rent_minimum,
+                    rent_minimum - *balance
+                ));
+            }
+        }
+    }
+
+    None
+}
+
+/// Checks that every account the transactions touch ends at zero or at least the
+/// rent-exempt minimum of a 0-byte account. Run before signing, balances are read
+/// at `commitment.read` unless given in `assumed_balances`, e.g. for a funding
+/// wallet that isn't funded yet.
+pub async fn check_rent_exemption(
+    rpc: &Rpc,
+    planned: &[PlannedTransfers<'_>],
+    assumed_balances: &[(Pubkey, u64)],
+    commitment: CommitmentConfig,
+) -> Result<(), Error> {
+    let mut pubkeys: Vec<String> = Vec::new();
+    for transaction in planned {
+        for pubkey in std::iter::once(&transaction.payer)
+            .chain(transaction.fee_payer.iter())
+            .chain(transaction.transfers.iter().map(|(to, _)| to))
+        {
+            let pubkey = pubkey.to_string();
+            if !pubkeys.contains(&pubkey) {
+                pubkeys.push(pubkey);
+            }
+        }
+    }
+
+    let mut balances: HashMap<Pubkey, u64> = HashMap::new();
+    for chunk in pubkeys.chunks(MAX_ACCOUNTS_PER_REQUEST) {
+        let accounts = get_multiple_accounts(
+            rpc,
+            "check_rent_exemption",
+            &chunk.to_vec(),
+            None,
+            commitment.read,
+        )
+        .await?;
+        for (pubkey, account) in chunk.iter().zip(accounts.iter()) {
+            let lamports = account.as_ref().map(|a| a.lamports).unwrap_or(0);
+            balances.insert(pubkey.parse()?, lamports);
+        }
+    }
+    balances.extend(assumed_balances.iter().copied());
+
+    let rent_minimum =
+        get_minimum_balance_for_rent_exemption(rpc, "check_rent_exemption", 0, commitment.read)
+            .await? as u64;
+
+    match find_rent_violation(planned, &mut balances, rent_minimum) {
+        Some(violation) => Err(Error::RentExemption(violation)),
+        None => Ok(()),
+    }
+}
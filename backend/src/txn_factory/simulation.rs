@@ -0,0 +1,98 @@
+use base64::{Engine, engine::general_purpose};
+use serde_json::Value;
+use solana_sdk::transaction::VersionedTransaction;
+
+use crate::{
+    Rpc,
+    config::CommitmentConfig,
+    errors::errors::Error,
+    rpc::{
+        read::{get_fee_for_message, get_multiple_accounts},
+        send::simulate_transaction,
+    },
+    txn_factory::util::SimpleTransaction,
+};
+
+pub struct BalanceChange {
+    pub pubkey: String,
+    pub pre_lamports: u64,
+    pub post_lamports: u64,
+}
+
+pub struct SimulationReport {
+    pub signature: String,
+    /// `None` when the blockhash expired before the fee could be quoted.
+    pub fee: Option<u64>,
+    pub balance_changes: Vec<BalanceChange>,
+    pub compute_units: u64,
+    pub logs: Vec<String>,
+    pub err: Option<String>,
+}
+
+/// Simulates a signed transaction against the current state, nothing is sent.
+/// Every transaction is simulated on its own, effects of others in the same batch are not seen.
+pub async fn simulate(
+    rpc: &Rpc,
+    request_id: &str,
+    txn: &SimpleTransaction,
+    commitment: CommitmentConfig,
+) -> Result<SimulationReport, Error> {
+    let decoded = bs58::decode(&txn.transaction).into_vec()?;
+    let versioned: VersionedTransaction = bincode::deserialize(&decoded)?;
+    let account_pubkeys: Vec<String> = versioned
+        .message
+        .static_account_keys()
+        .iter()
+        .map(|k| k.to_string())
+        .collect();
+
+    let pre_accounts =
+        get_multiple_accounts(rpc, request_id, &account_pubkeys, None, commitment.read).await?;
+    let simulated = simulate_transaction(
+        rpc,
+        request_id,
+        &txn.transaction,
+        &account_pubkeys,
+        commitment.read,
+    )
+    .await?;
+
+    let message = general_purpose::STANDARD.encode(versioned.message.serialize());
+    let fee = get_fee_for_message(rpc, request_id, &message, commitment.blockhash).await?;
+
+    let post_accounts = simulated.accounts.unwrap_or_default();
+    let mut balance_changes = Vec::new();
+    for (i, pubkey) in account_pubkeys.iter().enumerate() {
+        let pre_lamports = pre_accounts
+            .get(i)
+            .and_then(|a| a.as_ref())
+            .map(|a| a.lamports)
+            .unwrap_or(0);
+        let post_lamports = match post_accounts.get(i) {
+            Some(account) => account.as_ref().map(|a| a.lamports).unwrap_or(0),
+            None => pre_lamports,
+        };
+
+        if pre_lamports != post_lamports {
+            balance_changes.push(BalanceChange {
+                pubkey: pubkey.to_owned(),
+                pre_lamports,
+                post_lamports,
+            });
+        }
+    }
+
+    let err = simulated.err.map(|err| match err {
+        Value::String(s) => s,
+        other => other.to_string(),
+    });
+
+    Ok(SimulationReport {
+        signature: txn.signature.clone(),
+        fee,
+        balance_changes,
+        compute_units: simulated.units_consumed.unwrap_or(0),
+        logs: simulated.logs.unwrap_or_default(),
+        err,
+    })
+}
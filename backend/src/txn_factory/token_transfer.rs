@@ -0,0 +1,252 @@
+use solana_sdk::{
+    hash::Hash, instruction::Instruction, pubkey::Pubkey, signature::Keypair, signer::Signer,
+    transaction::Transaction,
+};
+use spl_associated_token_account_interface::{
+    address::get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+
+use crate::{
+    errors::errors::Error,
+    tokens::mints::MintInfo,
+    txn_factory::util::{self, batch_by_size, encode_transaction},
+};
+
+#[derive(Debug, Clone, Copy)]
+pub struct TokenRecipient {
+    pub owner: Pubkey,
+    /// The owner has no associated token account for the mint yet.
+    pub create_token_account: bool,
+}
+
+fn recipient_instructions(
+    payer: &Pubkey,
+    mint: &MintInfo,
+    recipient: &TokenRecipient,
+    amount: u64,
+) -> Vec<Instruction> {
+    let source = get_associated_token_address_with_program_id(payer, &mint.mint, &mint.program_id);
+    let destination = get_associated_token_address_with_program_id(
+        &recipient.owner,
+        &mint.mint,
+        &mint.program_id,
+    );
+
+    let mut instructions = Vec::new();
+    if recipient.create_token_account {
+        instructions.push(create_associated_token_account_idempotent(
+            payer,
+            &recipient.owner,
+            &mint.mint,
+            &mint.program_id,
+        ));
+    }
+
+    // Token-2022 shares the instruction layout, only the program id differs.
+    let mut transfer = spl_token_interface::instruction::transfer_checked(
+        &spl_token_interface::ID,
+        &source,
+        &mint.mint,
+        &destination,
+        payer,
+        &[],
+        amount,
+        mint.decimals,
+    )
+    .expect("transfer_checked accepts the token program id");
+    transfer.program_id = mint.program_id;
+    instructions.push(transfer);
+
+    instructions
+}
+
+/// Closes the token account, its lamports go to the owner.
+pub fn close_instruction(account: &Pubkey, owner: &Pubkey, program_id: &Pubkey) -> Instruction {
+    let mut close = spl_token_interface::instruction::close_account(
+        &spl_token_interface::ID,
+        account,
+        owner,
+        owner,
+        &[],
+    )
+    .expect("close_account accepts the token program id");
+    close.program_id = *program_id;
+    close
+}
+
+/// Splits the recipients into groups that each fit into one transaction
+/// sending `amount` from `payer`'s associated token account to all of them.
+pub fn batch_token_recipients(
+    payer: &Pubkey,
+    mint: &MintInfo,
+    recipients: &[TokenRecipient],
+    amount: u64,
+) -> Vec<Vec<TokenRecipient>> {
+    batch_by_size(payer, &[], recipients, |recipient| {
+        recipient_instructions(payer, mint, recipient, amount)
+    })
+}
+
+/// Sends `amount` of the mint from the wallet's associated token account to every
+/// recipient, creating their associated token accounts where needed.
+pub async fn build_token_transfers(
+    wallet: &Keypair,
+    mint: &MintInfo,
+    recipients: &[TokenRecipient],
+    amount: u64,
+    blockhash: &Hash,
+) -> Result<util::SimpleTransaction, Error> {
+    let instructions: Vec<Instruction> = recipients
+        .iter()
+        .flat_map(|r| recipient_instructions(&wallet.pubkey(), mint, r, amount))
+        .collect();
+
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&wallet.pubkey()));
+    transaction.sign(&[wallet], *blockhash);
+    let hash = transaction.signatures[0].to_string();
+
+    let encoded = encode_transaction(&transaction);
+
+    Ok(util::SimpleTransaction {
+        transaction: encoded,
+        signature: hash,
+    })
+}
+
+/// Moves tokens from the wallet's token accounts into `destination`'s associated token
+/// account, then closes `close` so their rent returns to the wallet. A `fee_payer` pays
+/// the fee and the destination's rent in the wallet's place.
+pub async fn build_token_collection(
+    wallet: &Keypair,
+    fee_payer: Option<&Keypair>,
+    mint: &MintInfo,
+    sources: &[(Pubkey, u64)],
+    close: &[Pubkey],
+    destination: &TokenRecipient,
+    blockhash: &Hash,
+) -> Result<util::SimpleTransaction, Error> {
+    let owner = wallet.pubkey();
+    let signers = util::signers(wallet, fee_payer);
+    let payer = signers[0].pubkey();
+    let destination_account = get_associated_token_address_with_program_id(
+        &destination.owner,
+        &mint.mint,
+        &mint.program_id,
+    );
+
+    let mut instructions = Vec::new();
+    if destination.create_token_account {
+        instructions.push(create_associated_token_account_idempotent(
+            &payer,
+            &destination.owner,
+            &mint.mint,
+            &mint.program_id,
+        ));
+    }
+    for (source, amount) in sources {
+        let mut transfer = spl_token_interface::instruction::transfer_checked(
+            &spl_token_interface::ID,
+            source,
+            &mint.mint,
+            &destination_account,
+            &owner,
+            &[],
+            *amount,
+            mint.decimals,
+        )
+        .expect("transfer_checked accepts the token program id");
+        transfer.program_id = mint.program_id;
+        instructions.push(transfer);
+    }
+    for account in close {
+        instructions.push(close_instruction(account, &owner, &mint.program_id));
+    }
+
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&payer));
+    transaction.sign(&signers, *blockhash);
+    let hash = transaction.signatures[0].to_string();
+
+    let encoded = encode_transaction(&transaction);
+
+    Ok(util::SimpleTransaction {
+        transaction: encoded,
+        signature: hash,
+    })
+}
+
+/// A token account to close, with the tokens it still holds burned first.
+#[derive(Debug, Clone, Copy)]
+pub struct ClosedTokenAccount {
+    pub pubkey: Pubkey,
+    pub mint: Pubkey,
+    pub program_id: Pubkey,
+    pub decimals: u8,
+    /// Tokens burned before closing, in base units.
+    pub burn: u64,
+    /// Rent returned to the owner.
+    pub lamports: u64,
+}
+
+fn close_account_instructions(owner: &Pubkey, account: &ClosedTokenAccount) -> Vec<Instruction> {
+    let mut instructions = Vec::new();
+    if account.burn > 0 {
+        let mut burn = spl_token_interface::instruction::burn_checked(
+            &spl_token_interface::ID,
+            &account.pubkey,
+            &account.mint,
+            owner,
+            &[],
+            account.burn,
+            account.decimals,
+        )
+        .expect("burn_checked accepts the token program id");
+        burn.program_id = account.program_id;
+        instructions.push(burn);
+    }
+    instructions.push(close_instruction(
+        &account.pubkey,
+        owner,
+        &account.program_id,
+    ));
+
+    instructions
+}
+
+/// Splits the accounts into groups that each fit into one transaction closing them,
+/// paid by `fee_payer` if given.
+pub fn batch_token_account_closes(
+    owner: &Pubkey,
+    fee_payer: Option<&Pubkey>,
+    accounts: &[ClosedTokenAccount],
+) -> Vec<Vec<ClosedTokenAccount>> {
+    batch_by_size(fee_payer.unwrap_or(owner), &[], accounts, |account| {
+        close_account_instructions(owner, account)
+    })
+}
+
+/// Closes the wallet's token accounts, burning what they still hold. A `fee_payer`
+/// pays the fee, the rent still returns to the wallet.
+pub async fn build_token_account_closes(
+    wallet: &Keypair,
+    fee_payer: Option<&Keypair>,
+    accounts: &[ClosedTokenAccount],
+    blockhash: &Hash,
+) -> Result<util::SimpleTransaction, Error> {
+    let instructions: Vec<Instruction> = accounts
+        .iter()
+        .flat_map(|a| close_account_instructions(&wallet.pubkey(), a))
+        .collect();
+
+    let signers = util::signers(wallet, fee_payer);
+    let mut transaction = Transaction::new_with_payer(&instructions, Some(&signers[0].pubkey()));
+    transaction.sign(&signers, *blockhash);
+    let hash = transaction.signatures[0].to_string();
+
+    let encoded = encode_transaction(&transaction);
+
+    Ok(util::SimpleTransaction {
+        transaction: encoded,
+        signature: hash,
+    })
+}
@@ -1,22 +1,33 @@
 use crate::{
     errors::errors::Error,
-    txn_factory::util::{self, encode_transaction},
+    txn_factory::util::{self, batch_by_size, encode_transaction, encode_versioned_transaction},
 };
+use solana_compute_budget_interface::ComputeBudgetInstruction;
 use solana_sdk::{
-    hash::Hash, pubkey::Pubkey, signature::Keypair, signer::Signer, transaction::Transaction,
+    hash::Hash,
+    instruction::Instruction,
+    message::{VersionedMessage, v0},
+    pubkey::Pubkey,
+    signature::Keypair,
+    signer::Signer,
+    transaction::{Transaction, VersionedTransaction},
 };
 use solana_system_interface::instruction;
 
+/// Sends `lamports` from the wallet. With a `fee_payer` it signs too and pays the fee,
+/// so the wallet moves exactly `lamports` and can be emptied.
 pub async fn build_sol_transfer(
     wallet: &Keypair,
+    fee_payer: Option<&Keypair>,
     lamports: u64,
     to_pubkey: &Pubkey,
     blockhash: &Hash,
 ) -> Result<util::SimpleTransaction, Error> {
     let transfer_ix = instruction::transfer(&wallet.pubkey(), to_pubkey, lamports);
 
-    let mut transaction = Transaction::new_with_payer(&[transfer_ix], Some(&wallet.pubkey()));
-    transaction.sign(&[wallet], blockhash.clone());
+    let signers = util::signers(wallet, fee_payer);
+    let mut transaction = Transaction::new_with_payer(&[transfer_ix], Some(&signers[0].pubkey()));
+    transaction.sign(&signers, blockhash.clone());
     let hash = transaction.signatures[0].to_string();
 
     let encoded = encode_transaction(&transaction);
@@ -26,3 +37,66 @@ pub async fn build_sol_transfer(
         signature: hash,
     })
 }
+
+/// Compute units a system transfer consumes, and the compute budget instruction too.
+const TRANSFER_COMPUTE_UNITS: u32 = 150;
+/// Most compute a transaction may ask for.
+const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+
+fn compute_unit_limit(transfers: usize) -> Instruction {
+    ComputeBudgetInstruction::set_compute_unit_limit(
+        TRANSFER_COMPUTE_UNITS * (transfers as u32 + 1),
+    )
+}
+
+/// Splits the transfers into groups that each fit into one transaction from `payer`,
+/// sponsored by `fee_payer` if given. Size is the binding limit, a full transaction
+/// stays far below the compute cap.
+pub fn batch_sol_transfers(
+    payer: &Pubkey,
+    fee_payer: Option<&Pubkey>,
+    transfers: &[(Pubkey, u64)],
+) -> Vec<Vec<(Pubkey, u64)>> {
+    let batches = batch_by_size(
+        fee_payer.unwrap_or(payer),
+        &[compute_unit_limit(0)],
+        transfers,
+        |(to, lamports)| vec![instruction::transfer(payer, to, *lamports)],
+    );
+
+    let max_transfers = (MAX_COMPUTE_UNIT_LIMIT / TRANSFER_COMPUTE_UNITS - 1) as usize;
+    batches
+        .iter()
+        .flat_map(|batch| batch.chunks(max_transfers).map(<[_]>::to_vec))
+        .collect()
+}
+
+/// Sends every recipient its lamports in one v0 transaction, asking only for the
+/// compute the transfers use. No lookup table is used, creating and warming one up
+/// costs about as many transactions as a one-off distribution saves with it. A
+/// `fee_payer` signs too and pays the fee.
+pub async fn build_sol_transfers(
+    wallet: &Keypair,
+    fee_payer: Option<&Keypair>,
+    transfers: &[(Pubkey, u64)],
+    blockhash: &Hash,
+) -> Result<util::SimpleTransaction, Error> {
+    let mut instructions = vec![compute_unit_limit(transfers.len())];
+    instructions.extend(
+        transfers
+            .iter()
+            .map(|(to, lamports)| instruction::transfer(&wallet.pubkey(), to, *lamports)),
+    );
+
+    let signers = util::signers(wallet, fee_payer);
+    let message = v0::Message::try_compile(&signers[0].pubkey(), &instructions, &[], *blockhash)?;
+    let transaction = VersionedTransaction::try_new(VersionedMessage::V0(message), &signers)?;
+    let hash = transaction.signatures[0].to_string();
+
+    let encoded = encode_versioned_transaction(&transaction);
+
+    Ok(util::SimpleTransaction {
+        transaction: encoded,
+        signature: hash,
+    })
+}
@@ -1,10 +1,33 @@
-use solana_sdk::transaction::{Transaction, VersionedTransaction};
+use solana_sdk::{
+    hash::Hash,
+    instruction::Instruction,
+    message::{VersionedMessage, v0},
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    signer::Signer,
+    transaction::{Transaction, VersionedTransaction},
+};
+
+/// Largest serialized transaction a node accepts.
+pub const PACKET_DATA_SIZE: u64 = 1232;
+
+/// Network fee per signature, shared with the mock node so its fees can't drift from ours.
+pub use mock_node::LAMPORTS_PER_SIGNATURE;
 
 pub struct SimpleTransaction {
     pub transaction: String,
     pub signature: String,
 }
 
+/// Signers of a wallet's transaction, `fee_payer` first when it sponsors the fee.
+/// The first signer pays the fee and its signature identifies the transaction.
+pub fn signers<'a>(wallet: &'a Keypair, fee_payer: Option<&'a Keypair>) -> Vec<&'a Keypair> {
+    match fee_payer {
+        Some(fee_payer) if fee_payer.pubkey() != wallet.pubkey() => vec![fee_payer, wallet],
+        _ => vec![wallet],
+    }
+}
+
 pub fn encode_transaction(txn: &Transaction) -> String {
     let serialized = bincode::serialize(txn).expect("failed to serialize transaction");
     let encoded = bs58::encode(serialized).into_string();
@@ -16,3 +39,50 @@ pub fn encode_versioned_transaction(txn: &VersionedTransaction) -> String {
     let encoded = bs58::encode(serialized).into_string();
     return encoded;
 }
+
+/// Groups the items greedily so `leading` plus the instructions of each group fit
+/// into one transaction paid by `payer`. Sized as a v0 transaction, which is a byte
+/// larger than the legacy one, so groups fit either format.
+pub fn batch_by_size<T: Clone>(
+    payer: &Pubkey,
+    leading: &[Instruction],
+    items: &[T],
+    instructions_for: impl Fn(&T) -> Vec<Instruction>,
+) -> Vec<Vec<T>> {
+    let mut batches: Vec<Vec<T>> = Vec::new();
+    let mut instructions: Vec<Instruction> = leading.to_vec();
+
+    for item in items {
+        let item_ixs = instructions_for(item);
+        let mut candidate = instructions.clone();
+        candidate.extend(item_ixs.iter().cloned());
+
+        let fits = v0::Message::try_compile(payer, &candidate, &[], Hash::default())
+            .map(|message| {
+                let transaction = VersionedTransaction {
+                    signatures: vec![
+                        Signature::default();
+                        message.header.num_required_signatures as usize
+                    ],
+                    message: VersionedMessage::V0(message),
+                };
+                bincode::serialized_size(&transaction).expect("transaction serializes")
+                    <= PACKET_DATA_SIZE
+            })
+            .unwrap_or(false);
+
+        match batches.last_mut() {
+            Some(batch) if fits => {
+                batch.push(item.clone());
+                instructions = candidate;
+            }
+            _ => {
+                batches.push(vec![item.clone()]);
+                instructions = leading.to_vec();
+                instructions.extend(item_ixs);
+            }
+        }
+    }
+
+    batches
+}
@@ -0,0 +1,82 @@
+use solana_sdk::{
+    hash::Hash, instruction::Instruction, pubkey::Pubkey, signature::Keypair, signer::Signer,
+    transaction::Transaction,
+};
+use solana_system_interface::instruction as system_instruction;
+use spl_associated_token_account_interface::{
+    address::get_associated_token_address, instruction::create_associated_token_account_idempotent,
+};
+use spl_token_interface::native_mint;
+
+use crate::{
+    errors::errors::Error,
+    txn_factory::{
+        token_transfer::close_instruction,
+        util::{self, encode_transaction},
+    },
+};
+
+/// The wallet's wrapped SOL account, wrapping always goes into it.
+pub fn wsol_account(wallet: &Pubkey) -> Pubkey {
+    get_associated_token_address(wallet, &native_mint::ID)
+}
+
+fn sign(
+    wallet: &Keypair,
+    fee_payer: Option<&Keypair>,
+    instructions: &[Instruction],
+    blockhash: &Hash,
+) -> util::SimpleTransaction {
+    let signers = util::signers(wallet, fee_payer);
+    let mut transaction = Transaction::new_with_payer(instructions, Some(&signers[0].pubkey()));
+    transaction.sign(&signers, *blockhash);
+    let hash = transaction.signatures[0].to_string();
+
+    util::SimpleTransaction {
+        transaction: encode_transaction(&transaction),
+        signature: hash,
+    }
+}
+
+/// Moves `lamports` into the wallet's wSOL account, creating it if needed. A `fee_payer`
+/// pays the fee and the new account's rent.
+pub async fn build_wrap_sol(
+    wallet: &Keypair,
+    fee_payer: Option<&Keypair>,
+    lamports: u64,
+    blockhash: &Hash,
+) -> Result<util::SimpleTransaction, Error> {
+    let owner = wallet.pubkey();
+    let account = wsol_account(&owner);
+    let payer = fee_payer.map(|f| f.pubkey()).unwrap_or(owner);
+
+    let instructions = [
+        create_associated_token_account_idempotent(
+            &payer,
+            &owner,
+            &native_mint::ID,
+            &spl_token_interface::ID,
+        ),
+        system_instruction::transfer(&owner, &account, lamports),
+        spl_token_interface::instruction::sync_native(&spl_token_interface::ID, &account)
+            .expect("sync_native accepts the token program id"),
+    ];
+
+    Ok(sign(wallet, fee_payer, &instructions, blockhash))
+}
+
+/// Closes the wallet's wSOL accounts, their wrapped amount and rent go back to the wallet.
+/// Takes each account with the token program owning it.
+pub async fn build_unwrap_sol(
+    wallet: &Keypair,
+    fee_payer: Option<&Keypair>,
+    accounts: &[(Pubkey, Pubkey)],
+    blockhash: &Hash,
+) -> Result<util::SimpleTransaction, Error> {
+    let instructions: Vec<Instruction> = accounts
+        .iter()
+        .map(|(account, program_id)| close_instruction(account, &wallet.pubkey(), program_id))
+        .collect();
+
+    Ok(sign(wallet, fee_payer, &instructions, blockhash))
+}
@@ -1,21 +1,22 @@
 use std::{collections::HashMap, sync::Arc};
 
-use futures_util::{
-    SinkExt, StreamExt,
-    stream::{SplitSink, SplitStream},
-};
-use reqwest::StatusCode;
 use serde::Deserialize;
-use serde_json::json;
-use tokio::{
-    net::TcpStream,
-    sync::{Mutex, Notify},
+use serde_json::{Value, json};
+use tokio::sync::{
+    Mutex,
+    mpsc::{UnboundedReceiver, UnboundedSender},
+    oneshot,
 };
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async};
-use tungstenite::{Message, client::IntoClientRequest};
 
-type WsWrite = SplitSink<WebSocketStream<MaybeTlsStream<TcpStream>>, Message>;
-type WsRead = SplitStream<WebSocketStream<MaybeTlsStream<TcpStream>>>;
+use crate::{Rpc, errors::errors::Error, rpc::commitment::Commitment};
+
+type WsWrite = UnboundedSender<String>;
+type WsRead = UnboundedReceiver<String>;
+
+/// Resolves once the signature reached the subscribed commitment, with the
+/// transaction's error if it landed but failed.
+pub type SignatureConfirmation = oneshot::Receiver<Result<(), String>>;
+type SignatureSender = oneshot::Sender<Result<(), String>>;
 
 #[derive(Deserialize)]
 struct RpcSubscriptionResponse {
@@ -31,23 +32,32 @@ struct RpcResponse {
 #[derive(Deserialize)]
 struct RpcResponseParams {
     subscription: u64,
+    result: RpcNotificationResult,
+}
+
+#[derive(Deserialize)]
+struct RpcNotificationResult {
+    value: RpcSignatureResult,
+}
+
+#[derive(Deserialize)]
+struct RpcSignatureResult {
+    err: Option<Value>,
 }
 
 pub struct SolanaWebsocket {
-    address: String,
+    transport: Rpc,
     ws_write: Arc<Mutex<Option<WsWrite>>>,
     subs_to_id: Arc<Mutex<HashMap<u64, String>>>,
-    subscriptions_results: Arc<Mutex<HashMap<String, String>>>,
-    subscriptions: Arc<Mutex<HashMap<String, Arc<Notify>>>>,
+    subscriptions: Arc<Mutex<HashMap<String, SignatureSender>>>,
 }
 
 impl SolanaWebsocket {
-    pub async fn new(address: &str) -> Self {
-        let mut ws = SolanaWebsocket {
-            address: address.to_owned(),
+    pub async fn new(transport: Rpc) -> Self {
+        let ws = SolanaWebsocket {
+            transport,
             ws_write: Arc::new(Mutex::new(None)),
             subs_to_id: Arc::new(Mutex::new(HashMap::new())),
-            subscriptions_results: Arc::new(Mutex::new(HashMap::new())),
             subscriptions: Arc::new(Mutex::new(HashMap::new())),
         };
 
@@ -59,79 +69,75 @@ impl SolanaWebsocket {
         ws
     }
 
-    async fn connect(&mut self) -> WsWrite {
-        let request = self.address.clone().into_client_request().unwrap();
-        let conn_result = connect_async(request).await;
-        let (stream, res) = match conn_result {
+    async fn connect(&self) -> WsWrite {
+        let conn_result = self.transport.open_subscription().await;
+        let channel = match conn_result {
             Err(err) => {
                 panic!("Error connecting to websocket {}", err);
             }
             Ok(v) => v,
         };
 
-        if res.status() == StatusCode::SWITCHING_PROTOCOLS {
-            println!("Established websocket connection successfully.");
-        }
-
-        let (write, read) = stream.split();
-
         let subs_to_id_arc = self.subs_to_id.clone();
         subs_to_id_arc.lock().await.clear();
-        let subscriptions_results_arc = self.subscriptions_results.clone();
-        subscriptions_results_arc.lock().await.clear();
+        // Dropping the senders wakes everyone waiting on the old connection.
         let subscriptions_arc = self.subscriptions.clone();
         subscriptions_arc.lock().await.clear();
 
-        self.spawn_reader(read);
+        self.spawn_reader(channel.receiver);
 
-        write
+        channel.sender
     }
 
-    fn spawn_reader(&self, ws_read: WsRead) {
+    fn spawn_reader(&self, mut ws_read: WsRead) {
         let subs_to_id = Arc::clone(&self.subs_to_id);
         let subscriptions = Arc::clone(&self.subscriptions);
         let ws_write = Arc::clone(&self.ws_write);
         tokio::spawn(async move {
-            ws_read
-                .for_each(|received| async {
-                    let message = match received {
-                        Err(err) => {
-                            if matches!(err, tungstenite::Error::ConnectionClosed) {
-                                eprintln!("Connection closed")
-                            }
-                            eprintln!("Error receiving websocket data: {err}");
-                            return;
-                        }
-                        Ok(msg) => msg,
-                    };
+            while let Some(message_res) = ws_read.recv().await {
+                let response = serde_json::from_str::<RpcSubscriptionResponse>(&message_res);
+                if let Ok(subscription) = response {
+                    let mut subs_to_id = subs_to_id.lock().await;
+                    subs_to_id.insert(subscription.result, subscription.id);
+                    continue;
+                }
 
-                    let message_res = message.into_text().unwrap();
-                    let response = serde_json::from_str::<RpcSubscriptionResponse>(&message_res);
-                    if let Ok(subscription) = response {
-                        let mut subs_to_id = subs_to_id.lock().await;
-                        subs_to_id.insert(subscription.result, subscription.id);
-                        return;
-                    }
-
-                    let response = serde_json::from_str::<RpcResponse>(&message_res);
-                    if let Ok(data) = response {
-                        let subs_to_id = subs_to_id.lock().await;
-                        let subscriptions = subscriptions.lock().await;
-                        let id = subs_to_id
-                            .get(&data.params.subscription)
-                            .expect("got unexpected message from websocket");
-                        let notify = &subscriptions[id];
-                        notify.notify_waiters();
-                    }
-                })
-                .await;
+                let response = serde_json::from_str::<RpcResponse>(&message_res);
+                if let Ok(data) = response {
+                    let mut subs_to_id = subs_to_id.lock().await;
+                    let mut subscriptions = subscriptions.lock().await;
+                    // Signature subscriptions end with their notification.
+                    let sender = subs_to_id
+                        .remove(&data.params.subscription)
+                        .and_then(|id| subscriptions.remove(&id));
+                    let Some(sender) = sender else {
+                        eprintln!(
+                            "Notification for unknown subscription {}",
+                            data.params.subscription
+                        );
+                        continue;
+                    };
+                    let result = match data.params.result.value.err {
+                        None => Ok(()),
+                        Some(err) => Err(err.to_string()),
+                    };
+                    let _ = sender.send(result);
+                }
+            }
 
             let mut ws_write_locked = ws_write.lock().await;
             *ws_write_locked = None;
         });
     }
 
-    pub async fn confirm_transaction(&mut self, signature: &str) {
+    /// Subscribes to the signature and returns right away, await the returned
+    /// confirmation without holding on to the websocket. Subscribe before sending,
+    /// a fast node may confirm right after the transaction arrives.
+    pub async fn subscribe_signature(
+        &self,
+        signature: &str,
+        commitment: Commitment,
+    ) -> Result<SignatureConfirmation, Error> {
         let id = &signature[..12];
         let message_data = json!({
             "jsonrpc": "2.0",
@@ -140,42 +146,68 @@ impl SolanaWebsocket {
             "params": [
                 signature,
                 {
-                    "commitment": "confirmed"
+                    "commitment": commitment
                 }
             ]
         });
-        let data_str = message_data.to_string();
-        let message = Message::text(data_str);
-        {
-            let writer_arc = Arc::clone(&self.ws_write);
-            let writer = &mut *writer_arc.lock().await;
-            if matches!(writer, None) {
-                *writer = Some(self.connect().await);
-                if matches!(writer, None) {
-                    eprintln!(
-                        "Failed to confirm transaction, no websocket writer after reconnecting."
-                    );
-                    return;
-                }
-            }
-            let writer = writer.as_mut().unwrap();
-            let send_result = writer.send(message).await;
+        let message = message_data.to_string();
+        let (sender, receiver) = oneshot::channel();
 
-            if let Err(err) = send_result {
-                eprintln!("Error sending: {err}");
-            }
+        let writer_arc = Arc::clone(&self.ws_write);
+        let writer = &mut *writer_arc.lock().await;
+        if writer.is_none() {
+            *writer = Some(self.connect().await);
         }
 
-        let notify = Arc::new(Notify::new());
+        // Registered before subscribing, a fast node may notify right after the subscription response.
         {
             let mut subcriptions = self.subscriptions.lock().await;
-            subcriptions.insert(id.to_owned(), notify.clone());
+            subcriptions.insert(id.to_owned(), sender);
+        }
+
+        let writer = writer.as_mut().expect("connected above");
+        if let Err(err) = writer.send(message) {
+            self.subscriptions.lock().await.remove(id);
+            return Err(Error::RpcError(format!(
+                "Failed to subscribe to {}: {}",
+                signature, err
+            )));
         }
 
         println!("Confirming transaction {}", &signature[..6]);
 
-        notify.notified().await;
-        let mut subscriptions = self.subscriptions.lock().await;
-        subscriptions.remove(id);
+        Ok(receiver)
+    }
+
+    /// Drops the subscription of a signature nobody waits for anymore.
+    pub async fn unsubscribe_signature(&self, signature: &str) {
+        let id = &signature[..12];
+        self.subscriptions.lock().await.remove(id);
+
+        let subscription = {
+            let mut subs_to_id = self.subs_to_id.lock().await;
+            let subscription = subs_to_id
+                .iter()
+                .find(|(_, sub_id)| sub_id.as_str() == id)
+                .map(|(subscription, _)| *subscription);
+            if let Some(subscription) = subscription {
+                subs_to_id.remove(&subscription);
+            }
+            subscription
+        };
+
+        if let Some(subscription) = subscription
+            && let Some(writer) = self.ws_write.lock().await.as_mut()
+        {
+            let message = json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "method": "signatureUnsubscribe",
+                "params": [subscription]
+            });
+            if let Err(err) = writer.send(message.to_string()) {
+                eprintln!("Error unsubscribing: {err}");
+            }
+        }
     }
 }
@@ -0,0 +1,362 @@
+use std::collections::HashMap;
+
+use solana_sdk::{
+    account::Account,
+    hash::Hash,
+    message::{VersionedMessage, compiled_instruction::CompiledInstruction},
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::VersionedTransaction,
+};
+use solana_system_interface::{instruction::SystemInstruction, program as system_program};
+
+use crate::{
+    LAMPORTS_PER_SIGNATURE,
+    ledger::{Execution, Ledger, ProcessedTransaction, SignatureStatus},
+};
+
+const MAX_PROCESSING_AGE: usize = 150;
+const UNITS_PER_INSTRUCTION: u64 = 150;
+const COMPUTE_BUDGET_PROGRAM: &str = "ComputeBudget111111111111111111111111111111";
+
+pub fn system_account(lamports: u64) -> Account {
+    Account {
+        lamports,
+        data: Vec::new(),
+        owner: system_program::ID,
+        executable: false,
+        rent_epoch: u64::MAX,
+    }
+}
+
+/// In-memory ledger that only knows the system program.
+pub struct Bank {
+    accounts: HashMap<Pubkey, Account>,
+    signatures: HashMap<Signature, SignatureStatus>,
+    transactions: HashMap<Signature, ProcessedTransaction>,
+    blockhashes: Vec<Hash>,
+    slot: u64,
+    block_height: u64,
+}
+
+impl Bank {
+    pub fn new() -> Self {
+        Self {
+            accounts: HashMap::new(),
+            signatures: HashMap::new(),
+            transactions: HashMap::new(),
+            blockhashes: vec![Hash::new_unique()],
+            slot: 1,
+            block_height: 1,
+        }
+    }
+
+    pub fn is_blockhash_valid(&self, blockhash: &Hash) -> bool {
+        self.blockhashes
+            .iter()
+            .rev()
+            .take(MAX_PROCESSING_AGE)
+            .any(|h| h == blockhash)
+    }
+
+    fn commit(&mut self, execution: &Execution) {
+        for pubkey in execution.changed_accounts() {
+            let (_, post) = execution
+                .post_accounts
+                .iter()
+                .find(|(p, _)| *p == pubkey)
+                .expect("changed account is part of the execution");
+            self.set_account(pubkey, post.clone());
+        }
+
+        self.signatures.insert(
+            execution.signature,
+            SignatureStatus {
+                slot: self.slot,
+                err: execution.err.clone(),
+            },
+        );
+        self.advance_slot();
+    }
+
+    fn advance_slot(&mut self) {
+        self.slot += 1;
+        self.block_height += 1;
+        self.blockhashes.push(Hash::new_unique());
+        if self.blockhashes.len() > MAX_PROCESSING_AGE * 2 {
+            self.blockhashes.remove(0);
+        }
+    }
+
+    fn check_rent_state(&self, pre: &[Account], post: &[Account]) -> Result<(), String> {
+        for (i, (pre, post)) in pre.iter().zip(post.iter()).enumerate() {
+            if pre.lamports == post.lamports {
+                continue;
+            }
+            let minimum = self.minimum_balance_for_rent_exemption(post.data.len());
+            let was_rent_paying = pre.lamports > 0 && pre.lamports < minimum;
+            let is_rent_paying = post.lamports > 0 && post.lamports < minimum;
+            if is_rent_paying && !was_rent_paying {
+                return Err(format!(
+                    "InsufficientFundsForRent {{ account_index: {} }}",
+                    i
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Ledger for Bank {
+    fn slot(&self) -> u64 {
+        self.slot
+    }
+
+    fn block_height(&self) -> u64 {
+        self.block_height
+    }
+
+    fn latest_blockhash(&self) -> (Hash, u64) {
+        let hash = *self
+            .blockhashes
+            .last()
+            .expect("bank always has a blockhash");
+        (hash, self.block_height + MAX_PROCESSING_AGE as u64)
+    }
+
+    fn minimum_balance_for_rent_exemption(&self, data_len: usize) -> u64 {
+        // Mirrors the default rent: 3480 lamports per byte-year, two years, 128 bytes overhead.
+        (128 + data_len as u64) * 3480 * 2
+    }
+
+    fn account(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.accounts.get(pubkey).cloned()
+    }
+
+    fn program_accounts(&self, program_id: &Pubkey) -> Vec<(Pubkey, Account)> {
+        self.accounts
+            .iter()
+            .filter(|(_, account)| account.owner == *program_id)
+            .map(|(pubkey, account)| (*pubkey, account.clone()))
+            .collect()
+    }
+
+    fn set_account(&mut self, pubkey: Pubkey, account: Account) {
+        if account.lamports == 0 && account.data.is_empty() {
+            self.accounts.remove(&pubkey);
+            return;
+        }
+        self.accounts.insert(pubkey, account);
+    }
+
+    fn airdrop(&mut self, pubkey: &Pubkey, lamports: u64) -> Result<Signature, String> {
+        let mut account = self.account(pubkey).unwrap_or_else(|| system_account(0));
+        account.lamports += lamports;
+        self.set_account(*pubkey, account);
+
+        let signature = Signature::new_unique();
+        self.signatures.insert(
+            signature,
+            SignatureStatus {
+                slot: self.slot,
+                err: None,
+            },
+        );
+        self.advance_slot();
+
+        Ok(signature)
+    }
+
+    fn signature_status(&self, signature: &Signature) -> Option<SignatureStatus> {
+        self.signatures.get(signature).cloned()
+    }
+
+    fn transaction(&self, signature: &Signature) -> Option<ProcessedTransaction> {
+        self.transactions.get(signature).cloned()
+    }
+
+    fn simulate(&self, txn: &VersionedTransaction) -> Result<Execution, String> {
+        txn.sanitize().map_err(|err| err.to_string())?;
+        if txn.verify_with_results().iter().any(|valid| !valid) {
+            return Err("SignatureFailure".to_owned());
+        }
+
+        let message = &txn.message;
+        if message
+            .address_table_lookups()
+            .is_some_and(|lookups| !lookups.is_empty())
+        {
+            return Err("address lookup tables are not supported by the mock node".to_owned());
+        }
+        if !self.is_blockhash_valid(message.recent_blockhash()) {
+            return Err("BlockhashNotFound".to_owned());
+        }
+
+        let signature = txn.signatures[0];
+        if self.signatures.contains_key(&signature) {
+            return Err("AlreadyProcessed".to_owned());
+        }
+
+        let keys = message.static_account_keys().to_vec();
+        let pre_accounts: Vec<Account> = keys
+            .iter()
+            .map(|k| self.account(k).unwrap_or_else(|| system_account(0)))
+            .collect();
+
+        let fee = LAMPORTS_PER_SIGNATURE * txn.signatures.len() as u64;
+        if pre_accounts[0].lamports < fee {
+            return Err("InsufficientFundsForFee".to_owned());
+        }
+
+        let mut post_accounts = pre_accounts.clone();
+        post_accounts[0].lamports -= fee;
+        let fee_only_accounts = post_accounts.clone();
+
+        let mut err = None;
+        let mut logs = Vec::new();
+        let mut compute_units_consumed = 0;
+        for (i, instruction) in message.instructions().iter().enumerate() {
+            let program_id = keys[instruction.program_id_index as usize];
+            logs.push(format!("Program {} invoke [1]", program_id));
+            compute_units_consumed += UNITS_PER_INSTRUCTION;
+
+            let result = execute_instruction(message, &keys, &mut post_accounts, instruction)
+                .and_then(|_| self.check_rent_state(&pre_accounts, &post_accounts));
+            if let Err(instruction_err) = result {
+                logs.push(format!(
+                    "Program {} failed: {}",
+                    program_id, instruction_err
+                ));
+                err = Some(format!(
+                    "Error processing Instruction {}: {}",
+                    i, instruction_err
+                ));
+                post_accounts = fee_only_accounts;
+                break;
+            }
+            logs.push(format!("Program {} success", program_id));
+        }
+
+        Ok(Execution {
+            signature,
+            fee,
+            err,
+            logs,
+            compute_units_consumed,
+            pre_accounts: keys.iter().cloned().zip(pre_accounts).collect(),
+            post_accounts: keys.into_iter().zip(post_accounts).collect(),
+        })
+    }
+
+    fn process(&mut self, txn: &VersionedTransaction) -> Result<Execution, String> {
+        let execution = self.simulate(txn)?;
+        self.transactions.insert(
+            execution.signature,
+            ProcessedTransaction {
+                slot: self.slot,
+                transaction: txn.clone(),
+                execution: execution.clone(),
+            },
+        );
+        self.commit(&execution);
+
+        Ok(execution)
+    }
+}
+
+impl Default for Bank {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn execute_instruction(
+    message: &VersionedMessage,
+    keys: &[Pubkey],
+    accounts: &mut [Account],
+    instruction: &CompiledInstruction,
+) -> Result<(), String> {
+    let program_id = keys[instruction.program_id_index as usize];
+
+    if program_id.to_string() == COMPUTE_BUDGET_PROGRAM {
+        return Ok(());
+    }
+
+    if program_id != system_program::ID {
+        return Err(format!(
+            "program {} is not supported by the mock node",
+            program_id
+        ));
+    }
+
+    let system_instruction: SystemInstruction =
+        bincode::deserialize(&instruction.data).map_err(|_| "InvalidInstructionData")?;
+    let account_index = |position: usize| -> Result<usize, String> {
+        instruction
+            .accounts
+            .get(position)
+            .map(|i| *i as usize)
+            .ok_or_else(|| "NotEnoughAccountKeys".to_owned())
+    };
+
+    match system_instruction {
+        SystemInstruction::Transfer { lamports } => {
+            let from = account_index(0)?;
+            let to = account_index(1)?;
+            if !message.is_signer(from) {
+                return Err("MissingRequiredSignature".to_owned());
+            }
+            if !message.is_maybe_writable(from, None) || !message.is_maybe_writable(to, None) {
+                return Err("ReadonlyLamportChange".to_owned());
+            }
+            if !accounts[from].data.is_empty() || accounts[from].owner != system_program::ID {
+                return Err("Transfer: `from` must not carry data".to_owned());
+            }
+            if accounts[from].lamports < lamports {
+                return Err(format!(
+                    "Transfer: insufficient lamports {}, need {}",
+                    accounts[from].lamports, lamports
+                ));
+            }
+
+            accounts[from].lamports -= lamports;
+            accounts[to].lamports += lamports;
+            Ok(())
+        }
+        SystemInstruction::CreateAccount {
+            lamports,
+            space,
+            owner,
+        } => {
+            let from = account_index(0)?;
+            let to = account_index(1)?;
+            if !message.is_signer(from) || !message.is_signer(to) {
+                return Err("MissingRequiredSignature".to_owned());
+            }
+            if accounts[to].lamports > 0 || !accounts[to].data.is_empty() {
+                return Err("Create Account: account already in use".to_owned());
+            }
+            if accounts[from].lamports < lamports {
+                return Err(format!(
+                    "Create Account: insufficient lamports {}, need {}",
+                    accounts[from].lamports, lamports
+                ));
+            }
+
+            accounts[from].lamports -= lamports;
+            accounts[to] = Account {
+                lamports,
+                owner,
+                data: vec![0u8; space as usize],
+                executable: false,
+                rent_epoch: u64::MAX,
+            };
+            Ok(())
+        }
+        other => Err(format!(
+            "system instruction {:?} is not supported by the mock node",
+            other
+        )),
+    }
+}
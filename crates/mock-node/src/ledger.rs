@@ -0,0 +1,65 @@
+use solana_sdk::{
+    account::Account, hash::Hash, pubkey::Pubkey, signature::Signature,
+    transaction::VersionedTransaction,
+};
+
+#[derive(Debug, Clone)]
+pub struct SignatureStatus {
+    pub slot: u64,
+    pub err: Option<String>,
+}
+
+/// Outcome of running a transaction, with the state of every static account key
+/// before and after it ran.
+#[derive(Clone)]
+pub struct Execution {
+    pub signature: Signature,
+    pub fee: u64,
+    pub err: Option<String>,
+    pub logs: Vec<String>,
+    pub compute_units_consumed: u64,
+    pub pre_accounts: Vec<(Pubkey, Account)>,
+    pub post_accounts: Vec<(Pubkey, Account)>,
+}
+
+impl Execution {
+    pub fn changed_accounts(&self) -> Vec<Pubkey> {
+        self.pre_accounts
+            .iter()
+            .zip(self.post_accounts.iter())
+            .filter(|((_, pre), (_, post))| pre != post)
+            .map(|((pubkey, _), _)| *pubkey)
+            .collect()
+    }
+}
+
+/// A transaction that was committed, as `getTransaction` returns it.
+#[derive(Clone)]
+pub struct ProcessedTransaction {
+    pub slot: u64,
+    pub transaction: VersionedTransaction,
+    pub execution: Execution,
+}
+
+/// The state and execution engine behind a `MockNode`.
+///
+/// Errors returned as `Err` are ones a real cluster would drop the transaction for,
+/// errors raised while executing the instructions end up in `Execution::err`.
+pub trait Ledger: Send {
+    fn slot(&self) -> u64;
+    fn block_height(&self) -> u64;
+    /// The latest blockhash and its last valid block height.
+    fn latest_blockhash(&self) -> (Hash, u64);
+    fn minimum_balance_for_rent_exemption(&self, data_len: usize) -> u64;
+    fn account(&self, pubkey: &Pubkey) -> Option<Account>;
+    /// Every account owned by `program_id`.
+    fn program_accounts(&self, program_id: &Pubkey) -> Vec<(Pubkey, Account)>;
+    fn set_account(&mut self, pubkey: Pubkey, account: Account);
+    fn airdrop(&mut self, pubkey: &Pubkey, lamports: u64) -> Result<Signature, String>;
+    fn signature_status(&self, signature: &Signature) -> Option<SignatureStatus>;
+    fn transaction(&self, signature: &Signature) -> Option<ProcessedTransaction>;
+    /// Runs the transaction without committing anything.
+    fn simulate(&self, txn: &VersionedTransaction) -> Result<Execution, String>;
+    /// Runs the transaction and commits its effects.
+    fn process(&mut self, txn: &VersionedTransaction) -> Result<Execution, String>;
+}
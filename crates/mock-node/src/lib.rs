@@ -0,0 +1,34 @@
+//! In-process stand-in for a Solana RPC node.
+//!
+//! Serves the JSON-RPC methods and signature/account subscriptions the backend
+//! uses from a `Ledger`. The default `SvmLedger` runs transactions in an embedded
+//! LiteSVM, so token programs work too. `Bank` is a lighter in-memory ledger that
+//! executes system program instructions itself.
+//!
+//! Without the `node` feature only the cluster constants are built, so a production
+//! binary can share them without shipping the node.
+
+/// What a cluster charges per transaction signature.
+pub const LAMPORTS_PER_SIGNATURE: u64 = 5000;
+
+#[cfg(feature = "node")]
+mod bank;
+#[cfg(feature = "node")]
+mod ledger;
+#[cfg(feature = "node")]
+mod node;
+#[cfg(feature = "node")]
+mod rpc;
+#[cfg(feature = "node")]
+mod subscriptions;
+#[cfg(feature = "node")]
+mod svm;
+
+#[cfg(feature = "node")]
+pub use bank::{Bank, system_account};
+#[cfg(feature = "node")]
+pub use ledger::{Execution, Ledger, ProcessedTransaction, SignatureStatus};
+#[cfg(feature = "node")]
+pub use node::MockNode;
+#[cfg(feature = "node")]
+pub use svm::SvmLedger;
@@ -0,0 +1,69 @@
+use std::sync::{Arc, Mutex};
+
+use solana_sdk::{account::Account, hash::Hash, pubkey::Pubkey, signature::Signature};
+
+use crate::{ledger::Ledger, subscriptions::Subscriptions, svm::SvmLedger};
+
+#[derive(Clone)]
+pub struct MockNode {
+    pub(crate) ledger: Arc<Mutex<Box<dyn Ledger>>>,
+    pub(crate) subscriptions: Arc<Mutex<Subscriptions>>,
+}
+
+impl MockNode {
+    pub fn new() -> Self {
+        Self::with_ledger(Box::new(SvmLedger::new()))
+    }
+
+    pub fn with_ledger(ledger: Box<dyn Ledger>) -> Self {
+        Self {
+            ledger: Arc::new(Mutex::new(ledger)),
+            subscriptions: Arc::new(Mutex::new(Subscriptions::default())),
+        }
+    }
+
+    pub fn account(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.ledger.lock().unwrap().account(pubkey)
+    }
+
+    pub fn balance(&self, pubkey: &Pubkey) -> u64 {
+        self.account(pubkey).map(|a| a.lamports).unwrap_or(0)
+    }
+
+    /// The latest blockhash and its last valid block height.
+    pub fn latest_blockhash(&self) -> (Hash, u64) {
+        self.ledger.lock().unwrap().latest_blockhash()
+    }
+
+    pub fn set_account(&self, pubkey: Pubkey, account: Account) {
+        let mut ledger = self.ledger.lock().unwrap();
+        ledger.set_account(pubkey, account);
+        let slot = ledger.slot();
+        let account = ledger.account(&pubkey);
+        drop(ledger);
+
+        self.notify_account(&pubkey, account.as_ref(), slot);
+    }
+
+    pub fn airdrop(&self, pubkey: &Pubkey, lamports: u64) -> Result<Signature, String> {
+        let mut ledger = self.ledger.lock().unwrap();
+        let signature = ledger.airdrop(pubkey, lamports)?;
+        let slot = ledger.slot();
+        let status = ledger
+            .signature_status(&signature)
+            .expect("airdrop has a status");
+        let account = ledger.account(pubkey);
+        drop(ledger);
+
+        self.notify_signature(&signature, &status);
+        self.notify_account(pubkey, account.as_ref(), slot);
+
+        Ok(signature)
+    }
+}
+
+impl Default for MockNode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
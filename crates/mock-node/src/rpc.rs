@@ -0,0 +1,503 @@
+use base64::{Engine, engine::general_purpose};
+use serde_json::{Value, json};
+use solana_sdk::{
+    account::Account, message::VersionedMessage, pubkey::Pubkey, signature::Signature,
+    transaction::VersionedTransaction,
+};
+
+use crate::{LAMPORTS_PER_SIGNATURE, Ledger, MockNode};
+
+const API_VERSION: &str = "mock-node";
+const TOKEN_PROGRAM: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+const TOKEN_2022_PROGRAM: &str = "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb";
+/// Size of an SPL token account without extensions, and of a mint.
+const TOKEN_ACCOUNT_LEN: usize = 165;
+const MINT_LEN: usize = 82;
+
+pub(crate) fn rpc_context(slot: u64) -> Value {
+    json!({ "slot": slot, "apiVersion": API_VERSION })
+}
+
+pub(crate) fn encode_account(account: &Account, encoding: &str) -> Value {
+    encode_account_slice(account, encoding, &Value::Null)
+}
+
+/// Encodes the account, limiting the data to `data_slice` (`{ offset, length }`) if given.
+/// `space` always reports the full data length.
+pub(crate) fn encode_account_slice(account: &Account, encoding: &str, data_slice: &Value) -> Value {
+    let start = (data_slice["offset"].as_u64().unwrap_or(0) as usize).min(account.data.len());
+    let end = match data_slice["length"].as_u64() {
+        Some(length) => (start + length as usize).min(account.data.len()),
+        None => account.data.len(),
+    };
+    let bytes = &account.data[start..end];
+
+    let data = match encoding {
+        "base58" => json!([bs58::encode(bytes).into_string(), "base58"]),
+        _ => json!([general_purpose::STANDARD.encode(bytes), "base64"]),
+    };
+
+    json!({
+        "lamports": account.lamports,
+        "owner": account.owner.to_string(),
+        "data": data,
+        "executable": account.executable,
+        "rentEpoch": account.rent_epoch,
+        "space": account.data.len(),
+    })
+}
+
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+impl RpcError {
+    fn invalid_params(message: impl Into<String>) -> Self {
+        Self {
+            code: -32602,
+            message: message.into(),
+        }
+    }
+}
+
+fn parse_pubkey(value: &Value) -> Result<Pubkey, RpcError> {
+    value
+        .as_str()
+        .and_then(|s| s.parse::<Pubkey>().ok())
+        .ok_or_else(|| RpcError::invalid_params("Invalid param: not a valid pubkey"))
+}
+
+impl MockNode {
+    /// Answers a single JSON-RPC request the way a Solana RPC node would.
+    pub fn handle_request(&self, request: &Value) -> Value {
+        let id = request["id"].clone();
+        let params = &request["params"];
+        let method = request["method"].as_str().unwrap_or_default();
+
+        let result = match method {
+            "getBalance" => self.get_balance(params),
+            "getAccountInfo" => self.get_account_info(params),
+            "getMultipleAccounts" => self.get_multiple_accounts(params),
+            "getLatestBlockhash" => self.get_latest_blockhash(),
+            "getMinimumBalanceForRentExemption" => self.get_minimum_balance(params),
+            "getSlot" => Ok(json!(self.ledger.lock().unwrap().slot())),
+            "getBlockHeight" => Ok(json!(self.ledger.lock().unwrap().block_height())),
+            "getSignatureStatuses" => self.get_signature_statuses(params),
+            "requestAirdrop" => self.request_airdrop(params),
+            "sendTransaction" => self.send_transaction(params),
+            "simulateTransaction" => self.simulate_transaction(params),
+            "getFeeForMessage" => self.get_fee_for_message(params),
+            "getTransaction" => self.get_transaction(params),
+            "getTokenAccountsByOwner" => self.get_token_accounts_by_owner(params),
+            _ => Err(RpcError {
+                code: -32601,
+                message: "Method not found".to_owned(),
+            }),
+        };
+
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err(err) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": err.code, "message": err.message }
+            }),
+        }
+    }
+
+    fn get_balance(&self, params: &Value) -> Result<Value, RpcError> {
+        let pubkey = parse_pubkey(&params[0])?;
+        let ledger = self.ledger.lock().unwrap();
+        let lamports = ledger.account(&pubkey).map(|a| a.lamports).unwrap_or(0);
+
+        Ok(json!({ "context": rpc_context(ledger.slot()), "value": lamports }))
+    }
+
+    fn get_account_info(&self, params: &Value) -> Result<Value, RpcError> {
+        let pubkey = parse_pubkey(&params[0])?;
+        let encoding = params[1]["encoding"].as_str().unwrap_or("base64");
+        let data_slice = &params[1]["dataSlice"];
+        let ledger = self.ledger.lock().unwrap();
+        let value = ledger
+            .account(&pubkey)
+            .map(|a| encode_account_slice(&a, encoding, data_slice))
+            .unwrap_or(Value::Null);
+
+        Ok(json!({ "context": rpc_context(ledger.slot()), "value": value }))
+    }
+
+    fn get_multiple_accounts(&self, params: &Value) -> Result<Value, RpcError> {
+        let pubkeys = params[0]
+            .as_array()
+            .ok_or_else(|| RpcError::invalid_params("Invalid param: expected an array"))?
+            .iter()
+            .map(parse_pubkey)
+            .collect::<Result<Vec<Pubkey>, RpcError>>()?;
+        let encoding = params[1]["encoding"].as_str().unwrap_or("base64");
+        let data_slice = &params[1]["dataSlice"];
+        let ledger = self.ledger.lock().unwrap();
+        let values: Vec<Value> = pubkeys
+            .iter()
+            .map(|p| {
+                ledger
+                    .account(p)
+                    .map(|a| encode_account_slice(&a, encoding, data_slice))
+                    .unwrap_or(Value::Null)
+            })
+            .collect();
+
+        Ok(json!({ "context": rpc_context(ledger.slot()), "value": values }))
+    }
+
+    fn get_token_accounts_by_owner(&self, params: &Value) -> Result<Value, RpcError> {
+        let owner = parse_pubkey(&params[0])?;
+        let filter = &params[1];
+        let mint = match filter.get("mint") {
+            Some(mint) => Some(parse_pubkey(mint)?),
+            None => None,
+        };
+        let encoding = params[2]["encoding"].as_str().unwrap_or("base64");
+        let data_slice = &params[2]["dataSlice"];
+
+        let ledger = self.ledger.lock().unwrap();
+        let program_ids: Vec<Pubkey> = match filter.get("programId") {
+            Some(program_id) => vec![parse_pubkey(program_id)?],
+            None => {
+                let mint = mint.ok_or_else(|| {
+                    RpcError::invalid_params("Invalid param: expected a mint or programId")
+                })?;
+                let mint_owner = ledger.account(&mint).map(|a| a.owner).ok_or_else(|| {
+                    RpcError::invalid_params("Invalid param: could not find mint")
+                })?;
+                vec![mint_owner]
+            }
+        };
+        if let Some(program_id) = program_ids
+            .iter()
+            .find(|p| !is_token_program(&p.to_string()))
+        {
+            return Err(RpcError::invalid_params(format!(
+                "Invalid param: unrecognized Token program id {}",
+                program_id
+            )));
+        }
+
+        let values: Vec<Value> = program_ids
+            .iter()
+            .flat_map(|program_id| ledger.program_accounts(program_id))
+            .filter(|(_, account)| {
+                account.data.len() >= TOKEN_ACCOUNT_LEN
+                    && account.data[32..64] == owner.to_bytes()
+                    && mint.is_none_or(|m| account.data[0..32] == m.to_bytes())
+            })
+            .map(|(pubkey, account)| {
+                json!({
+                    "pubkey": pubkey.to_string(),
+                    "account": encode_account_slice(&account, encoding, data_slice)
+                })
+            })
+            .collect();
+
+        Ok(json!({ "context": rpc_context(ledger.slot()), "value": values }))
+    }
+
+    fn get_latest_blockhash(&self) -> Result<Value, RpcError> {
+        let ledger = self.ledger.lock().unwrap();
+        let (blockhash, last_valid_block_height) = ledger.latest_blockhash();
+
+        Ok(json!({
+            "context": rpc_context(ledger.slot()),
+            "value": {
+                "blockhash": blockhash.to_string(),
+                "lastValidBlockHeight": last_valid_block_height
+            }
+        }))
+    }
+
+    fn get_minimum_balance(&self, params: &Value) -> Result<Value, RpcError> {
+        let data_len = params[0]
+            .as_u64()
+            .ok_or_else(|| RpcError::invalid_params("Invalid param: expected a data length"))?;
+        let ledger = self.ledger.lock().unwrap();
+
+        Ok(json!(
+            ledger.minimum_balance_for_rent_exemption(data_len as usize)
+        ))
+    }
+
+    fn get_signature_statuses(&self, params: &Value) -> Result<Value, RpcError> {
+        let signatures = params[0]
+            .as_array()
+            .ok_or_else(|| RpcError::invalid_params("Invalid param: expected an array"))?;
+        let ledger = self.ledger.lock().unwrap();
+        let statuses: Vec<Value> = signatures
+            .iter()
+            .map(|s| {
+                s.as_str()
+                    .and_then(|s| s.parse::<Signature>().ok())
+                    .and_then(|s| ledger.signature_status(&s))
+                    .map(|status| {
+                        json!({
+                            "slot": status.slot,
+                            "confirmations": null,
+                            "err": status.err,
+                            "confirmationStatus": "finalized"
+                        })
+                    })
+                    .unwrap_or(Value::Null)
+            })
+            .collect();
+
+        Ok(json!({ "context": rpc_context(ledger.slot()), "value": statuses }))
+    }
+
+    fn request_airdrop(&self, params: &Value) -> Result<Value, RpcError> {
+        let pubkey = parse_pubkey(&params[0])?;
+        let lamports = params[1]
+            .as_u64()
+            .ok_or_else(|| RpcError::invalid_params("Invalid param: expected lamports"))?;
+
+        let signature = self.airdrop(&pubkey, lamports).map_err(|err| RpcError {
+            code: -32603,
+            message: format!("Airdrop failed: {}", err),
+        })?;
+
+        Ok(json!(signature.to_string()))
+    }
+
+    fn send_transaction(&self, params: &Value) -> Result<Value, RpcError> {
+        let encoded = params[0]
+            .as_str()
+            .ok_or_else(|| RpcError::invalid_params("Invalid param: expected a transaction"))?;
+        let config = &params[1];
+        let skip_preflight = config["skipPreflight"].as_bool().unwrap_or(false);
+        let txn = decode_transaction(encoded, config["encoding"].as_str().unwrap_or("base58"))?;
+
+        let mut ledger = self.ledger.lock().unwrap();
+        let simulation_failed = |err: String| RpcError {
+            code: -32002,
+            message: format!("Transaction simulation failed: {}", err),
+        };
+
+        if !skip_preflight {
+            let simulation = ledger.simulate(&txn).map_err(simulation_failed)?;
+            if let Some(err) = simulation.err {
+                return Err(simulation_failed(err));
+            }
+        }
+
+        let execution = ledger.process(&txn).map_err(simulation_failed)?;
+        let slot = ledger.slot();
+        let status = ledger
+            .signature_status(&execution.signature)
+            .expect("processed transaction has a status");
+        let changed_accounts: Vec<(Pubkey, Option<Account>)> = execution
+            .changed_accounts()
+            .into_iter()
+            .map(|p| (p, ledger.account(&p)))
+            .collect();
+        drop(ledger);
+
+        self.notify_signature(&execution.signature, &status);
+        for (pubkey, account) in changed_accounts {
+            self.notify_account(&pubkey, account.as_ref(), slot);
+        }
+
+        Ok(json!(execution.signature.to_string()))
+    }
+
+    fn simulate_transaction(&self, params: &Value) -> Result<Value, RpcError> {
+        let encoded = params[0]
+            .as_str()
+            .ok_or_else(|| RpcError::invalid_params("Invalid param: expected a transaction"))?;
+        let config = &params[1];
+        let txn = decode_transaction(encoded, config["encoding"].as_str().unwrap_or("base58"))?;
+        let addresses = match config["accounts"]["addresses"].as_array() {
+            Some(addresses) => addresses
+                .iter()
+                .map(parse_pubkey)
+                .collect::<Result<Vec<Pubkey>, RpcError>>()?,
+            None => Vec::new(),
+        };
+        let account_encoding = config["accounts"]["encoding"].as_str().unwrap_or("base64");
+
+        let ledger = self.ledger.lock().unwrap();
+        let slot = ledger.slot();
+        let value = match ledger.simulate(&txn) {
+            Ok(execution) => {
+                let accounts: Vec<Value> = addresses
+                    .iter()
+                    .map(|address| {
+                        execution
+                            .post_accounts
+                            .iter()
+                            .find(|(pubkey, _)| pubkey == address)
+                            .map(|(_, account)| account.clone())
+                            .or_else(|| ledger.account(address))
+                            .filter(|account| account.lamports > 0)
+                            .map(|account| encode_account(&account, account_encoding))
+                            .unwrap_or(Value::Null)
+                    })
+                    .collect();
+
+                json!({
+                    "err": execution.err,
+                    "logs": execution.logs,
+                    "accounts": accounts,
+                    "unitsConsumed": execution.compute_units_consumed,
+                    "returnData": null
+                })
+            }
+            Err(err) => json!({
+                "err": err,
+                "logs": [],
+                "accounts": null,
+                "unitsConsumed": 0,
+                "returnData": null
+            }),
+        };
+
+        Ok(json!({ "context": rpc_context(slot), "value": value }))
+    }
+
+    fn get_fee_for_message(&self, params: &Value) -> Result<Value, RpcError> {
+        let message: VersionedMessage = params[0]
+            .as_str()
+            .and_then(|m| general_purpose::STANDARD.decode(m).ok())
+            .and_then(|bytes| bincode::deserialize(&bytes).ok())
+            .ok_or_else(|| RpcError::invalid_params("Invalid param: not a valid message"))?;
+        let fee = LAMPORTS_PER_SIGNATURE * message.header().num_required_signatures as u64;
+        let ledger = self.ledger.lock().unwrap();
+
+        Ok(json!({ "context": rpc_context(ledger.slot()), "value": fee }))
+    }
+
+    fn get_transaction(&self, params: &Value) -> Result<Value, RpcError> {
+        let signature = params[0]
+            .as_str()
+            .and_then(|s| s.parse::<Signature>().ok())
+            .ok_or_else(|| RpcError::invalid_params("Invalid param: not a valid signature"))?;
+        let ledger = self.ledger.lock().unwrap();
+        let Some(processed) = ledger.transaction(&signature) else {
+            return Ok(Value::Null);
+        };
+
+        let txn = &processed.transaction;
+        let execution = &processed.execution;
+        let message = &txn.message;
+        let version = match message {
+            VersionedMessage::Legacy(_) => json!("legacy"),
+            VersionedMessage::V0(_) => json!(0),
+        };
+        let instructions: Vec<Value> = message
+            .instructions()
+            .iter()
+            .map(|i| {
+                json!({
+                    "programIdIndex": i.program_id_index,
+                    "accounts": i.accounts,
+                    "data": bs58::encode(&i.data).into_string(),
+                    "stackHeight": null
+                })
+            })
+            .collect();
+        let balances = |accounts: &[(Pubkey, Account)]| -> Vec<u64> {
+            accounts.iter().map(|(_, a)| a.lamports).collect()
+        };
+
+        Ok(json!({
+            "slot": processed.slot,
+            "blockTime": null,
+            "version": version,
+            "transaction": {
+                "signatures": txn.signatures.iter().map(|s| s.to_string()).collect::<Vec<_>>(),
+                "message": {
+                    "accountKeys": message
+                        .static_account_keys()
+                        .iter()
+                        .map(|k| k.to_string())
+                        .collect::<Vec<_>>(),
+                    "header": {
+                        "numRequiredSignatures": message.header().num_required_signatures,
+                        "numReadonlySignedAccounts": message.header().num_readonly_signed_accounts,
+                        "numReadonlyUnsignedAccounts": message.header().num_readonly_unsigned_accounts
+                    },
+                    "recentBlockhash": message.recent_blockhash().to_string(),
+                    "instructions": instructions
+                }
+            },
+            "meta": {
+                "err": execution.err,
+                "status": match &execution.err {
+                    Some(err) => json!({ "Err": err }),
+                    None => json!({ "Ok": null }),
+                },
+                "fee": execution.fee,
+                "preBalances": balances(&execution.pre_accounts),
+                "postBalances": balances(&execution.post_accounts),
+                "preTokenBalances": token_balances(ledger.as_ref(), &execution.pre_accounts),
+                "postTokenBalances": token_balances(ledger.as_ref(), &execution.post_accounts),
+                "innerInstructions": [],
+                "logMessages": execution.logs,
+                "computeUnitsConsumed": execution.compute_units_consumed,
+                "loadedAddresses": { "writable": [], "readonly": [] },
+                "rewards": []
+            }
+        }))
+    }
+}
+
+fn is_token_program(program_id: &str) -> bool {
+    program_id == TOKEN_PROGRAM || program_id == TOKEN_2022_PROGRAM
+}
+
+/// The `preTokenBalances`/`postTokenBalances` entries for the SPL token accounts among `accounts`.
+fn token_balances(ledger: &dyn Ledger, accounts: &[(Pubkey, Account)]) -> Vec<Value> {
+    accounts
+        .iter()
+        .enumerate()
+        .filter(|(_, (_, account))| {
+            is_token_program(&account.owner.to_string()) && account.data.len() >= TOKEN_ACCOUNT_LEN
+        })
+        .map(|(index, (_, account))| {
+            let data = &account.data;
+            let mint = Pubkey::try_from(&data[0..32]).expect("slice is 32 bytes");
+            let owner = Pubkey::try_from(&data[32..64]).expect("slice is 32 bytes");
+            let amount = u64::from_le_bytes(data[64..72].try_into().expect("slice is 8 bytes"));
+            let decimals = ledger
+                .account(&mint)
+                .filter(|m| m.data.len() >= MINT_LEN)
+                .map(|m| m.data[44])
+                .unwrap_or(0);
+            let ui_amount = amount as f64 / 10f64.powi(decimals as i32);
+
+            json!({
+                "accountIndex": index,
+                "mint": mint.to_string(),
+                "owner": owner.to_string(),
+                "programId": account.owner.to_string(),
+                "uiTokenAmount": {
+                    "amount": amount.to_string(),
+                    "decimals": decimals,
+                    "uiAmount": ui_amount,
+                    "uiAmountString": ui_amount.to_string()
+                }
+            })
+        })
+        .collect()
+}
+
+fn decode_transaction(encoded: &str, encoding: &str) -> Result<VersionedTransaction, RpcError> {
+    let bytes = match encoding {
+        "base64" => general_purpose::STANDARD
+            .decode(encoded)
+            .map_err(|err| RpcError::invalid_params(format!("invalid base64: {}", err)))?,
+        _ => bs58::decode(encoded)
+            .into_vec()
+            .map_err(|err| RpcError::invalid_params(format!("invalid base58: {}", err)))?,
+    };
+
+    bincode::deserialize(&bytes)
+        .map_err(|err| RpcError::invalid_params(format!("invalid transaction: {}", err)))
+}
@@ -0,0 +1,195 @@
+use serde_json::{Value, json};
+use solana_sdk::{account::Account, pubkey::Pubkey, signature::Signature};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::{
+    MockNode,
+    bank::system_account,
+    ledger::SignatureStatus,
+    rpc::{encode_account, rpc_context},
+};
+
+struct SignatureSubscription {
+    id: u64,
+    signature: Signature,
+    client: UnboundedSender<String>,
+}
+
+struct AccountSubscription {
+    id: u64,
+    pubkey: Pubkey,
+    encoding: String,
+    client: UnboundedSender<String>,
+}
+
+#[derive(Default)]
+pub(crate) struct Subscriptions {
+    next_id: u64,
+    signatures: Vec<SignatureSubscription>,
+    accounts: Vec<AccountSubscription>,
+}
+
+impl Subscriptions {
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        self.next_id
+    }
+
+    fn remove(&mut self, id: u64) -> bool {
+        let before = self.signatures.len() + self.accounts.len();
+        self.signatures.retain(|s| s.id != id);
+        self.accounts.retain(|s| s.id != id);
+        before != self.signatures.len() + self.accounts.len()
+    }
+}
+
+impl MockNode {
+    /// Opens a subscription connection, the in-process equivalent of a websocket.
+    /// Requests are written to the returned sender, responses and notifications
+    /// arrive on the returned receiver.
+    pub fn connect(&self) -> (UnboundedSender<String>, UnboundedReceiver<String>) {
+        let (request_tx, mut request_rx) = mpsc::unbounded_channel::<String>();
+        let (response_tx, response_rx) = mpsc::unbounded_channel::<String>();
+
+        let node = self.clone();
+        tokio::spawn(async move {
+            while let Some(message) = request_rx.recv().await {
+                let response = match serde_json::from_str::<Value>(&message) {
+                    Ok(request) => node.handle_subscription_request(&request, &response_tx),
+                    Err(err) => json!({
+                        "jsonrpc": "2.0",
+                        "id": null,
+                        "error": { "code": -32700, "message": format!("Parse error: {}", err) }
+                    }),
+                };
+                if response_tx.send(response.to_string()).is_err() {
+                    break;
+                }
+                node.flush_settled_signatures();
+            }
+        });
+
+        (request_tx, response_rx)
+    }
+
+    fn handle_subscription_request(
+        &self,
+        request: &Value,
+        client: &UnboundedSender<String>,
+    ) -> Value {
+        let id = request["id"].clone();
+        let params = &request["params"];
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+
+        let result = match request["method"].as_str().unwrap_or_default() {
+            "signatureSubscribe" => {
+                match params[0].as_str().and_then(|s| s.parse::<Signature>().ok()) {
+                    Some(signature) => {
+                        let sub_id = subscriptions.next_id();
+                        subscriptions.signatures.push(SignatureSubscription {
+                            id: sub_id,
+                            signature,
+                            client: client.clone(),
+                        });
+                        Ok(json!(sub_id))
+                    }
+                    None => Err("Invalid signature"),
+                }
+            }
+            "accountSubscribe" => match params[0].as_str().and_then(|s| s.parse::<Pubkey>().ok()) {
+                Some(pubkey) => {
+                    let sub_id = subscriptions.next_id();
+                    subscriptions.accounts.push(AccountSubscription {
+                        id: sub_id,
+                        pubkey,
+                        encoding: params[1]["encoding"]
+                            .as_str()
+                            .unwrap_or("base64")
+                            .to_owned(),
+                        client: client.clone(),
+                    });
+                    Ok(json!(sub_id))
+                }
+                None => Err("Invalid pubkey"),
+            },
+            "signatureUnsubscribe" | "accountUnsubscribe" => match params[0].as_u64() {
+                Some(sub_id) => Ok(json!(subscriptions.remove(sub_id))),
+                None => Err("Invalid subscription id"),
+            },
+            _ => Err("Method not found"),
+        };
+
+        match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "result": result, "id": id }),
+            Err(message) => json!({
+                "jsonrpc": "2.0",
+                "error": { "code": -32602, "message": message },
+                "id": id
+            }),
+        }
+    }
+
+    /// Notifies signature subscribers whose transaction already landed,
+    /// e.g. because it was sent before the subscription was made.
+    fn flush_settled_signatures(&self) {
+        let ledger = self.ledger.lock().unwrap();
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions
+            .signatures
+            .retain(|sub| match ledger.signature_status(&sub.signature) {
+                Some(status) => {
+                    send_signature_notification(sub, &status);
+                    false
+                }
+                None => true,
+            });
+    }
+
+    pub(crate) fn notify_signature(&self, signature: &Signature, status: &SignatureStatus) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions.signatures.retain(|sub| {
+            if &sub.signature != signature {
+                return true;
+            }
+            send_signature_notification(sub, status);
+            false
+        });
+    }
+
+    pub(crate) fn notify_account(&self, pubkey: &Pubkey, account: Option<&Account>, slot: u64) {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+        subscriptions.accounts.retain(|sub| {
+            if &sub.pubkey != pubkey {
+                return true;
+            }
+            let empty = system_account(0);
+            let notification = json!({
+                "jsonrpc": "2.0",
+                "method": "accountNotification",
+                "params": {
+                    "result": {
+                        "context": rpc_context(slot),
+                        "value": encode_account(account.unwrap_or(&empty), &sub.encoding),
+                    },
+                    "subscription": sub.id
+                }
+            });
+            sub.client.send(notification.to_string()).is_ok()
+        });
+    }
+}
+
+fn send_signature_notification(sub: &SignatureSubscription, status: &SignatureStatus) {
+    let notification = json!({
+        "jsonrpc": "2.0",
+        "method": "signatureNotification",
+        "params": {
+            "result": {
+                "context": rpc_context(status.slot),
+                "value": { "err": status.err }
+            },
+            "subscription": sub.id
+        }
+    });
+    let _ = sub.client.send(notification.to_string());
+}
@@ -0,0 +1,301 @@
+use std::{
+    collections::{HashMap, HashSet},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use litesvm::LiteSVM;
+use solana_sdk::{
+    account::Account,
+    clock::Clock,
+    hash::Hash,
+    program_pack::Pack,
+    pubkey::Pubkey,
+    signature::Signature,
+    transaction::{TransactionError, VersionedTransaction},
+};
+use spl_token_interface::{native_mint, state::Mint};
+
+use crate::{
+    LAMPORTS_PER_SIGNATURE,
+    bank::system_account,
+    ledger::{Execution, Ledger, ProcessedTransaction, SignatureStatus},
+};
+
+const MAX_PROCESSING_AGE: usize = 150;
+
+/// Ledger running transactions in an embedded LiteSVM, so besides the system
+/// program it knows the Token, Token-2022 and associated token account programs.
+pub struct SvmLedger {
+    svm: LiteSVM,
+    /// Every account that was ever set or written, LiteSVM can't list its accounts.
+    touched: HashSet<Pubkey>,
+    blockhashes: Vec<Hash>,
+    statuses: HashMap<Signature, SignatureStatus>,
+    transactions: HashMap<Signature, ProcessedTransaction>,
+    slot: u64,
+}
+
+impl SvmLedger {
+    pub fn new() -> Self {
+        // Blockhash age is checked here, LiteSVM only accepts its latest blockhash.
+        let mut svm = LiteSVM::new().with_blockhash_check(false);
+
+        // Every cluster has the wrapped SOL mint, LiteSVM only ships the token programs.
+        let mut native_mint = vec![0; Mint::LEN];
+        Mint {
+            decimals: native_mint::DECIMALS,
+            is_initialized: true,
+            ..Mint::default()
+        }
+        .pack_into_slice(&mut native_mint);
+        let native_mint_account = Account {
+            lamports: svm.minimum_balance_for_rent_exemption(Mint::LEN),
+            data: native_mint,
+            owner: spl_token_interface::ID,
+            executable: false,
+            rent_epoch: 0,
+        };
+        if let Err(err) = svm.set_account(native_mint::ID, native_mint_account) {
+            eprintln!("Failed to create the native mint: {}", err);
+        }
+
+        let blockhashes = vec![svm.latest_blockhash()];
+
+        let mut ledger = Self {
+            svm,
+            touched: HashSet::new(),
+            blockhashes,
+            statuses: HashMap::new(),
+            transactions: HashMap::new(),
+            slot: 1,
+        };
+        ledger.sync_clock();
+
+        ledger
+    }
+
+    /// Programs copied from a cluster check timestamps against the clock, so it
+    /// follows the wall clock instead of starting at 1970.
+    fn sync_clock(&mut self) {
+        let mut clock = self.svm.get_sysvar::<Clock>();
+        clock.unix_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or_default();
+        self.svm.set_sysvar(&clock);
+    }
+
+    fn is_blockhash_valid(&self, blockhash: &Hash) -> bool {
+        self.blockhashes
+            .iter()
+            .rev()
+            .take(MAX_PROCESSING_AGE)
+            .any(|h| h == blockhash)
+    }
+
+    fn advance_slot(&mut self) {
+        self.slot += 1;
+        self.svm.warp_to_slot(self.slot);
+        self.sync_clock();
+        self.svm.expire_blockhash();
+        self.blockhashes.push(self.svm.latest_blockhash());
+        if self.blockhashes.len() > MAX_PROCESSING_AGE * 2 {
+            self.blockhashes.remove(0);
+        }
+    }
+
+    fn check_admission(&self, txn: &VersionedTransaction) -> Result<(), String> {
+        if !self.is_blockhash_valid(txn.message.recent_blockhash()) {
+            return Err("BlockhashNotFound".to_owned());
+        }
+        if self.statuses.contains_key(&txn.signatures[0]) {
+            return Err("AlreadyProcessed".to_owned());
+        }
+
+        Ok(())
+    }
+
+    fn accounts_of(&self, keys: &[Pubkey]) -> Vec<(Pubkey, Account)> {
+        keys.iter()
+            .map(|k| {
+                let account = self.svm.get_account(k).unwrap_or_else(|| system_account(0));
+                (*k, account)
+            })
+            .collect()
+    }
+}
+
+impl Default for SvmLedger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Only instruction errors land on chain, everything else makes a cluster drop the transaction.
+fn is_included(err: &TransactionError) -> bool {
+    matches!(
+        err,
+        TransactionError::InstructionError(..) | TransactionError::InsufficientFundsForRent { .. }
+    )
+}
+
+fn charge_fee(accounts: &mut [(Pubkey, Account)], fee: u64) {
+    if let Some((_, payer)) = accounts.first_mut() {
+        payer.lamports = payer.lamports.saturating_sub(fee);
+    }
+}
+
+impl Ledger for SvmLedger {
+    fn slot(&self) -> u64 {
+        self.slot
+    }
+
+    fn block_height(&self) -> u64 {
+        self.slot
+    }
+
+    fn latest_blockhash(&self) -> (Hash, u64) {
+        (
+            self.svm.latest_blockhash(),
+            self.slot + MAX_PROCESSING_AGE as u64,
+        )
+    }
+
+    fn minimum_balance_for_rent_exemption(&self, data_len: usize) -> u64 {
+        self.svm.minimum_balance_for_rent_exemption(data_len)
+    }
+
+    fn account(&self, pubkey: &Pubkey) -> Option<Account> {
+        self.svm.get_account(pubkey).filter(|a| a.lamports > 0)
+    }
+
+    fn program_accounts(&self, program_id: &Pubkey) -> Vec<(Pubkey, Account)> {
+        self.touched
+            .iter()
+            .filter_map(|p| self.account(p).map(|a| (*p, a)))
+            .filter(|(_, a)| a.owner == *program_id)
+            .collect()
+    }
+
+    fn set_account(&mut self, pubkey: Pubkey, account: Account) {
+        if let Err(err) = self.svm.set_account(pubkey, account) {
+            eprintln!("Failed to set account {}: {}", pubkey, err);
+            return;
+        }
+        self.touched.insert(pubkey);
+    }
+
+    fn airdrop(&mut self, pubkey: &Pubkey, lamports: u64) -> Result<Signature, String> {
+        let meta = self
+            .svm
+            .airdrop(pubkey, lamports)
+            .map_err(|failed| failed.err.to_string())?;
+
+        self.statuses.insert(
+            meta.signature,
+            SignatureStatus {
+                slot: self.slot,
+                err: None,
+            },
+        );
+        self.touched.insert(*pubkey);
+        self.advance_slot();
+
+        Ok(meta.signature)
+    }
+
+    fn signature_status(&self, signature: &Signature) -> Option<SignatureStatus> {
+        self.statuses.get(signature).cloned()
+    }
+
+    fn transaction(&self, signature: &Signature) -> Option<ProcessedTransaction> {
+        self.transactions.get(signature).cloned()
+    }
+
+    fn simulate(&self, txn: &VersionedTransaction) -> Result<Execution, String> {
+        self.check_admission(txn)?;
+
+        let keys = txn.message.static_account_keys().to_vec();
+        let fee = LAMPORTS_PER_SIGNATURE * txn.signatures.len() as u64;
+        let pre_accounts = self.accounts_of(&keys);
+        let mut post_accounts = pre_accounts.clone();
+
+        let (err, meta) = match self.svm.simulate_transaction(txn.clone()) {
+            Ok(info) => {
+                for (pubkey, account) in info.post_accounts {
+                    if let Some((_, post)) = post_accounts.iter_mut().find(|(k, _)| *k == pubkey) {
+                        *post = Account::from(account);
+                    }
+                }
+                (None, info.meta)
+            }
+            Err(failed) if is_included(&failed.err) => {
+                charge_fee(&mut post_accounts, fee);
+                (Some(failed.err.to_string()), failed.meta)
+            }
+            Err(failed) => return Err(failed.err.to_string()),
+        };
+
+        Ok(Execution {
+            signature: txn.signatures[0],
+            fee,
+            err,
+            logs: meta.logs,
+            compute_units_consumed: meta.compute_units_consumed,
+            pre_accounts,
+            post_accounts,
+        })
+    }
+
+    fn process(&mut self, txn: &VersionedTransaction) -> Result<Execution, String> {
+        self.check_admission(txn)?;
+
+        let keys = txn.message.static_account_keys().to_vec();
+        let fee = LAMPORTS_PER_SIGNATURE * txn.signatures.len() as u64;
+        let pre_accounts = self.accounts_of(&keys);
+
+        let (err, meta) = match self.svm.send_transaction(txn.clone()) {
+            Ok(meta) => (None, meta),
+            Err(failed) if is_included(&failed.err) => {
+                // LiteSVM rolls back failed transactions completely, a cluster still takes the fee.
+                let mut payer = pre_accounts[0].clone();
+                charge_fee(std::slice::from_mut(&mut payer), fee);
+                if let Err(err) = self.svm.set_account(payer.0, payer.1) {
+                    eprintln!("Failed to charge the fee: {}", err);
+                }
+                (Some(failed.err.to_string()), failed.meta)
+            }
+            Err(failed) => return Err(failed.err.to_string()),
+        };
+
+        let execution = Execution {
+            signature: txn.signatures[0],
+            fee,
+            err,
+            logs: meta.logs,
+            compute_units_consumed: meta.compute_units_consumed,
+            post_accounts: self.accounts_of(&keys),
+            pre_accounts,
+        };
+
+        self.statuses.insert(
+            execution.signature,
+            SignatureStatus {
+                slot: self.slot,
+                err: execution.err.clone(),
+            },
+        );
+        self.transactions.insert(
+            execution.signature,
+            ProcessedTransaction {
+                slot: self.slot,
+                transaction: txn.clone(),
+                execution: execution.clone(),
+            },
+        );
+        self.touched.extend(keys);
+        self.advance_slot();
+
+        Ok(execution)
+    }
+}